@@ -0,0 +1,151 @@
+//! Throughput of a scan of this machine, to compare across changes
+//!
+//! ```sh
+//! cargo bench --bench scan
+//! SKANNER_BENCH_PORTS=2000 SKANNER_BENCH_RUNS=20 cargo bench --bench scan
+//! ```
+//!
+//! Half of the ports scanned are open on loopback listeners, the other half
+//! closed, and one of them drops the connections as a firewall would. Each run
+//! scans all of them, the fastest, median and slowest runs being printed with
+//! the ports probed per second of the median. The dropped port waits for the
+//! whole timeout, the shorter one of loopback scans.
+//!
+//! The HTTP probes are timed on web servers redirecting `/` to a login page on
+//! the same port, reached on `SKANNER_BENCH_HOSTS` loopback addresses. One
+//! keeps its connections open, the redirect being followed on the connection
+//! of the probe, the other closes them, each redirect opening a new one.
+//!
+//! criterion isn't a dependency of the crate, the runs are timed by hand and
+//! built with `harness = false`.
+
+use std::io::{Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::time::{Duration, Instant};
+
+use socket2::{Domain, Socket, Type};
+use tokio::runtime::Runtime;
+
+use skanner::port::PortState;
+use skanner::Scanner;
+
+const DEFAULT_PORTS: usize = 500;
+const DEFAULT_HOSTS: usize = 200;
+const DEFAULT_RUNS: usize = 10;
+
+fn env_or(name: &str, default: usize) -> usize {
+  std::env::var(name).ok().and_then(|value| value.parse().ok()).unwrap_or(default)
+}
+
+/// Listener whose backlog is full, the SYNs it gets being dropped, with the connection filling it
+fn dropping_listener() -> (Socket, TcpStream) {
+  let socket = Socket::new(Domain::IPV4, Type::STREAM, None).unwrap();
+  socket.bind(&"127.0.0.1:0".parse::<SocketAddr>().unwrap().into()).unwrap();
+  socket.listen(0).unwrap();
+  let address = socket.local_addr().unwrap().as_socket().unwrap();
+
+  // Never accepted, a backlog of 0 holds this single connection
+  let filling = TcpStream::connect(address).unwrap();
+  (socket, filling)
+}
+
+/// Web server on every loopback address redirecting `/` to `/login`, keeping its connections open or not
+/// Return its port
+fn redirecting_server(keep_alive: bool) -> u16 {
+  let listener = TcpListener::bind("0.0.0.0:0").unwrap();
+  let port = listener.local_addr().unwrap().port();
+
+  std::thread::spawn(move || {
+    for stream in listener.incoming() {
+      let Ok(mut stream) = stream else { continue };
+      std::thread::spawn(move || {
+        let mut request = [0_u8; 2048];
+        while let Ok(size) = stream.read(&mut request) {
+          if size == 0 {
+            break;
+          }
+          let (status, location, body) = match request.starts_with(b"GET / ") {
+            true => ("302 Found", "Location: /login\r\n", ""),
+            false => ("200 OK", "", "<title>Login</title>"),
+          };
+          let connection = if keep_alive { "keep-alive" } else { "close" };
+          let response = format!("HTTP/1.1 {}\r\n{}Content-Length: {}\r\nConnection: {}\r\n\r\n{}", status, location, body.len(), connection, body);
+          if stream.write_all(response.as_bytes()).is_err() || !keep_alive {
+            break;
+          }
+        }
+      });
+    }
+  });
+
+  port
+}
+
+/// Time `runs` scans, sorted from the fastest
+fn time_runs<B: Fn() -> Scanner, C: Fn(&Scanner)>(runtime: &Runtime, runs: usize, build: B, check: C) -> Vec<Duration> {
+  let mut times = (0..runs)
+    .map(|_| {
+      let mut scanner = build();
+      let start = Instant::now();
+      runtime.block_on(scanner.scan());
+      let elapsed = start.elapsed();
+
+      check(&scanner);
+      elapsed
+    })
+    .collect::<Vec<Duration>>();
+  times.sort();
+
+  times
+}
+
+fn print_times(name: &str, probes: usize, times: &[Duration]) {
+  let median = times[times.len() / 2];
+  println!("{}, {} runs", name, times.len());
+  println!("  fastest {:?}, median {:?}, slowest {:?}", times[0], median, times[times.len() - 1]);
+  println!("  {:.0} probes per second", probes as f64 / median.as_secs_f64());
+}
+
+fn main() {
+  let count = env_or("SKANNER_BENCH_PORTS", DEFAULT_PORTS).max(2);
+  let hosts = env_or("SKANNER_BENCH_HOSTS", DEFAULT_HOSTS).clamp(1, 254);
+  let runs = env_or("SKANNER_BENCH_RUNS", DEFAULT_RUNS).max(1);
+  let runtime = Runtime::new().unwrap();
+
+  // Kept open for the whole benchmark, their connections completing in the backlog
+  let listeners = (0..count / 2).map(|_| TcpListener::bind("127.0.0.1:0").unwrap()).collect::<Vec<TcpListener>>();
+  // Bound then closed, so nothing listens on them
+  let closed = (0..count - listeners.len()).map(|_| TcpListener::bind("127.0.0.1:0").unwrap()).collect::<Vec<TcpListener>>();
+  let mut ports = listeners.iter().chain(&closed).map(|listener| listener.local_addr().unwrap().port()).collect::<Vec<u16>>();
+  drop(closed);
+  let (dropping, _filling) = dropping_listener();
+  ports.push(dropping.local_addr().unwrap().as_socket().unwrap().port());
+
+  let times = time_runs(
+    &runtime,
+    runs,
+    || Scanner::builder().ip("127.0.0.1").ports(ports.clone()).discovery(false).quiet(true).build().unwrap(),
+    |scanner| {
+      let open = scanner.results().iter().map(|result| result.open_ports.len()).sum::<usize>();
+      assert_eq!(open, listeners.len(), "every listener should be found open");
+      let filtered = scanner.results().iter().flat_map(|result| &result.filtered_ports).filter(|port| port.state == PortState::Filtered).count();
+      assert_eq!(filtered, 1, "the dropping listener should be found filtered");
+    },
+  );
+  print_times(&format!("scan of {} loopback ports", ports.len()), ports.len(), &times);
+
+  let target = format!("127.0.0.1-127.0.0.{}", hosts);
+  for (name, keep_alive) in [("redirect on the same connection", true), ("redirect on a new connection", false)] {
+    let port = redirecting_server(keep_alive);
+    let times = time_runs(
+      &runtime,
+      runs,
+      || Scanner::builder().ip(&target).ports(vec![port]).discovery(false).http(true).http_ports(vec![port]).quiet(true).build().unwrap(),
+      |scanner| {
+        let titles = scanner.results().iter().flat_map(|result| &result.open_ports).filter(|port| port.http.as_ref().is_some_and(|http| http.title.as_deref() == Some("Login"))).count();
+        assert_eq!(titles, hosts, "every redirect should be followed");
+      },
+    );
+    print_times(&format!("HTTP probes of {} hosts, {}", hosts, name), hosts, &times);
+  }
+}
@@ -0,0 +1,179 @@
+//! Discovery of the hosts on the link from the ARP table of the kernel
+//!
+//! A datagram sent to a host of a local network makes the kernel resolve its
+//! hardware address with ARP, the host being up once the table has a complete
+//! entry for it. No raw socket is needed, only the table (`/proc/net/arp`,
+//! on Linux), and the hosts off-link are still discovered with TCP.
+
+use std::collections::HashSet;
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+
+use crate::cidr::Cidr;
+use crate::connection::Source;
+
+/// ARP table of the kernel
+pub const ARP_TABLE: &str = "/proc/net/arp";
+
+/// Time between two reads of the ARP table while a host is being resolved
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Flag of an entry whose hardware address is known
+const COMPLETE: u32 = 0x2;
+
+/// Port of the datagram making the kernel resolve a host, the discard service
+const DISCARD_PORT: u16 = 9;
+
+/// How a host is checked to be up before its ports are scanned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum DiscoveryMethod {
+  /// TCP connection to the discovery ports
+  #[default]
+  Tcp,
+
+  /// ARP resolution for the hosts of a local network, TCP for the others
+  Arp,
+}
+
+/// Hosts of the local networks resolved by the kernel, shared by every host of a scan
+#[derive(Debug)]
+pub struct ArpTable {
+  networks: Vec<Cidr>,
+  source: Source,
+  resolved: Mutex<(Instant, HashSet<Ipv4Addr>)>,
+}
+
+impl ArpTable {
+  /// Read the table and the networks of the interfaces, failing when there is no table to read
+  pub fn open(source: &Source) -> io::Result<Self> {
+    let resolved = parse_arp_table(&std::fs::read_to_string(ARP_TABLE)?);
+
+    Ok(Self { networks: local_networks(), source: source.clone(), resolved: Mutex::new((Instant::now(), resolved)) })
+  }
+
+  /// Whether `ip` is in the network of a local interface, ARP resolving it
+  pub fn on_link(&self, ip: Ipv4Addr) -> bool {
+    self.networks.iter().any(|network| network.contains(&IpAddr::V4(ip)))
+  }
+
+  /// Whether a host of a local network answers ARP within `timeout`
+  pub async fn is_up(&self, ip: Ipv4Addr, timeout: Duration) -> bool {
+    if self.resolved(ip) {
+      return true;
+    }
+
+    // Sending the datagram is what makes the kernel resolve the host, nothing needs to listen
+    let Ok(socket) = self.source.bind_udp(ip.into()) else { return false };
+    if socket.send_to(&[], SocketAddr::new(ip.into(), DISCARD_PORT)).await.is_err() {
+      return false;
+    }
+
+    let deadline = Instant::now() + timeout;
+    while Instant::now() < deadline {
+      tokio::time::sleep(POLL_INTERVAL).await;
+      if self.resolved(ip) {
+        return true;
+      }
+    }
+    false
+  }
+
+  /// Whether the table has a complete entry for `ip`, reading it again when older than `POLL_INTERVAL`
+  fn resolved(&self, ip: Ipv4Addr) -> bool {
+    let mut resolved = self.resolved.lock().unwrap();
+    if resolved.0.elapsed() >= POLL_INTERVAL {
+      if let Ok(table) = std::fs::read_to_string(ARP_TABLE) {
+        *resolved = (Instant::now(), parse_arp_table(&table));
+      }
+    }
+
+    resolved.1.contains(&ip)
+  }
+}
+
+/// Addresses of the complete entries of an ARP table in the format of `/proc/net/arp`
+fn parse_arp_table(table: &str) -> HashSet<Ipv4Addr> {
+  table
+    .lines()
+    .skip(1)
+    .filter_map(|line| {
+      let mut fields = line.split_whitespace();
+      let ip = fields.next()?.parse::<Ipv4Addr>().ok()?;
+      let flags = u32::from_str_radix(fields.nth(1)?.trim_start_matches("0x"), 16).ok()?;
+
+      (flags & COMPLETE != 0).then_some(ip)
+    })
+    .collect()
+}
+
+/// IPv4 networks of the interfaces that are up, loopback excluded
+#[cfg(unix)]
+pub fn local_networks() -> Vec<Cidr> {
+  let mut networks = Vec::new();
+  let mut addresses: *mut libc::ifaddrs = std::ptr::null_mut();
+  if unsafe { libc::getifaddrs(&mut addresses) } != 0 {
+    return networks;
+  }
+
+  let mut current = addresses;
+  while let Some(interface) = unsafe { current.as_ref() } {
+    current = interface.ifa_next;
+
+    let flags = interface.ifa_flags as libc::c_int;
+    if flags & libc::IFF_UP == 0 || flags & libc::IFF_LOOPBACK != 0 || interface.ifa_addr.is_null() || interface.ifa_netmask.is_null() {
+      continue;
+    }
+    if unsafe { (*interface.ifa_addr).sa_family } as libc::c_int != libc::AF_INET {
+      continue;
+    }
+
+    let (address, netmask) = unsafe { (*(interface.ifa_addr as *const libc::sockaddr_in), *(interface.ifa_netmask as *const libc::sockaddr_in)) };
+    let ip = Ipv4Addr::from(u32::from_be(address.sin_addr.s_addr));
+    let prefix = u32::from_be(netmask.sin_addr.s_addr).count_ones() as u8;
+    networks.push(Cidr { ip: ip.into(), prefix });
+  }
+
+  unsafe { libc::freeifaddrs(addresses) };
+  networks
+}
+
+/// The networks of the interfaces are only listed on unix systems
+#[cfg(not(unix))]
+pub fn local_networks() -> Vec<Cidr> {
+  Vec::new()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_arp_table() {
+    let table = "\
+IP address       HW type     Flags       HW address            Mask     Device
+192.168.1.1      0x1         0x2         02:fc:00:00:00:05     *        eth0
+192.168.1.20     0x1         0x0         00:00:00:00:00:00     *        eth0
+192.168.1.30     0x1         0x6         02:fc:00:00:00:07     *        eth0
+not an address   0x1         0x2         02:fc:00:00:00:08     *        eth0
+";
+
+    let resolved = parse_arp_table(table);
+    assert_eq!(resolved.len(), 2);
+    assert!(resolved.contains(&Ipv4Addr::new(192, 168, 1, 1)));
+    assert!(resolved.contains(&Ipv4Addr::new(192, 168, 1, 30)));
+  }
+
+  #[test]
+  fn test_local_networks() {
+    // Loopback is not on a link
+    assert!(local_networks().iter().all(|network| !network.contains(&IpAddr::V4(Ipv4Addr::LOCALHOST))));
+
+    let table = ArpTable { networks: vec!["192.168.1.0/24".parse().unwrap()], source: Source::default(), resolved: Mutex::new((Instant::now(), HashSet::new())) };
+    assert!(table.on_link(Ipv4Addr::new(192, 168, 1, 77)));
+    assert!(!table.on_link(Ipv4Addr::new(192, 168, 2, 1)));
+  }
+}
@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use tokio::io::AsyncReadExt;
+use tokio::net::TcpStream;
+
+/// Default time to wait for a service to send its banner or answer an HTTP request, once connected
+pub const BANNER_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Maximum number of bytes read from a banner
+pub const BANNER_SIZE: usize = 256;
+
+/// Read the banner sent by a service right after the connection, until its
+/// first line ends, the service closes the connection or the timeout
+/// What a slow service sent before the timeout is kept
+/// Return `None` when the service sent nothing printable before the timeout
+pub async fn grab_banner(stream: &mut TcpStream, timeout: Duration) -> Option<String> {
+  let deadline = tokio::time::Instant::now() + timeout;
+  let mut buffer = [0_u8; BANNER_SIZE];
+  let mut size = 0;
+
+  while size < BANNER_SIZE && !buffer[..size].contains(&b'\n') {
+    match tokio::time::timeout_at(deadline, stream.read(&mut buffer[size..])).await {
+      Ok(Ok(0)) | Ok(Err(_)) | Err(_) => break,
+      Ok(Ok(read)) => size += read,
+    }
+  }
+
+  let banner = sanitize(&buffer[..size]);
+  if banner.is_empty() {
+    None
+  } else {
+    Some(banner)
+  }
+}
+
+/// Keep only the printable characters of a banner
+/// Whitespaces (new lines included) become a single space
+pub fn sanitize(bytes: &[u8]) -> String {
+  let text = String::from_utf8_lossy(bytes);
+
+  text
+    .split(|c: char| c.is_whitespace())
+    .map(|word| word.chars().filter(|c| !c.is_control() && *c != char::REPLACEMENT_CHARACTER).collect::<String>())
+    .filter(|word| !word.is_empty())
+    .collect::<Vec<String>>()
+    .join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::Write;
+  use std::net::TcpListener;
+
+  #[test]
+  fn test_sanitize() {
+    assert_eq!(sanitize(b"SSH-2.0-OpenSSH_8.9p1 Ubuntu-3\r\n"), "SSH-2.0-OpenSSH_8.9p1 Ubuntu-3");
+    assert_eq!(sanitize(b"220 \x00\x01mail\x7f ready\r\n\r\n"), "220 mail ready");
+    assert_eq!(sanitize(b"\xff\xfe\x00"), "");
+  }
+
+  #[tokio::test]
+  async fn test_grab_banner() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let handle = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      stream.write_all(b"SSH-2.0-OpenSSH_8.9\r\n").unwrap();
+    });
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    assert_eq!(grab_banner(&mut stream, BANNER_TIMEOUT).await, Some("SSH-2.0-OpenSSH_8.9".to_string()));
+    handle.join().unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_grab_banner_slow_service() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // A byte every 30ms, the line never ending before the timeout
+    let handle = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      for byte in b"220 mail.example.com ESMTP\r\n" {
+        if stream.write_all(&[*byte]).is_err() {
+          break;
+        }
+        std::thread::sleep(Duration::from_millis(30));
+      }
+    });
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let start = std::time::Instant::now();
+    let banner = grab_banner(&mut stream, Duration::from_millis(200)).await.unwrap();
+
+    assert!(start.elapsed() < Duration::from_millis(400), "{:?}", start.elapsed());
+    assert!(banner.len() > 1 && "220 mail.example.com ESMTP".starts_with(&banner), "{}", banner);
+    drop(stream);
+    handle.join().unwrap();
+
+    // Given the time, the whole line is read across the chunks
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let handle = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      for chunk in [&b"SSH-2.0-"[..], b"OpenSSH_9.6\r\n"] {
+        stream.write_all(chunk).unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+      }
+    });
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    assert_eq!(grab_banner(&mut stream, BANNER_TIMEOUT).await, Some("SSH-2.0-OpenSSH_9.6".to_string()));
+    handle.join().unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_grab_banner_silent_service() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    assert_eq!(grab_banner(&mut stream, Duration::from_millis(50)).await, None);
+  }
+}
@@ -0,0 +1,73 @@
+//! Cancellation of a scan in progress, for the programs embedding the scanner
+//!
+//! A `CancelToken` is given to `Scanner::scan_with_cancel` and cancelled from
+//! anywhere, a clone of it sharing its state. Once cancelled no new probe is
+//! started and the ones in flight are dropped, their ports being unscanned.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+use tokio::sync::Notify;
+
+/// Token stopping a scan once cancelled, its clones cancelling the same scan
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken(Arc<State>);
+
+#[derive(Debug, Default)]
+struct State {
+  cancelled: AtomicBool,
+  notify: Notify,
+}
+
+impl CancelToken {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  /// Cancel the scan, waking everything waiting on `cancelled`
+  pub fn cancel(&self) {
+    self.0.cancelled.store(true, Ordering::SeqCst);
+    self.0.notify.notify_waiters();
+  }
+
+  pub fn is_cancelled(&self) -> bool {
+    self.0.cancelled.load(Ordering::SeqCst)
+  }
+
+  /// Complete once cancelled, right away when it already is
+  pub async fn cancelled(&self) {
+    let notified = self.0.notify.notified();
+    tokio::pin!(notified);
+    // Registered before the check so a cancellation in between isn't missed
+    notified.as_mut().enable();
+
+    if !self.is_cancelled() {
+      notified.await;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use std::time::Duration;
+
+  #[tokio::test]
+  async fn test_cancel_token() {
+    let token = CancelToken::new();
+    assert!(!token.is_cancelled());
+    assert!(tokio::time::timeout(Duration::from_millis(20), token.cancelled()).await.is_err());
+
+    let waiting = tokio::spawn({
+      let token = token.clone();
+      async move { token.cancelled().await }
+    });
+    token.clone().cancel();
+
+    tokio::time::timeout(Duration::from_secs(1), waiting).await.unwrap().unwrap();
+    assert!(token.is_cancelled());
+    // Already cancelled
+    tokio::time::timeout(Duration::from_millis(20), token.cancelled()).await.unwrap();
+  }
+}
@@ -0,0 +1,175 @@
+//! Checkpoint of a long scan, to resume it after an interruption
+//!
+//! The checkpoint is a JSON file, rewritten as the hosts are done:
+//!
+//! ```json
+//! {
+//!   "target": "10.0.0.0/16",
+//!   "ports": [22, 80, 443, 8080],
+//!   "complete": false,
+//!   "results": [{ "ip": "10.0.0.1", "up": true, "openPorts": [] }]
+//! }
+//! ```
+//!
+//! `results` holds the result of every host whose (IP, port) pairs were all
+//! probed, in the format of the JSON report, the hosts in progress when the scan
+//! stopped being scanned again. `target` and `ports` are the ones of the scan,
+//! a checkpoint of another scan being refused. `complete` is set once the scan
+//! went through every target, the next run with the checkpoint starting over.
+//!
+//! The file is written to a temporary file first, then renamed over the
+//! checkpoint, so an interruption while writing leaves the previous one intact.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::error::ScannerError;
+use crate::scanner::{IpScanResult, Scanner};
+
+/// Time between two writes of the checkpoint during a scan
+pub const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Content of a checkpoint file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CheckpointState {
+  /// Target of the scan, as in its report
+  pub target: String,
+
+  /// Ports scanned on each host
+  pub ports: Vec<u16>,
+
+  /// Whether the scan went through every target
+  pub complete: bool,
+
+  /// Hosts done, with every port probed
+  pub results: Vec<IpScanResult>,
+}
+
+/// Checkpoint of a scan in progress, written to its file at most every `CHECKPOINT_INTERVAL`
+#[derive(Debug)]
+pub struct Checkpoint {
+  path: String,
+  state: CheckpointState,
+  written: Instant,
+  restarted: bool,
+}
+
+impl Checkpoint {
+  /// Open the checkpoint of the scan at `path`, with the results of the hosts
+  /// a previous run already scanned unless its scan was complete
+  pub fn open(path: &str, scanner: &Scanner) -> Result<Self, ScannerError> {
+    let error = |message: String| ScannerError::Checkpoint { path: path.to_string(), message };
+    let mut state = CheckpointState { target: scanner.target(), ports: scanner.ports().to_vec(), complete: false, results: Vec::new() };
+    let mut restarted = false;
+
+    if Path::new(path).exists() {
+      let content = std::fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
+      let previous: CheckpointState = serde_json::from_str(&content).map_err(|err| error(err.to_string()))?;
+
+      if previous.target != state.target || previous.ports != state.ports {
+        return Err(error(format!("it is the checkpoint of a scan of {} on ports {:?}, remove it or use another file", previous.target, previous.ports)));
+      }
+      match previous.complete {
+        true => restarted = true,
+        false => state.results = previous.results,
+      }
+    }
+
+    Ok(Self { path: path.to_string(), state, written: Instant::now(), restarted })
+  }
+
+  /// Results of the hosts already scanned
+  pub fn results(&self) -> &[IpScanResult] {
+    &self.state.results
+  }
+
+  /// Whether the checkpoint was of a complete scan, the scan starting over
+  pub fn restarted(&self) -> bool {
+    self.restarted
+  }
+
+  /// Record a host whose ports were all probed, writing the checkpoint when it is due
+  pub fn add(&mut self, result: &IpScanResult) -> Result<(), ScannerError> {
+    self.state.results.push(result.clone());
+
+    if self.written.elapsed() >= CHECKPOINT_INTERVAL {
+      self.write()?;
+    }
+    Ok(())
+  }
+
+  /// Write the checkpoint once the scan stopped, marking it complete when it went through every target
+  pub fn finish(&mut self, complete: bool) -> Result<(), ScannerError> {
+    self.state.complete = complete;
+    self.write()
+  }
+
+  /// Replace the file with the current state
+  fn write(&mut self) -> Result<(), ScannerError> {
+    let error = |err: std::io::Error| ScannerError::Checkpoint { path: self.path.clone(), message: err.to_string() };
+    let content = serde_json::to_string(&self.state).map_err(|err| ScannerError::Serialization(err.to_string()))?;
+
+    let temporary = format!("{}.tmp", self.path);
+    std::fs::write(&temporary, content).map_err(error)?;
+    std::fs::rename(&temporary, &self.path).map_err(error)?;
+
+    self.written = Instant::now();
+    Ok(())
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn path(name: &str) -> String {
+    std::env::temp_dir().join(format!("skanner-checkpoint-{}-{}.json", std::process::id(), name)).to_str().unwrap().to_string()
+  }
+
+  #[test]
+  fn test_checkpoint_resume() {
+    let path = path("resume");
+    let scanner = Scanner::new("10.0.0.0/30".to_string(), Some(vec![22])).unwrap();
+
+    let mut checkpoint = Checkpoint::open(&path, &scanner).unwrap();
+    assert!(checkpoint.results().is_empty());
+    checkpoint.add(&IpScanResult::new("10.0.0.1".parse().unwrap())).unwrap();
+    checkpoint.finish(false).unwrap();
+    assert!(!Path::new(&format!("{}.tmp", path)).exists());
+
+    let state: CheckpointState = serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+    assert_eq!(state.target, "10.0.0.0/30");
+    assert!(!state.complete);
+
+    let mut checkpoint = Checkpoint::open(&path, &scanner).unwrap();
+    assert_eq!(checkpoint.results(), [IpScanResult::new("10.0.0.1".parse().unwrap())]);
+    assert!(!checkpoint.restarted());
+    checkpoint.add(&IpScanResult::new("10.0.0.2".parse().unwrap())).unwrap();
+    checkpoint.finish(true).unwrap();
+
+    // A complete scan starts over
+    let checkpoint = Checkpoint::open(&path, &scanner).unwrap();
+    assert!(checkpoint.results().is_empty());
+    assert!(checkpoint.restarted());
+
+    std::fs::remove_file(&path).unwrap();
+  }
+
+  #[test]
+  fn test_checkpoint_of_another_scan() {
+    let path = path("another");
+    let scanner = Scanner::new("10.0.0.0/30".to_string(), Some(vec![22])).unwrap();
+    Checkpoint::open(&path, &scanner).unwrap().finish(false).unwrap();
+
+    let other = Scanner::new("10.0.0.0/30".to_string(), Some(vec![80])).unwrap();
+    let error = Checkpoint::open(&path, &other).unwrap_err();
+    assert!(error.to_string().contains("checkpoint of a scan of 10.0.0.0/30 on ports [22]"), "{}", error);
+
+    std::fs::write(&path, "not json").unwrap();
+    assert!(matches!(Checkpoint::open(&path, &scanner), Err(ScannerError::Checkpoint { .. })));
+
+    std::fs::remove_file(&path).unwrap();
+  }
+}
@@ -0,0 +1,521 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+/// A network in CIDR notation (`192.168.1.0/24`, `fd00::/120`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Cidr {
+  /// Address given by the user, not necessarily the network address
+  pub ip: IpAddr,
+
+  /// Prefix length, up to 32 for IPv4 and 128 for IPv6
+  pub prefix: u8,
+}
+
+/// Error returned when a CIDR string can't be parsed
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CidrParseError(pub String);
+
+impl Display for CidrParseError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "Invalid CIDR notation: {}", self.0)
+  }
+}
+
+impl std::error::Error for CidrParseError {}
+
+impl FromStr for Cidr {
+  type Err = CidrParseError;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (ip, prefix) = s.split_once('/').ok_or_else(|| CidrParseError(s.to_string()))?;
+
+    let ip = ip.parse::<IpAddr>().map_err(|_| CidrParseError(s.to_string()))?;
+    let prefix = prefix.parse::<u8>().map_err(|_| CidrParseError(s.to_string()))?;
+
+    if prefix > address_bits(&ip) {
+      return Err(CidrParseError(s.to_string()));
+    }
+
+    Ok(Self { ip, prefix })
+  }
+}
+
+impl Display for Cidr {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}/{}", self.ip, self.prefix)
+  }
+}
+
+impl Serialize for Cidr {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+/// Clap value parser for a network in CIDR notation or a single IP,
+/// the latter being a network of a single address
+pub fn parse_network(s: &str) -> Result<Cidr, String> {
+  if s.contains('/') {
+    return s.parse::<Cidr>().map_err(|err| err.to_string());
+  }
+
+  let ip = s.parse::<IpAddr>().map_err(|_| format!("Invalid IP address: {}", s))?;
+  Ok(Cidr { ip, prefix: address_bits(&ip) })
+}
+
+/// Number of bits of an address of the same family as `ip`
+fn address_bits(ip: &IpAddr) -> u8 {
+  match ip {
+    IpAddr::V4(_) => 32,
+    IpAddr::V6(_) => 128,
+  }
+}
+
+fn to_u128(ip: &IpAddr) -> u128 {
+  match ip {
+    IpAddr::V4(ip) => u32::from(*ip) as u128,
+    IpAddr::V6(ip) => u128::from(*ip),
+  }
+}
+
+/// Build an address of the same family as `family` from its integer value
+fn from_u128(family: &IpAddr, value: u128) -> IpAddr {
+  match family {
+    IpAddr::V4(_) => IpAddr::V4(Ipv4Addr::from(value as u32)),
+    IpAddr::V6(_) => IpAddr::V6(Ipv6Addr::from(value)),
+  }
+}
+
+impl Cidr {
+  /// Number of bits left for the hosts
+  fn host_bits(&self) -> u8 {
+    address_bits(&self.ip) - self.prefix
+  }
+
+  /// Mask of the host part of the address
+  fn host_mask(&self) -> u128 {
+    match self.host_bits() {
+      128 => u128::MAX,
+      bits => (1_u128 << bits) - 1,
+    }
+  }
+
+  /// Number of addresses in the network, network and broadcast addresses included
+  pub fn size(&self) -> u128 {
+    self.host_mask().saturating_add(1)
+  }
+
+  /// First address of the network
+  pub fn network(&self) -> IpAddr {
+    from_u128(&self.ip, to_u128(&self.ip) & !self.host_mask())
+  }
+
+  /// Last address of the network, the broadcast address for IPv4
+  pub fn broadcast(&self) -> IpAddr {
+    from_u128(&self.ip, to_u128(&self.ip) | self.host_mask())
+  }
+
+  /// Whether an IP is part of the network
+  pub fn contains(&self, ip: &IpAddr) -> bool {
+    ip.is_ipv4() == self.ip.is_ipv4() && to_u128(ip) & !self.host_mask() == to_u128(&self.network())
+  }
+
+  /// Whether an IP is one of the usable hosts of the network, as yielded by `iter_hosts`
+  pub fn contains_host(&self, ip: &IpAddr) -> bool {
+    let value = to_u128(ip);
+    match (self.ip, self.host_bits()) {
+      _ if !self.contains(ip) => false,
+      (_, 0..=1) => true,
+      (IpAddr::V4(_), _) => value != to_u128(&self.network()) && value != to_u128(&self.broadcast()),
+      (IpAddr::V6(_), _) => value != to_u128(&self.network()),
+    }
+  }
+
+  /// Usable hosts of the network
+  ///
+  /// IPv4 excludes the network and broadcast addresses, except for `/31` and `/32`
+  /// which have none. IPv6 has no broadcast so only the subnet-router anycast
+  /// (network) address is excluded, except for `/127` and `/128`.
+  pub fn hosts(&self) -> Vec<IpAddr> {
+    self.iter_hosts().collect()
+  }
+
+  /// Usable hosts of the network, yielded one at a time rather than listed up front
+  pub fn iter_hosts(&self) -> impl Iterator<Item = IpAddr> + Clone + Send + 'static {
+    let network = to_u128(&self.network());
+    let broadcast = to_u128(&self.broadcast());
+
+    let range = match (self.ip, self.host_bits()) {
+      (_, 0..=1) => network..=broadcast,
+      (IpAddr::V4(_), _) => (network + 1)..=(broadcast - 1),
+      (IpAddr::V6(_), _) => (network + 1)..=broadcast,
+    };
+
+    let family = self.ip;
+    range.map(move |value| from_u128(&family, value))
+  }
+}
+
+/// An inclusive range of IPv4 addresses (`192.168.1.50-192.168.1.90`)
+///
+/// Unlike a network, every address of the range is a host, the first and
+/// the last included.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpRange {
+  pub start: Ipv4Addr,
+  pub end: Ipv4Addr,
+}
+
+impl FromStr for IpRange {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let (start, end) = s.split_once('-').ok_or_else(|| format!("{}, expected two IPv4 addresses such as 192.168.1.50-192.168.1.90", s))?;
+
+    let parse = |ip: &str| ip.trim().parse::<Ipv4Addr>().map_err(|_| format!("{}, {} is not an IPv4 address", s, ip.trim()));
+    let (start, end) = (parse(start)?, parse(end)?);
+
+    if u32::from(start) > u32::from(end) {
+      return Err(format!("{}, the start comes after the end", s));
+    }
+
+    Ok(Self { start, end })
+  }
+}
+
+impl Display for IpRange {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}-{}", self.start, self.end)
+  }
+}
+
+impl Serialize for IpRange {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+impl<'de> Deserialize<'de> for IpRange {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+  }
+}
+
+impl IpRange {
+  /// Number of addresses in the range, both ends included
+  pub fn size(&self) -> u64 {
+    u64::from(u32::from(self.end)) - u64::from(u32::from(self.start)) + 1
+  }
+
+  /// Whether an IP is in the range
+  pub fn contains(&self, ip: &IpAddr) -> bool {
+    matches!(ip, IpAddr::V4(ip) if (self.start..=self.end).contains(ip))
+  }
+
+  /// Addresses of the range, yielded one at a time
+  pub fn iter(&self) -> impl Iterator<Item = IpAddr> + Clone + Send + 'static {
+    (u32::from(self.start)..=u32::from(self.end)).map(|value| IpAddr::V4(Ipv4Addr::from(value)))
+  }
+}
+
+/// IPv4 addresses with wildcard octets (`192.168.*.*`, `10.*.0.1`), each `*` going over 0 to 255
+///
+/// The wildcards ending the address make a network for each value of the
+/// others, its network and broadcast addresses being skipped as in CIDR
+/// notation: `192.168.1.*` is `192.168.1.1` to `192.168.1.254`. A wildcard
+/// followed by a fixed octet goes over every value.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IpWildcard {
+  /// Octets of the address, `None` for a wildcard
+  pub octets: [Option<u8>; 4],
+}
+
+impl FromStr for IpWildcard {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let parts = s.split('.').collect::<Vec<&str>>();
+    if parts.len() != 4 {
+      return Err(format!("{}, expected four octets such as 192.168.1.*", s));
+    }
+
+    let mut octets = [None; 4];
+    for (octet, part) in octets.iter_mut().zip(parts) {
+      *octet = match part {
+        "*" => None,
+        part => Some(part.parse::<u8>().map_err(|_| format!("{}, {} is neither an octet nor *", s, part))?),
+      };
+    }
+
+    if octets.iter().all(Option::is_some) {
+      return Err(format!("{}, no octet is a wildcard", s));
+    }
+
+    Ok(Self { octets })
+  }
+}
+
+impl Display for IpWildcard {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let octets = self.octets.iter().map(|octet| octet.map_or("*".to_string(), |octet| octet.to_string())).collect::<Vec<String>>();
+    write!(f, "{}", octets.join("."))
+  }
+}
+
+impl Serialize for IpWildcard {
+  fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(self)
+  }
+}
+
+impl<'de> Deserialize<'de> for IpWildcard {
+  fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+    String::deserialize(deserializer)?.parse().map_err(serde::de::Error::custom)
+  }
+}
+
+impl IpWildcard {
+  /// Number of wildcards ending the address, making a network
+  fn trailing(&self) -> usize {
+    self.octets.iter().rev().take_while(|octet| octet.is_none()).count()
+  }
+
+  /// Number of addresses scanned, network and broadcast addresses excluded
+  pub fn size(&self) -> u64 {
+    let trailing = self.trailing();
+    let inner = self.octets.iter().filter(|octet| octet.is_none()).count() - trailing;
+
+    match trailing {
+      0 => 256_u64.pow(inner as u32),
+      _ => 256_u64.pow(inner as u32) * ((1_u64 << (8 * trailing)) - 2),
+    }
+  }
+
+  /// Lowest and highest addresses matched, network and broadcast addresses included
+  pub fn bounds(&self) -> (Ipv4Addr, Ipv4Addr) {
+    (Ipv4Addr::from(self.octets.map(|octet| octet.unwrap_or(0))), Ipv4Addr::from(self.octets.map(|octet| octet.unwrap_or(255))))
+  }
+
+  /// Whether the host part of an address, the one of the wildcards ending it, is neither its network nor its broadcast
+  fn is_host(address: &[u8; 4], trailing: usize) -> bool {
+    let host = &address[4 - trailing..];
+    host.is_empty() || (!host.iter().all(|value| *value == 0) && !host.iter().all(|value| *value == 255))
+  }
+
+  /// Whether an IP is one of the addresses scanned, as yielded by `iter`
+  pub fn contains(&self, ip: &IpAddr) -> bool {
+    let IpAddr::V4(ip) = ip else { return false };
+    let address = ip.octets();
+
+    self.octets.iter().zip(address).all(|(octet, value)| octet.is_none_or(|octet| octet == value)) && Self::is_host(&address, self.trailing())
+  }
+
+  /// Addresses scanned, in ascending order, yielded one at a time
+  pub fn iter(&self) -> impl Iterator<Item = IpAddr> + Clone + Send + 'static {
+    let octets = self.octets;
+    let wildcards = octets.iter().filter(|octet| octet.is_none()).count() as u32;
+    let trailing = self.trailing();
+
+    // The wildcards are the digits of a base 256 counter, the last one varying first
+    (0..256_u64.pow(wildcards))
+      .map(move |counter| {
+        let mut digits = counter.to_be_bytes()[8 - wildcards as usize..].to_vec().into_iter();
+        octets.map(|octet| octet.unwrap_or_else(|| digits.next().unwrap_or(0)))
+      })
+      .filter(move |address| Self::is_host(address, trailing))
+      .map(|address| IpAddr::V4(Ipv4Addr::from(address)))
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_cidr() {
+    let cidr = "10.0.0.0/20".parse::<Cidr>().unwrap();
+
+    assert_eq!(cidr.ip, IpAddr::V4(Ipv4Addr::new(10, 0, 0, 0)));
+    assert_eq!(cidr.prefix, 20);
+  }
+
+  #[test]
+  fn test_parse_invalid_cidr() {
+    assert!("10.0.0.0".parse::<Cidr>().is_err());
+    assert!("10.0.0.0/33".parse::<Cidr>().is_err());
+    assert!("10.0.0/24".parse::<Cidr>().is_err());
+    assert!("10.0.0.0/abc".parse::<Cidr>().is_err());
+    assert!("fd00::/129".parse::<Cidr>().is_err());
+  }
+
+  #[test]
+  fn test_network_and_broadcast() {
+    let cidr = "192.168.1.77/26".parse::<Cidr>().unwrap();
+
+    assert_eq!(cidr.network(), "192.168.1.64".parse::<IpAddr>().unwrap());
+    assert_eq!(cidr.broadcast(), "192.168.1.127".parse::<IpAddr>().unwrap());
+  }
+
+  #[test]
+  fn test_hosts_exclude_network_and_broadcast() {
+    let cidr = "192.168.1.0/26".parse::<Cidr>().unwrap();
+    let hosts = cidr.hosts();
+
+    assert_eq!(hosts.len(), 62);
+    assert_eq!(hosts[0], "192.168.1.1".parse::<IpAddr>().unwrap());
+    assert_eq!(hosts[61], "192.168.1.62".parse::<IpAddr>().unwrap());
+  }
+
+  #[test]
+  fn test_iter_hosts_lazy() {
+    let cidr = "10.0.0.0/8".parse::<Cidr>().unwrap();
+    let mut hosts = cidr.iter_hosts();
+
+    assert_eq!(hosts.next(), Some("10.0.0.1".parse::<IpAddr>().unwrap()));
+    assert_eq!(hosts.last(), Some("10.255.255.254".parse::<IpAddr>().unwrap()));
+  }
+
+  #[test]
+  fn test_hosts_small_prefixes() {
+    let single = "192.168.1.5/32".parse::<Cidr>().unwrap();
+    assert_eq!(single.hosts(), vec!["192.168.1.5".parse::<IpAddr>().unwrap()]);
+
+    let pair = "192.168.1.4/31".parse::<Cidr>().unwrap();
+    assert_eq!(pair.hosts(), vec!["192.168.1.4".parse::<IpAddr>().unwrap(), "192.168.1.5".parse::<IpAddr>().unwrap()]);
+  }
+
+  #[test]
+  fn test_hosts_ipv6() {
+    let cidr = "fd00::1:0/124".parse::<Cidr>().unwrap();
+    let hosts = cidr.hosts();
+
+    assert_eq!(cidr.size(), 16);
+    assert_eq!(hosts.len(), 15);
+    assert_eq!(hosts[0], "fd00::1:1".parse::<IpAddr>().unwrap());
+    assert_eq!(hosts[14], "fd00::1:f".parse::<IpAddr>().unwrap());
+  }
+
+  #[test]
+  fn test_contains() {
+    let cidr = "192.168.1.0/24".parse::<Cidr>().unwrap();
+
+    assert!(cidr.contains(&"192.168.1.0".parse().unwrap()));
+    assert!(cidr.contains(&"192.168.1.200".parse().unwrap()));
+    assert!(!cidr.contains(&"192.168.2.1".parse().unwrap()));
+    assert!(!cidr.contains(&"::ffff:192.168.1.1".parse().unwrap()));
+
+    // The network and broadcast addresses are not hosts
+    assert!(!cidr.contains_host(&"192.168.1.0".parse().unwrap()));
+    assert!(cidr.contains_host(&"192.168.1.200".parse().unwrap()));
+    assert!(!cidr.contains_host(&"192.168.1.255".parse().unwrap()));
+    assert!(!cidr.contains_host(&"192.168.2.1".parse().unwrap()));
+    assert!("192.168.1.4/31".parse::<Cidr>().unwrap().contains_host(&"192.168.1.4".parse().unwrap()));
+    assert!("fd00::/120".parse::<Cidr>().unwrap().contains_host(&"fd00::ff".parse().unwrap()));
+  }
+
+  #[test]
+  fn test_parse_network() {
+    assert_eq!(parse_network("192.168.1.1").unwrap().to_string(), "192.168.1.1/32");
+    assert_eq!(parse_network("fd00::1").unwrap().to_string(), "fd00::1/128");
+    assert_eq!(parse_network("10.0.0.0/8").unwrap().to_string(), "10.0.0.0/8");
+    assert!(parse_network("10.0.0.300").is_err());
+  }
+
+  #[test]
+  fn test_parse_ip_wildcard() {
+    let wildcard = "192.168.1.*".parse::<IpWildcard>().unwrap();
+    assert_eq!(wildcard.octets, [Some(192), Some(168), Some(1), None]);
+    assert_eq!(wildcard.to_string(), "192.168.1.*");
+    assert_eq!(wildcard.bounds(), (Ipv4Addr::new(192, 168, 1, 0), Ipv4Addr::new(192, 168, 1, 255)));
+
+    assert!("192.168.1.1".parse::<IpWildcard>().is_err());
+    assert!("192.168.*".parse::<IpWildcard>().is_err());
+    assert!("192.168.256.*".parse::<IpWildcard>().is_err());
+    assert!("192.168.1-2.*".parse::<IpWildcard>().is_err());
+  }
+
+  #[test]
+  fn test_ip_wildcard_hosts() {
+    let wildcard = "192.168.1.*".parse::<IpWildcard>().unwrap();
+    let hosts = wildcard.iter().collect::<Vec<IpAddr>>();
+    // Network and broadcast addresses skipped, as for 192.168.1.0/24
+    assert_eq!(hosts, "192.168.1.0/24".parse::<Cidr>().unwrap().hosts());
+    assert_eq!(wildcard.size(), 254);
+
+    let wildcard = "10.*.0.1".parse::<IpWildcard>().unwrap();
+    let hosts = wildcard.iter().collect::<Vec<IpAddr>>();
+    assert_eq!(hosts.len(), 256);
+    assert_eq!(hosts[0], IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1)));
+    assert_eq!(hosts[255], IpAddr::V4(Ipv4Addr::new(10, 255, 0, 1)));
+    assert_eq!(wildcard.size(), 256);
+
+    let wildcard = "10.*.1.*".parse::<IpWildcard>().unwrap();
+    let hosts = wildcard.iter().collect::<Vec<IpAddr>>();
+    assert_eq!(hosts.len() as u64, wildcard.size());
+    assert_eq!(&hosts[253..255], &[IpAddr::V4(Ipv4Addr::new(10, 0, 1, 254)), IpAddr::V4(Ipv4Addr::new(10, 1, 1, 1))]);
+
+    // The host part of 192.168.*.* is the last two octets
+    let hosts = "192.168.*.*".parse::<IpWildcard>().unwrap().iter().take(256).collect::<Vec<IpAddr>>();
+    assert_eq!(hosts[0], IpAddr::V4(Ipv4Addr::new(192, 168, 0, 1)));
+    assert_eq!(hosts[255], IpAddr::V4(Ipv4Addr::new(192, 168, 1, 0)));
+  }
+
+  #[test]
+  fn test_ip_wildcard_positions() {
+    // The wildcards vary in positional order, the first one the slowest, the fixed octets staying fixed
+    let hosts = "*.10.*.5".parse::<IpWildcard>().unwrap().iter().collect::<Vec<IpAddr>>();
+    assert_eq!(hosts.len(), 256 * 256);
+    assert_eq!(&hosts[..2], &[IpAddr::V4(Ipv4Addr::new(0, 10, 0, 5)), IpAddr::V4(Ipv4Addr::new(0, 10, 1, 5))]);
+    assert_eq!(hosts[256], IpAddr::V4(Ipv4Addr::new(1, 10, 0, 5)));
+    assert_eq!(hosts[hosts.len() - 1], IpAddr::V4(Ipv4Addr::new(255, 10, 255, 5)));
+    assert!(hosts.iter().all(|ip| matches!(ip, IpAddr::V4(ip) if ip.octets()[1] == 10 && ip.octets()[3] == 5)));
+  }
+
+  #[test]
+  fn test_ip_wildcard_contains() {
+    let wildcard = "192.168.*.*".parse::<IpWildcard>().unwrap();
+    assert!(wildcard.contains(&"192.168.4.0".parse().unwrap()));
+    assert!(wildcard.contains(&"192.168.1.255".parse().unwrap()));
+    assert!(!wildcard.contains(&"192.168.0.0".parse().unwrap()));
+    assert!(!wildcard.contains(&"192.168.255.255".parse().unwrap()));
+    assert!(!wildcard.contains(&"192.169.1.1".parse().unwrap()));
+    assert!(!wildcard.contains(&"::1".parse().unwrap()));
+
+    let wildcard = "10.*.0.1".parse::<IpWildcard>().unwrap();
+    assert!(wildcard.contains(&"10.7.0.1".parse().unwrap()));
+    assert!(!wildcard.contains(&"10.7.0.2".parse().unwrap()));
+  }
+
+  #[test]
+  fn test_parse_ip_range() {
+    let range = "192.168.1.50-192.168.1.53".parse::<IpRange>().unwrap();
+
+    assert_eq!(range.size(), 4);
+    assert_eq!(range.to_string(), "192.168.1.50-192.168.1.53");
+    assert_eq!(
+      range.iter().map(|ip| ip.to_string()).collect::<Vec<String>>(),
+      vec!["192.168.1.50", "192.168.1.51", "192.168.1.52", "192.168.1.53"]
+    );
+    assert_eq!("10.0.0.255 - 10.0.1.0".parse::<IpRange>().unwrap().size(), 2);
+    assert!(range.contains(&"192.168.1.53".parse().unwrap()));
+    assert!(!range.contains(&"192.168.1.54".parse().unwrap()));
+    assert_eq!("0.0.0.0-255.255.255.255".parse::<IpRange>().unwrap().size(), 1 << 32);
+  }
+
+  #[test]
+  fn test_parse_ip_range_errors() {
+    assert_eq!("192.168.1.90-192.168.1.50".parse::<IpRange>().unwrap_err(), "192.168.1.90-192.168.1.50, the start comes after the end");
+    assert!("192.168.1.50-192.168.1.300".parse::<IpRange>().is_err());
+    assert!("fd00::1-fd00::5".parse::<IpRange>().is_err());
+    assert!("192.168.1.50".parse::<IpRange>().is_err());
+  }
+
+  #[test]
+  fn test_size_full_range() {
+    assert_eq!("::/0".parse::<Cidr>().unwrap().size(), u128::MAX);
+    assert_eq!("0.0.0.0/0".parse::<Cidr>().unwrap().size(), 1 << 32);
+  }
+}
@@ -0,0 +1,60 @@
+//! ANSI colors of the port states in the text report printed to a terminal
+//!
+//! Open ports are green, closed ones red and filtered ones yellow. The report
+//! is left plain when stdout is not a terminal, with `--no-color` or when the
+//! `NO_COLOR` environment variable is set and not empty (<https://no-color.org>).
+
+use std::ffi::OsString;
+use std::io::IsTerminal;
+
+use crate::port::PortState;
+
+const GREEN: &str = "\x1b[32m";
+const RED: &str = "\x1b[31m";
+const YELLOW: &str = "\x1b[33m";
+const RESET: &str = "\x1b[0m";
+
+/// Whether the text report printed to stdout is colored
+pub fn enabled(no_color: bool) -> bool {
+  should_color(no_color, std::env::var_os("NO_COLOR"), std::io::stdout().is_terminal())
+}
+
+fn should_color(no_color: bool, no_color_env: Option<OsString>, terminal: bool) -> bool {
+  !no_color && no_color_env.is_none_or(|value| value.is_empty()) && terminal
+}
+
+/// `text` in the color of `state`, as is for a state without one
+pub fn paint(text: &str, state: PortState) -> String {
+  let color = match state {
+    PortState::Open => GREEN,
+    PortState::Closed => RED,
+    PortState::Filtered | PortState::OpenFiltered => YELLOW,
+    PortState::Unknown => return text.to_string(),
+  };
+
+  format!("{}{}{}", color, text, RESET)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_should_color() {
+    assert!(should_color(false, None, true));
+    assert!(should_color(false, Some(OsString::new()), true));
+
+    // Piped or redirected output is never colored
+    assert!(!should_color(false, None, false));
+    assert!(!should_color(true, None, true));
+    assert!(!should_color(false, Some("1".into()), true));
+  }
+
+  #[test]
+  fn test_paint() {
+    assert_eq!(paint("22/tcp", PortState::Open), "\x1b[32m22/tcp\x1b[0m");
+    assert_eq!(paint("53/udp (open|filtered)", PortState::OpenFiltered), "\x1b[33m53/udp (open|filtered)\x1b[0m");
+    assert_eq!(paint("23/tcp (closed)", PortState::Closed), "\x1b[31m23/tcp (closed)\x1b[0m");
+    assert_eq!(paint("80/tcp (unknown)", PortState::Unknown), "80/tcp (unknown)");
+  }
+}
@@ -0,0 +1,247 @@
+//! Defaults of the command line read from a TOML file, given with `--config`
+//!
+//! ```toml
+//! # office network
+//! target = "192.168.1.0/24"
+//! ports = "22,80,443 udp:53"
+//! timeout_ms = 500
+//! concurrency = 128
+//! output = "office-{timestamp}.json"
+//! format = "json"
+//! ```
+//!
+//! Only top-level keys are read, with strings, integers, booleans and arrays
+//! on a single line as values. The options given on the command line win over
+//! the file.
+
+use serde::de::Error;
+use serde::{Deserialize, Deserializer};
+use serde_json::{Map, Number, Value};
+
+use crate::output::OutputFormat;
+use crate::port::{self, PortList};
+
+/// Options of a config file, each one being a default of its command line option
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Config {
+  /// Target of `--ip`
+  pub target: Option<String>,
+
+  /// Port lists of `--ports`, separated by spaces as on the command line
+  #[serde(default, deserialize_with = "deserialize_ports")]
+  pub ports: Option<Vec<PortList>>,
+
+  /// Timeout of `--timeout-ms`, in milliseconds
+  pub timeout_ms: Option<u64>,
+
+  /// Probes in flight of `--concurrency`
+  pub concurrency: Option<usize>,
+
+  /// Report file of `--output`
+  pub output: Option<String>,
+
+  /// Format of `--format`
+  #[serde(default, deserialize_with = "deserialize_format")]
+  pub format: Option<OutputFormat>,
+}
+
+fn deserialize_ports<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Vec<PortList>>, D::Error> {
+  let ports = String::deserialize(deserializer)?;
+
+  ports.split_whitespace().map(port::parse_port_list).collect::<Result<Vec<PortList>, String>>().map(Some).map_err(D::Error::custom)
+}
+
+fn deserialize_format<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<OutputFormat>, D::Error> {
+  let format = String::deserialize(deserializer)?;
+
+  <OutputFormat as clap::ValueEnum>::from_str(&format, true).map(Some).map_err(|_| D::Error::custom(format!("unknown format {}", format)))
+}
+
+/// Clap value parser for a config file, read when the command line is parsed
+pub fn read_config(path: &str) -> Result<Config, String> {
+  let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+
+  parse_config(&content)
+}
+
+/// Parse a config file, an unknown key or a value of the wrong type being an error
+pub fn parse_config(content: &str) -> Result<Config, String> {
+  let values = parse_toml(content).map_err(|(line, err)| format!("line {}: {}", line, err))?;
+  let config: Config = serde_json::from_value(Value::Object(values)).map_err(|err| err.to_string())?;
+
+  if config.timeout_ms == Some(0) || config.concurrency == Some(0) {
+    return Err("timeout_ms and concurrency must be at least 1".to_string());
+  }
+
+  Ok(config)
+}
+
+/// Top-level keys of a TOML document, returning the number of the first invalid line and its error
+fn parse_toml(content: &str) -> Result<Map<String, Value>, (usize, String)> {
+  let mut values = Map::new();
+
+  for (index, line) in content.lines().enumerate() {
+    let error = |message: String| (index + 1, message);
+
+    let line = line.trim();
+    if line.is_empty() || line.starts_with('#') {
+      continue;
+    }
+    if line.starts_with('[') {
+      return Err(error(format!("tables are not supported, {} has to be removed", line)));
+    }
+
+    let (key, value) = line.split_once('=').ok_or_else(|| error(format!("expected key = value, got {}", line)))?;
+    let key = parse_key(key.trim()).ok_or_else(|| error(format!("invalid key {}", key.trim())))?;
+    let (value, rest) = parse_value(value.trim()).map_err(error)?;
+    let rest = rest.trim();
+    if !rest.is_empty() && !rest.starts_with('#') {
+      return Err(error(format!("unexpected {} after the value of {}", rest, key)));
+    }
+
+    if values.insert(key.clone(), value).is_some() {
+      return Err(error(format!("{} is defined twice", key)));
+    }
+  }
+
+  Ok(values)
+}
+
+/// Bare or quoted key
+fn parse_key(key: &str) -> Option<String> {
+  if let Some(quoted) = key.strip_prefix('"').and_then(|key| key.strip_suffix('"')) {
+    return Some(quoted.to_string());
+  }
+
+  (!key.is_empty() && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')).then(|| key.to_string())
+}
+
+/// Value at the start of `s`, with what follows it
+fn parse_value(s: &str) -> Result<(Value, &str), String> {
+  if let Some(rest) = s.strip_prefix('"') {
+    return parse_basic_string(rest).map(|(string, rest)| (Value::String(string), rest));
+  }
+  if let Some(rest) = s.strip_prefix('\'') {
+    let end = rest.find('\'').ok_or("unterminated string")?;
+    return Ok((Value::String(rest[..end].to_string()), &rest[end + 1..]));
+  }
+  if let Some(rest) = s.strip_prefix('[') {
+    return parse_array(rest);
+  }
+
+  let end = s.find(|c: char| c == ',' || c == ']' || c == '#' || c.is_whitespace()).unwrap_or(s.len());
+  let (token, rest) = s.split_at(end);
+  let value = match token {
+    "true" => Value::Bool(true),
+    "false" => Value::Bool(false),
+    "" => return Err("missing value".to_string()),
+    _ => {
+      let integer = token.replace('_', "").parse::<i64>().map_err(|_| format!("{} is neither a string, an integer, a boolean nor an array", token))?;
+      Value::Number(Number::from(integer))
+    }
+  };
+
+  Ok((value, rest))
+}
+
+/// String between double quotes, the opening one being already read
+fn parse_basic_string(s: &str) -> Result<(String, &str), String> {
+  let mut string = String::new();
+  let mut chars = s.char_indices();
+
+  while let Some((index, c)) = chars.next() {
+    match c {
+      '"' => return Ok((string, &s[index + 1..])),
+      '\\' => match chars.next().map(|(_, c)| c) {
+        Some('"') => string.push('"'),
+        Some('\\') => string.push('\\'),
+        Some('n') => string.push('\n'),
+        Some('t') => string.push('\t'),
+        Some(other) => return Err(format!("unsupported escape \\{}", other)),
+        None => break,
+      },
+      c => string.push(c),
+    }
+  }
+
+  Err("unterminated string".to_string())
+}
+
+/// Array on a single line, the opening bracket being already read
+fn parse_array(mut s: &str) -> Result<(Value, &str), String> {
+  let mut items = Vec::new();
+
+  loop {
+    s = s.trim_start();
+    if let Some(rest) = s.strip_prefix(']') {
+      return Ok((Value::Array(items), rest));
+    }
+
+    let (item, rest) = parse_value(s)?;
+    items.push(item);
+
+    s = rest.trim_start();
+    match s.strip_prefix(',') {
+      Some(rest) => s = rest,
+      None if s.starts_with(']') => {}
+      None => return Err("unterminated array".to_string()),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::port::PortRange;
+
+  #[test]
+  fn test_parse_config() {
+    let content = r#"
+# office network
+target = "192.168.1.0/24"
+ports = "22,80 udp:53"  # web and DNS
+timeout_ms = 1_500
+concurrency = 64
+output = 'office-{timestamp}.json'
+format = "json"
+"#;
+
+    let config = parse_config(content).unwrap();
+    assert_eq!(config.target.as_deref(), Some("192.168.1.0/24"));
+    assert_eq!(config.ports.as_ref().map(|lists| lists.iter().flat_map(PortList::ranges).collect::<Vec<PortRange>>().len()), Some(3));
+    assert_eq!(config.timeout_ms, Some(1500));
+    assert_eq!(config.concurrency, Some(64));
+    assert_eq!(config.output.as_deref(), Some("office-{timestamp}.json"));
+    assert_eq!(config.format, Some(OutputFormat::Json));
+
+    assert_eq!(parse_config("# nothing set\n"), Ok(Config::default()));
+  }
+
+  #[test]
+  fn test_parse_config_errors() {
+    let error = |content: &str| parse_config(content).unwrap_err();
+
+    assert!(error("threads = 4").starts_with("unknown field `threads`, expected one of `target`"), "{}", error("threads = 4"));
+    assert_eq!(error("timeout_ms = \"fast\""), "invalid type: string \"fast\", expected u64");
+    assert_eq!(error("concurrency = -1"), "invalid value: integer `-1`, expected usize");
+    assert_eq!(error("concurrency = 0"), "timeout_ms and concurrency must be at least 1");
+    assert_eq!(error("format = \"pdf\""), "unknown format pdf");
+    assert!(error("ports = \"22,x\"").contains("x"), "{}", error("ports = \"22,x\""));
+
+    assert_eq!(error("target = \"10.0.0.1\"\ntarget = \"10.0.0.2\""), "line 2: target is defined twice");
+    assert_eq!(error("[scan]\ntarget = \"10.0.0.1\""), "line 1: tables are not supported, [scan] has to be removed");
+    assert_eq!(error("target"), "line 1: expected key = value, got target");
+    assert_eq!(error("target = \"10.0.0.1"), "line 1: unterminated string");
+    assert_eq!(error("target = 10.0.0.1"), "line 1: 10.0.0.1 is neither a string, an integer, a boolean nor an array");
+    assert_eq!(error("target = \"10.0.0.1\" \"10.0.0.2\""), "line 1: unexpected \"10.0.0.2\" after the value of target");
+  }
+
+  #[test]
+  fn test_parse_toml_values() {
+    let values = parse_toml("a = [1, \"two\", [true]] # list\n\"quoted key\" = \"tab\\there\"\nb = false").unwrap();
+
+    assert_eq!(Value::Object(values), serde_json::json!({ "a": [1, "two", [true]], "quoted key": "tab\there", "b": false }));
+  }
+}
@@ -0,0 +1,268 @@
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io::{self, ErrorKind};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::sync::Mutex;
+
+use serde::Serialize;
+use socket2::{Domain, Socket, Type};
+use tokio::net::{TcpSocket, TcpStream, UdpSocket};
+
+/// Local end of the connections, to choose the path of the traffic on a multi-homed machine
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Source {
+  /// Local address the sockets are bound to
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub ip: Option<IpAddr>,
+
+  /// Network interface the sockets are bound to, on Linux only
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub interface: Option<String>,
+}
+
+impl Display for Source {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match (&self.ip, &self.interface) {
+      (Some(ip), Some(interface)) => write!(f, "{} on {}", ip, interface),
+      (Some(ip), None) => write!(f, "{}", ip),
+      (None, Some(interface)) => write!(f, "{}", interface),
+      (None, None) => write!(f, "the default address"),
+    }
+  }
+}
+
+impl Source {
+  /// Whether the sockets are left to the OS, neither an address nor an interface being set
+  pub fn is_default(&self) -> bool {
+    self.ip.is_none() && self.interface.is_none()
+  }
+
+  /// Whether `ip` can be reached from the source address, both being of the same family
+  pub fn reaches(&self, ip: &IpAddr) -> bool {
+    self.ip.is_none_or(|source| source.is_ipv4() == ip.is_ipv4())
+  }
+
+  /// Check a socket to a host of the family of `ip` can be bound to the source
+  pub fn check(&self, ip: IpAddr) -> io::Result<()> {
+    self.socket(ip, Type::STREAM).map(|_| ())
+  }
+
+  /// Open a TCP connection from the source
+  pub async fn connect(&self, address: SocketAddr) -> io::Result<TcpStream> {
+    if self.is_default() {
+      return TcpStream::connect(address).await;
+    }
+
+    let socket = self.socket(address.ip(), Type::STREAM)?;
+    TcpSocket::from_std_stream(socket.into()).connect(address).await
+  }
+
+  /// Open a TCP connection from the source to a `host:port`, trying each of its addresses
+  pub async fn connect_host(&self, host: &str) -> io::Result<TcpStream> {
+    if self.is_default() {
+      return TcpStream::connect(host).await;
+    }
+
+    let mut error = None;
+    for address in tokio::net::lookup_host(host).await?.filter(|address| self.reaches(&address.ip())) {
+      match self.connect(address).await {
+        Ok(stream) => return Ok(stream),
+        Err(err) => error = Some(err),
+      }
+    }
+
+    Err(error.unwrap_or_else(|| io::Error::new(ErrorKind::AddrNotAvailable, format!("no address of {} reachable from {}", host, self))))
+  }
+
+  /// Open an UDP socket from the source, to send datagrams to `ip`
+  pub fn bind_udp(&self, ip: IpAddr) -> io::Result<UdpSocket> {
+    UdpSocket::from_std(self.socket(ip, Type::DGRAM)?.into())
+  }
+
+  /// Non blocking socket bound to the source, with the family of `ip`
+  fn socket(&self, ip: IpAddr, kind: Type) -> io::Result<Socket> {
+    let local = match (self.ip, ip) {
+      (Some(local), _) => local,
+      (None, IpAddr::V4(_)) => Ipv4Addr::UNSPECIFIED.into(),
+      (None, IpAddr::V6(_)) => Ipv6Addr::UNSPECIFIED.into(),
+    };
+
+    let socket = Socket::new(Domain::for_address(SocketAddr::new(ip, 0)), kind, None)?;
+    if let Some(interface) = &self.interface {
+      bind_device(&socket, interface)?;
+    }
+    socket.bind(&SocketAddr::new(local, 0).into())?;
+    socket.set_nonblocking(true)?;
+
+    Ok(socket)
+  }
+}
+
+#[cfg(any(target_os = "android", target_os = "fuchsia", target_os = "linux"))]
+pub(crate) fn bind_device(socket: &Socket, interface: &str) -> io::Result<()> {
+  socket.bind_device(Some(interface.as_bytes()))
+}
+
+#[cfg(not(any(target_os = "android", target_os = "fuchsia", target_os = "linux")))]
+pub(crate) fn bind_device(_: &Socket, _: &str) -> io::Result<()> {
+  Err(io::Error::new(ErrorKind::Unsupported, "binding to an interface is only supported on Linux"))
+}
+
+/// Reason a connection to a port failed, telling a filtered network from a host refusing
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum ConnectionError {
+  /// The host answered with a reset, nothing listens on the port
+  Refused,
+
+  /// Nothing answered before the timeout, the packets were most likely dropped
+  TimedOut,
+
+  /// No route to the host or its network
+  Unreachable,
+
+  /// The connection was reset or aborted while being opened
+  Reset,
+
+  /// Any other error, such as a proxy failure
+  Other,
+}
+
+impl From<ErrorKind> for ConnectionError {
+  fn from(kind: ErrorKind) -> Self {
+    match kind {
+      ErrorKind::ConnectionRefused => ConnectionError::Refused,
+      ErrorKind::TimedOut => ConnectionError::TimedOut,
+      ErrorKind::HostUnreachable | ErrorKind::NetworkUnreachable => ConnectionError::Unreachable,
+      ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted => ConnectionError::Reset,
+      _ => ConnectionError::Other,
+    }
+  }
+}
+
+impl Display for ConnectionError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      ConnectionError::Refused => "refused",
+      ConnectionError::TimedOut => "timed out",
+      ConnectionError::Unreachable => "unreachable",
+      ConnectionError::Reset => "reset",
+      ConnectionError::Other => "other",
+    };
+    write!(f, "{}", name)
+  }
+}
+
+/// Number of failed connections per reason
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(transparent)]
+pub struct ConnectionErrors(BTreeMap<ConnectionError, u64>);
+
+impl ConnectionErrors {
+  /// Count a failed connection
+  pub fn add(&mut self, kind: ErrorKind) {
+    *self.0.entry(kind.into()).or_default() += 1;
+  }
+
+  /// Number of connections that failed for `error`
+  pub fn get(&self, error: ConnectionError) -> u64 {
+    self.0.get(&error).copied().unwrap_or_default()
+  }
+
+  /// Whether no connection failed
+  pub fn is_empty(&self) -> bool {
+    self.0.is_empty()
+  }
+}
+
+/// The most frequent reasons first, as in `refused: 12000, timed out: 340`
+impl Display for ConnectionErrors {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let mut counts = self.0.iter().collect::<Vec<(&ConnectionError, &u64)>>();
+    counts.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+
+    let counts = counts.into_iter().map(|(error, count)| format!("{}: {}", error, count)).collect::<Vec<String>>();
+    write!(f, "{}", counts.join(", "))
+  }
+}
+
+/// Counts of failed connections shared by the hosts of a scan
+#[derive(Debug, Default)]
+pub struct ErrorCounter(Mutex<ConnectionErrors>);
+
+impl ErrorCounter {
+  /// Count a failed connection
+  pub fn add(&self, kind: ErrorKind) {
+    self.0.lock().unwrap().add(kind);
+  }
+
+  /// Counts so far
+  pub fn counts(&self) -> ConnectionErrors {
+    self.0.lock().unwrap().clone()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[tokio::test]
+  async fn test_source_connect() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let source = Source { ip: Some("127.0.0.2".parse().unwrap()), interface: None };
+    let stream = source.connect(address).await.unwrap();
+    assert_eq!(stream.local_addr().unwrap().ip(), source.ip.unwrap());
+
+    let stream = source.connect_host(&format!("localhost:{}", address.port())).await.unwrap();
+    assert_eq!(stream.local_addr().unwrap().ip(), source.ip.unwrap());
+
+    let socket = source.bind_udp(address.ip()).unwrap();
+    assert_eq!(socket.local_addr().unwrap().ip(), source.ip.unwrap());
+  }
+
+  #[test]
+  fn test_source_check() {
+    let source = Source { ip: Some("127.0.0.1".parse().unwrap()), interface: None };
+    assert!(source.check("127.0.0.1".parse().unwrap()).is_ok());
+    assert!(source.reaches(&"192.168.1.1".parse().unwrap()));
+    assert!(!source.reaches(&"fd00::1".parse().unwrap()));
+
+    // Not an address of this machine
+    let source = Source { ip: Some("192.0.2.1".parse().unwrap()), interface: None };
+    assert_eq!(source.check("192.0.2.2".parse().unwrap()).unwrap_err().kind(), ErrorKind::AddrNotAvailable);
+
+    let source = Source { ip: None, interface: Some("skanner-none0".to_string()) };
+    assert!(source.check("192.0.2.2".parse().unwrap()).is_err());
+    assert_eq!(source.to_string(), "skanner-none0");
+    assert!(Source::default().is_default());
+  }
+
+  #[test]
+  fn test_connection_error_kinds() {
+    assert_eq!(ConnectionError::from(ErrorKind::ConnectionRefused), ConnectionError::Refused);
+    assert_eq!(ConnectionError::from(ErrorKind::TimedOut), ConnectionError::TimedOut);
+    assert_eq!(ConnectionError::from(ErrorKind::HostUnreachable), ConnectionError::Unreachable);
+    assert_eq!(ConnectionError::from(ErrorKind::NetworkUnreachable), ConnectionError::Unreachable);
+    assert_eq!(ConnectionError::from(ErrorKind::ConnectionReset), ConnectionError::Reset);
+    assert_eq!(ConnectionError::from(ErrorKind::Other), ConnectionError::Other);
+  }
+
+  #[test]
+  fn test_connection_errors() {
+    let counter = ErrorCounter::default();
+    for _ in 0..3 {
+      counter.add(ErrorKind::ConnectionRefused);
+    }
+    counter.add(ErrorKind::HostUnreachable);
+    counter.add(ErrorKind::TimedOut);
+
+    let counts = counter.counts();
+    assert_eq!(counts.get(ConnectionError::Refused), 3);
+    assert_eq!(counts.get(ConnectionError::Reset), 0);
+    assert_eq!(counts.to_string(), "refused: 3, timed out: 1, unreachable: 1");
+    assert_eq!(serde_json::to_string(&counts).unwrap(), r#"{"refused":3,"timedOut":1,"unreachable":1}"#);
+    assert!(ConnectionErrors::default().is_empty());
+  }
+}
@@ -0,0 +1,209 @@
+use std::collections::BTreeSet;
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use serde::Serialize;
+
+use crate::error::ScannerError;
+use crate::output::OutputFormat;
+use crate::port::Protocol;
+use crate::scanner::IpScanResult;
+
+/// Port of a host that changed between two scans
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize)]
+pub struct PortChange {
+  pub ip: IpAddr,
+  pub protocol: Protocol,
+  pub port: u16,
+}
+
+impl Display for PortChange {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{} {}/{}", self.ip, self.port, self.protocol)
+  }
+}
+
+/// Changes between a previous scan and a new one
+///
+/// A host is there when it was up, a port when it was open (`open|filtered`
+/// included). The ports a host didn't have the time to probe are not
+/// reported as closed, their state being unknown.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanDiff {
+  /// Hosts up in the new scan only
+  pub new_hosts: Vec<IpAddr>,
+
+  /// Hosts up in the previous scan only
+  pub vanished_hosts: Vec<IpAddr>,
+
+  /// Ports open in the new scan only
+  pub opened_ports: Vec<PortChange>,
+
+  /// Ports open in the previous scan only
+  pub closed_ports: Vec<PortChange>,
+}
+
+impl ScanDiff {
+  /// Compare the results of a previous scan with the ones of a new scan
+  pub fn new(previous: &[IpScanResult], current: &[IpScanResult]) -> Self {
+    let previous_hosts = up_hosts(previous);
+    let current_hosts = up_hosts(current);
+    let previous_ports = open_ports(previous);
+    let current_ports = open_ports(current);
+
+    let unscanned = current
+      .iter()
+      .flat_map(|result| result.unscanned_ports.iter().map(|port| PortChange { ip: result.ip, protocol: port.protocol, port: port.port }))
+      .collect::<BTreeSet<PortChange>>();
+
+    Self {
+      new_hosts: current_hosts.difference(&previous_hosts).copied().collect(),
+      vanished_hosts: previous_hosts.difference(&current_hosts).copied().collect(),
+      opened_ports: current_ports.difference(&previous_ports).copied().collect(),
+      closed_ports: previous_ports.difference(&current_ports).filter(|port| !unscanned.contains(port)).copied().collect(),
+    }
+  }
+
+  /// Serialize the changes, the formats other than JSON and YAML giving the text, one change per line
+  pub fn serialize(&self, format: OutputFormat) -> Result<String, ScannerError> {
+    match format {
+      OutputFormat::Json => serde_json::to_string_pretty(self).map(|json| json + "\n").map_err(|err| ScannerError::Serialization(err.to_string())),
+      OutputFormat::Yaml => serde_yaml::to_string(self).map_err(|err| ScannerError::Serialization(err.to_string())),
+      _ => Ok(self.to_string()),
+    }
+  }
+
+  /// Only what appeared since the previous scan, the new hosts and the opened ports
+  pub fn additions(self) -> Self {
+    Self { vanished_hosts: Vec::new(), closed_ports: Vec::new(), ..self }
+  }
+
+  /// Whether nothing changed
+  pub fn is_empty(&self) -> bool {
+    self.new_hosts.is_empty() && self.vanished_hosts.is_empty() && self.opened_ports.is_empty() && self.closed_ports.is_empty()
+  }
+}
+
+/// One change per line, `+` for what appeared and `-` for what is gone
+impl Display for ScanDiff {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    if self.is_empty() {
+      return writeln!(f, "No changes since the previous scan");
+    }
+
+    for ip in &self.new_hosts {
+      writeln!(f, "+ host {}", ip)?;
+    }
+    for ip in &self.vanished_hosts {
+      writeln!(f, "- host {}", ip)?;
+    }
+    for port in &self.opened_ports {
+      writeln!(f, "+ {}", port)?;
+    }
+    for port in &self.closed_ports {
+      writeln!(f, "- {}", port)?;
+    }
+    Ok(())
+  }
+}
+
+/// Clap value parser for the baseline of `--alert-on-new`, which has to be a report that can be read back
+pub fn parse_baseline_path(path: &str) -> Result<String, String> {
+  match OutputFormat::from_path(path) {
+    OutputFormat::Json | OutputFormat::Yaml => Ok(path.to_string()),
+    format => Err(format!("the baseline is written as {:?}, which can't be read back, use a .json or .yaml file", format)),
+  }
+}
+
+fn up_hosts(results: &[IpScanResult]) -> BTreeSet<IpAddr> {
+  results.iter().filter(|result| result.up).map(|result| result.ip).collect()
+}
+
+fn open_ports(results: &[IpScanResult]) -> BTreeSet<PortChange> {
+  results
+    .iter()
+    .flat_map(|result| result.open_ports.iter().map(|port| PortChange { ip: result.ip, protocol: port.protocol, port: port.port }))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::port::{PortResult, PortState};
+
+  fn host(ip: &str, up: bool, ports: &[u16]) -> IpScanResult {
+    IpScanResult {
+      up,
+      open_ports: ports.iter().map(|port| PortResult::new(*port, Protocol::Tcp, PortState::Open)).collect(),
+      ..IpScanResult::new(ip.parse().unwrap())
+    }
+  }
+
+  fn change(ip: &str, port: u16) -> PortChange {
+    PortChange { ip: ip.parse().unwrap(), protocol: Protocol::Tcp, port }
+  }
+
+  #[test]
+  fn test_diff() {
+    let previous = vec![host("192.168.1.1", true, &[22, 80]), host("192.168.1.2", true, &[443]), host("192.168.1.3", false, &[])];
+    let current = vec![host("192.168.1.1", true, &[22, 8080]), host("192.168.1.2", false, &[]), host("192.168.1.3", true, &[])];
+
+    let diff = ScanDiff::new(&previous, &current);
+
+    assert_eq!(diff.new_hosts, vec!["192.168.1.3".parse::<IpAddr>().unwrap()]);
+    assert_eq!(diff.vanished_hosts, vec!["192.168.1.2".parse::<IpAddr>().unwrap()]);
+    assert_eq!(diff.opened_ports, vec![change("192.168.1.1", 8080)]);
+    assert_eq!(diff.closed_ports, vec![change("192.168.1.1", 80), change("192.168.1.2", 443)]);
+    assert_eq!(diff.to_string(), "+ host 192.168.1.3\n- host 192.168.1.2\n+ 192.168.1.1 8080/tcp\n- 192.168.1.1 80/tcp\n- 192.168.1.2 443/tcp\n");
+  }
+
+  #[test]
+  fn test_diff_additions() {
+    let previous = vec![host("192.168.1.1", true, &[22, 80]), host("192.168.1.2", true, &[443])];
+    let current = vec![host("192.168.1.1", true, &[22, 8080]), host("192.168.1.3", true, &[])];
+
+    let additions = ScanDiff::new(&previous, &current).additions();
+
+    assert_eq!(additions.to_string(), "+ host 192.168.1.3\n+ 192.168.1.1 8080/tcp\n");
+    // Only vanished hosts and closed ports
+    assert!(ScanDiff::new(&previous, &previous[..1]).additions().is_empty());
+  }
+
+  #[test]
+  fn test_parse_baseline_path() {
+    assert_eq!(parse_baseline_path("baseline.json"), Ok("baseline.json".to_string()));
+    assert!(parse_baseline_path("baseline.yml").is_ok());
+    assert!(parse_baseline_path("baseline.csv").is_err());
+  }
+
+  #[test]
+  fn test_diff_unchanged() {
+    let results = vec![host("10.0.0.1", true, &[22])];
+    let diff = ScanDiff::new(&results, &results);
+
+    assert!(diff.is_empty());
+    assert_eq!(diff.to_string(), "No changes since the previous scan\n");
+  }
+
+  #[test]
+  fn test_diff_unscanned_ports_not_closed() {
+    let previous = vec![host("10.0.0.1", true, &[22, 80])];
+    let mut current = host("10.0.0.1", true, &[22]);
+    current.timed_out = true;
+    current.unscanned_ports = vec![PortResult::new(80, Protocol::Tcp, PortState::Unknown)];
+
+    assert!(ScanDiff::new(&previous, &[current]).is_empty());
+  }
+
+  #[test]
+  fn test_diff_json() {
+    let diff = ScanDiff::new(&[], &[host("10.0.0.1", true, &[22])]);
+
+    assert_eq!(
+      serde_json::to_string(&diff).unwrap(),
+      r#"{"newHosts":["10.0.0.1"],"vanishedHosts":[],"openedPorts":[{"ip":"10.0.0.1","protocol":"tcp","port":22}],"closedPorts":[]}"#
+    );
+    assert_eq!(diff.serialize(OutputFormat::Csv).unwrap(), "+ host 10.0.0.1\n+ 10.0.0.1 22/tcp\n");
+  }
+}
@@ -0,0 +1,102 @@
+use std::fmt::{Display, Formatter};
+use std::net::{IpAddr, SocketAddr};
+
+use crate::cidr::CidrParseError;
+use crate::reserved::Reserved;
+
+/// Errors returned by the scanner
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ScannerError {
+  /// No IP was given to scan
+  MissingTarget,
+
+  /// The IP given is not a valid IP address
+  InvalidIp(String),
+
+  /// The network given is not a valid CIDR notation
+  InvalidCidr(String),
+
+  /// The range given is not two IPv4 addresses, the start before the end
+  InvalidRange(String),
+
+  /// The target with wildcards is not four octets or `*`, one of them at least being a wildcard
+  InvalidWildcard(String),
+
+  /// The ports given can't be scanned, such as port 0 or an empty list
+  InvalidPorts(String),
+
+  /// The network given has more addresses than the scanner accepts
+  NetworkTooLarge(String),
+
+  /// The targets include a reserved range, multicast for instance, and reserved ranges are not allowed
+  ReservedTarget { network: String, kind: Reserved },
+
+  /// The host name given couldn't be resolved to an address
+  Resolution { host: String, message: String },
+
+  /// The targets file couldn't be read, or one of its lines is not a valid target
+  TargetsFile { path: String, line: Option<usize>, message: String },
+
+  /// The SOCKS5 proxy couldn't be reached or refused our credentials
+  Proxy { proxy: String, message: String },
+
+  /// The source address is not of the family of a target, an IPv4 can't reach an IPv6 target
+  SourceFamily { source: IpAddr, target: String },
+
+  /// The connections couldn't be bound to the source address or interface
+  Source { source: String, message: String },
+
+  /// A saved report couldn't be read or is not a valid report
+  InvalidReport { path: Option<String>, message: String },
+
+  /// The report couldn't be serialized
+  Serialization(String),
+
+  /// The report couldn't be written to the output file
+  Output { path: String, message: String },
+
+  /// The checkpoint couldn't be read or written, or is the one of another scan
+  Checkpoint { path: String, message: String },
+
+  /// The dashboard couldn't listen on its address
+  Serve { address: SocketAddr, message: String },
+
+  /// A single port couldn't be checked, the connection failing for another reason than the port being closed or filtered
+  Connection { address: SocketAddr, message: String },
+}
+
+impl Display for ScannerError {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      ScannerError::MissingTarget => write!(f, "No IP to scan"),
+      ScannerError::InvalidIp(ip) => write!(f, "Invalid IP address: {}", ip),
+      ScannerError::InvalidCidr(cidr) => write!(f, "Invalid CIDR notation: {}", cidr),
+      ScannerError::InvalidRange(message) => write!(f, "Invalid IP range: {}", message),
+      ScannerError::InvalidWildcard(message) => write!(f, "Invalid wildcard target: {}", message),
+      ScannerError::InvalidPorts(message) => write!(f, "Invalid ports: {}", message),
+      ScannerError::NetworkTooLarge(cidr) => write!(f, "Network too large to be scanned: {}", cidr),
+      ScannerError::ReservedTarget { network, kind } => write!(f, "Refusing to scan addresses of {} ({} range), use --allow-reserved to scan them anyway", network, kind),
+      ScannerError::Resolution { host, message } => write!(f, "Unable to resolve {}: {}", host, message),
+      ScannerError::TargetsFile { path, line: Some(line), message } => write!(f, "Invalid target in {} at line {}: {}", path, line, message),
+      ScannerError::TargetsFile { path, line: None, message } => write!(f, "Unable to read the targets from {}: {}", path, message),
+      ScannerError::Proxy { proxy, message } => write!(f, "Unable to use the proxy {}: {}", proxy, message),
+      ScannerError::SourceFamily { source, target } => write!(f, "The source address {} can't reach {}, which is of another address family", source, target),
+      ScannerError::Source { source, message } => write!(f, "Unable to bind to {}: {}", source, message),
+      ScannerError::InvalidReport { path: Some(path), message } => write!(f, "Unable to read the report from {}: {}", path, message),
+      ScannerError::InvalidReport { path: None, message } => write!(f, "Unable to read the report: {}", message),
+      ScannerError::Serialization(message) => write!(f, "Unable to serialize the report: {}", message),
+      ScannerError::Output { path, message } => write!(f, "Unable to write the report to {}: {}", path, message),
+      ScannerError::Checkpoint { path, message } => write!(f, "Unable to use the checkpoint {}: {}", path, message),
+      ScannerError::Serve { address, message } => write!(f, "Unable to serve the dashboard on {}: {}", address, message),
+      ScannerError::Connection { address, message } => write!(f, "Unable to check {}: {}", address, message),
+    }
+  }
+}
+
+impl std::error::Error for ScannerError {}
+
+impl From<CidrParseError> for ScannerError {
+  fn from(err: CidrParseError) -> Self {
+    ScannerError::InvalidCidr(err.0)
+  }
+}
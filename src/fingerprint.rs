@@ -0,0 +1,181 @@
+use std::fmt::{Display, Formatter};
+
+use serde::{Deserialize, Serialize};
+
+/// Service guessed from a banner
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct Service {
+  /// Protocol spoken by the service (`ssh`, `ftp`, `smtp`, …)
+  pub name: String,
+
+  /// Software behind the service, when the banner tells it
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub product: Option<String>,
+
+  /// Version of the software, when the banner tells it
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub version: Option<String>,
+}
+
+impl Display for Service {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.name)?;
+    if let Some(product) = &self.product {
+      write!(f, "/{}", product)?;
+    }
+    if let Some(version) = &self.version {
+      write!(f, " {}", version)?;
+    }
+    Ok(())
+  }
+}
+
+/// Pattern of a banner and the service it belongs to
+///
+/// Patterns match the whole banner, ignoring case: `*` matches anything,
+/// possibly nothing, and `{version}` matches a version number (a digit
+/// followed by letters, digits, `.`, `-` and `_`) which is kept as the
+/// version of the service.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rule {
+  pub pattern: &'static str,
+  pub service: &'static str,
+  pub product: Option<&'static str>,
+}
+
+const fn rule(pattern: &'static str, service: &'static str, product: Option<&'static str>) -> Rule {
+  Rule { pattern, service, product }
+}
+
+/// Known banners, the first matching rule wins so the specific ones come before the generic ones
+pub const RULES: &[Rule] = &[
+  rule("SSH-*-OpenSSH_{version}*", "ssh", Some("OpenSSH")),
+  rule("SSH-*-dropbear_{version}*", "ssh", Some("Dropbear")),
+  rule("SSH-*-libssh_{version}*", "ssh", Some("libssh")),
+  rule("SSH-*", "ssh", None),
+  rule("220*ProFTPD {version} *", "ftp", Some("ProFTPD")),
+  rule("220*(vsFTPd {version})*", "ftp", Some("vsftpd")),
+  rule("220*Pure-FTPd*", "ftp", Some("Pure-FTPd")),
+  rule("220*FileZilla Server {version}*", "ftp", Some("FileZilla Server")),
+  rule("220*ESMTP Postfix*", "smtp", Some("Postfix")),
+  rule("220*ESMTP Exim {version} *", "smtp", Some("Exim")),
+  rule("220*Microsoft ESMTP MAIL Service*", "smtp", Some("Exchange")),
+  rule("220*ESMTP*", "smtp", None),
+  rule("220*FTP*", "ftp", None),
+  rule("* OK*Dovecot*", "imap", Some("Dovecot")),
+  rule("* OK*IMAP*", "imap", None),
+  rule("+OK*Dovecot*", "pop3", Some("Dovecot")),
+  rule("+OK*", "pop3", None),
+  rule("*{version}-MariaDB*", "mysql", Some("MariaDB")),
+  rule("*{version}*mysql_native_password*", "mysql", Some("MySQL")),
+  rule("RFB {version}", "vnc", None),
+];
+
+/// Guess the service sending a banner with the built-in rules
+pub fn identify(banner: &str) -> Option<Service> {
+  identify_with(RULES, banner)
+}
+
+/// Guess the service sending a banner with the first matching rule of `rules`
+pub fn identify_with(rules: &[Rule], banner: &str) -> Option<Service> {
+  rules.iter().find_map(|rule| {
+    let version = matches(rule.pattern.as_bytes(), banner.as_bytes())?;
+
+    Some(Service {
+      name: rule.service.to_string(),
+      product: rule.product.map(str::to_string),
+      version: version.map(|(start, end)| banner[start..end].to_string()),
+    })
+  })
+}
+
+const VERSION: &[u8] = b"{version}";
+
+/// Whether a pattern matches the whole text
+/// Return the range of the text matched by `{version}`, if the pattern has one
+fn matches(pattern: &[u8], text: &[u8]) -> Option<Option<(usize, usize)>> {
+  match_from(pattern, text, 0)
+}
+
+fn match_from(pattern: &[u8], text: &[u8], position: usize) -> Option<Option<(usize, usize)>> {
+  let rest = &text[position..];
+
+  if pattern.is_empty() {
+    return rest.is_empty().then_some(None);
+  }
+
+  if pattern[0] == b'*' {
+    return (position..=text.len()).find_map(|next| match_from(&pattern[1..], text, next));
+  }
+
+  if let Some(pattern) = pattern.strip_prefix(VERSION) {
+    if !rest.first().is_some_and(u8::is_ascii_digit) {
+      return None;
+    }
+    let length = rest.iter().take_while(|c| c.is_ascii_alphanumeric() || matches!(c, b'.' | b'-' | b'_')).count();
+
+    // Longest version first, giving back characters the rest of the pattern needs
+    return (1..=length).rev().find_map(|length| {
+      let end = position + length;
+      match_from(pattern, text, end).map(|version| version.or(Some((position, end))))
+    });
+  }
+
+  match rest.first() {
+    Some(c) if c.eq_ignore_ascii_case(&pattern[0]) => match_from(&pattern[1..], text, position + 1),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn service(name: &str, product: Option<&str>, version: Option<&str>) -> Option<Service> {
+    Some(Service { name: name.to_string(), product: product.map(str::to_string), version: version.map(str::to_string) })
+  }
+
+  #[test]
+  fn test_identify_ssh() {
+    assert_eq!(identify("SSH-2.0-OpenSSH_8.9p1 Ubuntu-3ubuntu0.1"), service("ssh", Some("OpenSSH"), Some("8.9p1")));
+    assert_eq!(identify("SSH-2.0-dropbear_2022.83"), service("ssh", Some("Dropbear"), Some("2022.83")));
+    assert_eq!(identify("SSH-2.0-Cisco-1.25"), service("ssh", None, None));
+  }
+
+  #[test]
+  fn test_identify_mail_and_ftp() {
+    assert_eq!(identify("220 mail.example.com ESMTP Postfix (Ubuntu)"), service("smtp", Some("Postfix"), None));
+    assert_eq!(identify("220 mx.example.com ESMTP Exim 4.96 Mon, 14 Oct 2026 10:00:00 +0000"), service("smtp", Some("Exim"), Some("4.96")));
+    assert_eq!(identify("220 (vsFTPd 3.0.5)"), service("ftp", Some("vsftpd"), Some("3.0.5")));
+    assert_eq!(identify("220 ProFTPD 1.3.8 Server (Debian) [::ffff:10.0.0.2]"), service("ftp", Some("ProFTPD"), Some("1.3.8")));
+    assert_eq!(identify("* OK [CAPABILITY IMAP4rev1 SASL-IR LOGIN-REFERRALS ID ENABLE IDLE] Dovecot (Ubuntu) ready."), service("imap", Some("Dovecot"), None));
+    assert_eq!(identify("+OK POP3 server ready"), service("pop3", None, None));
+  }
+
+  #[test]
+  fn test_identify_databases_and_vnc() {
+    assert_eq!(identify("J 5.5.5-10.11.6-MariaDB-0+deb12u1 mysql_native_password"), service("mysql", Some("MariaDB"), Some("5.5.5-10.11.6")));
+    assert_eq!(identify("J 8.0.35 mysql_native_password"), service("mysql", Some("MySQL"), Some("8.0.35")));
+    assert_eq!(identify("RFB 003.008"), service("vnc", None, Some("003.008")));
+  }
+
+  #[test]
+  fn test_identify_unknown() {
+    assert_eq!(identify("Welcome to the machine"), None);
+    assert_eq!(identify(""), None);
+  }
+
+  #[test]
+  fn test_identify_with_custom_rules() {
+    let rules = [rule("*Redis*v={version} *", "redis", Some("Redis"))];
+
+    assert_eq!(identify_with(&rules, "# Server redis_version:7.2.4 Redis v=7.2.4 sha=00000000"), service("redis", Some("Redis"), Some("7.2.4")));
+    assert_eq!(identify_with(&rules, "SSH-2.0-OpenSSH_8.9"), None);
+  }
+
+  #[test]
+  fn test_display_service() {
+    assert_eq!(service("ssh", Some("OpenSSH"), Some("8.9p1")).unwrap().to_string(), "ssh/OpenSSH 8.9p1");
+    assert_eq!(service("pop3", None, None).unwrap().to_string(), "pop3");
+  }
+}
@@ -0,0 +1,416 @@
+use std::fmt::{Display, Formatter};
+use std::future::Future;
+use std::io;
+use std::net::{IpAddr, SocketAddr};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use tokio::time::Instant;
+
+use crate::banner;
+
+/// Ports probed with an HTTP request when none are given
+///
+/// HTTPS ports are not part of it: the request is sent in the clear, most
+/// TLS servers answering it with an error page at best.
+pub const HTTP_PORTS: [u16; 5] = [80, 8000, 8008, 8080, 8888];
+
+/// Maximum number of bytes read from a response, enough for the headers and the `<title>`
+const MAX_RESPONSE_SIZE: usize = 16 * 1024;
+
+/// Maximum number of characters kept from a title
+const MAX_TITLE_LENGTH: usize = 128;
+
+/// Web server found on a port, with the page it serves at `/`
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HttpInfo {
+  /// Status code of the response, the one of the redirected page when a redirect was followed
+  pub status: u16,
+
+  /// Title of the HTML page, when there is one
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub title: Option<String>,
+
+  /// Location of the redirect of `/`, followed or not
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub location: Option<String>,
+}
+
+impl Display for HttpInfo {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "http {}", self.status)?;
+    if let Some(title) = &self.title {
+      write!(f, " \"{}\"", title)?;
+    }
+    if let Some(location) = &self.location {
+      write!(f, " -> {}", location)?;
+    }
+    Ok(())
+  }
+}
+
+/// Request `/` on a stream connected to `address` and read the status and title of the response
+/// A redirect to the same host is followed once, on the same connection when the server keeps it
+/// open for a redirect to the same port, on a new one opened with `connect` otherwise
+/// The response is read for `timeout`, what a slow server sent by then being kept
+/// Return `None` when the service doesn't speak HTTP or didn't answer before the timeout
+pub async fn probe_http<C, Fut>(stream: &mut TcpStream, address: SocketAddr, server_name: Option<&str>, timeout: Duration, connect: C) -> Option<HttpInfo>
+where
+  C: FnOnce(SocketAddr) -> Fut,
+  Fut: Future<Output = io::Result<TcpStream>>,
+{
+  let host = host_header(server_name, address);
+
+  let response = request(stream, &host, "/", Instant::now() + timeout).await?;
+  let mut info = parse_response(&response.content)?;
+
+  let Some(location) = info.location.clone() else { return Some(info) };
+  let Some((port, path)) = redirect_target(&location, &host, address.port()) else { return Some(info) };
+
+  // A redirect that can't be followed still tells the server is up, keep the first response
+  let deadline = Instant::now() + timeout;
+  let followed = tokio::time::timeout_at(deadline, async {
+    // Most redirects of `/` are to a login page on the same port: reusing the connection saves the
+    // handshake of a new one and its round trip, as `benches/scan.rs` measures
+    if port == address.port() && response.reusable {
+      let redirected = request(stream, &host, &path, deadline).await.and_then(|response| parse_response(&response.content));
+      if redirected.is_some() {
+        return redirected;
+      }
+    }
+
+    // The server didn't keep the connection open after all
+    let address = SocketAddr::new(address.ip(), port);
+    let mut stream = connect(address).await.ok()?;
+    request(&mut stream, &host_header(server_name, address), &path, deadline).await.and_then(|response| parse_response(&response.content))
+  });
+
+  if let Some(redirected) = followed.await.ok().flatten() {
+    info = HttpInfo { location: Some(location), ..redirected };
+  }
+
+  Some(info)
+}
+
+/// Response read from a connection
+struct Response {
+  content: Vec<u8>,
+
+  /// Whether the whole response was read and the server keeps the connection open for another request
+  reusable: bool,
+}
+
+/// Send a `GET` request and read the response until its end, when its length is known, or until the
+/// server closes the connection
+/// Return what was read once the response is larger than `MAX_RESPONSE_SIZE` or at the deadline,
+/// `None` when nothing was
+async fn request(stream: &mut TcpStream, host: &str, path: &str, deadline: Instant) -> Option<Response> {
+  let request = format!(
+    "GET {} HTTP/1.1\r\nHost: {}\r\nUser-Agent: skanner/{}\r\nAccept: text/html,*/*\r\nConnection: keep-alive\r\n\r\n",
+    path,
+    host,
+    env!("CARGO_PKG_VERSION")
+  );
+  tokio::time::timeout_at(deadline, stream.write_all(request.as_bytes())).await.ok()?.ok()?;
+
+  let mut content = Vec::new();
+  let mut buffer = [0_u8; 4096];
+  while content.len() < MAX_RESPONSE_SIZE {
+    if let Some((length, keep_alive)) = response_end(&content) {
+      content.truncate(length);
+      return Some(Response { content, reusable: keep_alive });
+    }
+
+    match tokio::time::timeout_at(deadline, stream.read(&mut buffer)).await {
+      Ok(Ok(0)) => break,
+      Ok(Ok(size)) => content.extend_from_slice(&buffer[..size]),
+      Ok(Err(_)) | Err(_) if !content.is_empty() => break,
+      Ok(Err(_)) | Err(_) => return None,
+    }
+  }
+
+  Some(Response { content, reusable: false })
+}
+
+/// Length of a response once it is fully read, with whether the server keeps the connection open
+/// `None` while it is incomplete, or when only the server closing the connection ends it
+fn response_end(content: &[u8]) -> Option<(usize, bool)> {
+  let head_end = content.windows(4).position(|window| window == b"\r\n\r\n")? + 4;
+  let head = String::from_utf8_lossy(&content[..head_end]);
+  let mut lines = head.lines();
+
+  let mut status_line = lines.next()?.split_whitespace();
+  let version = status_line.next()?;
+  let status = status_line.next()?.parse::<u16>().ok()?;
+
+  let headers = lines.filter_map(|line| line.split_once(':')).map(|(name, value)| (name.trim().to_ascii_lowercase(), value.trim().to_ascii_lowercase())).collect::<Vec<(String, String)>>();
+  let header = |name: &str| headers.iter().find(|(header, _)| header == name).map(|(_, value)| value.as_str());
+
+  // HTTP/1.1 connections are persistent unless closed, HTTP/1.0 ones only when asked
+  let keep_alive = match header("connection") {
+    Some(connection) => connection.contains("keep-alive") || (version == "HTTP/1.1" && !connection.contains("close")),
+    None => version == "HTTP/1.1",
+  };
+
+  let body = &content[head_end..];
+  if matches!(status, 100..=199 | 204 | 304) {
+    return Some((head_end, keep_alive));
+  }
+  if header("transfer-encoding").is_some_and(|encoding| encoding.contains("chunked")) {
+    let end = if body.starts_with(b"0\r\n\r\n") { Some(5) } else { body.windows(7).position(|window| window == b"\r\n0\r\n\r\n").map(|index| index + 7) };
+    return end.map(|end| (head_end + end, keep_alive));
+  }
+
+  let length = header("content-length")?.parse::<usize>().ok()?;
+  (body.len() >= length).then_some((head_end + length, keep_alive))
+}
+
+/// Value of the `Host` header for the name of the server, or for its IP when there is none
+/// The port is only given when it is not the HTTP one
+fn host_header(server_name: Option<&str>, address: SocketAddr) -> String {
+  let host = match (server_name, address.ip()) {
+    (Some(name), _) => name.to_string(),
+    (None, IpAddr::V4(ip)) => ip.to_string(),
+    (None, IpAddr::V6(ip)) => format!("[{}]", ip),
+  };
+
+  match address.port() {
+    80 => host,
+    port => format!("{}:{}", host, port),
+  }
+}
+
+/// Status, title and redirect location of a response
+/// Return `None` when the response doesn't start with an HTTP status line
+pub fn parse_response(response: &[u8]) -> Option<HttpInfo> {
+  let text = String::from_utf8_lossy(response);
+
+  let (head, body) = match text.find("\r\n\r\n") {
+    Some(end) => (&text[..end], &text[end + 4..]),
+    None => (&text[..], ""),
+  };
+  let mut lines = head.lines();
+
+  // `HTTP/1.1 200 OK`
+  let mut status_line = lines.next()?.split_whitespace();
+  if !status_line.next()?.starts_with("HTTP/") {
+    return None;
+  }
+  let status = status_line.next()?.parse::<u16>().ok().filter(|status| (100..=599).contains(status))?;
+
+  let location = match status {
+    300..=399 => lines
+      .filter_map(|line| line.split_once(':'))
+      .find(|(name, _)| name.trim().eq_ignore_ascii_case("location"))
+      .map(|(_, value)| value.trim().to_string())
+      .filter(|location| !location.is_empty()),
+    _ => None,
+  };
+
+  Some(HttpInfo { status, title: parse_title(body), location })
+}
+
+/// Content of the `<title>` of an HTML page, whitespaces collapsed
+fn parse_title(body: &str) -> Option<String> {
+  let lowercase = body.to_ascii_lowercase();
+
+  let start = lowercase.find("<title")?;
+  let start = start + lowercase[start..].find('>')? + 1;
+  let end = start + lowercase[start..].find("</title").unwrap_or(lowercase.len() - start);
+
+  let title = banner::sanitize(&body.as_bytes()[start..end]);
+  if title.is_empty() {
+    None
+  } else {
+    Some(title.chars().take(MAX_TITLE_LENGTH).collect())
+  }
+}
+
+/// Port and path of a redirect to follow, `None` for a redirect to another host or to HTTPS
+fn redirect_target(location: &str, host: &str, port: u16) -> Option<(u16, String)> {
+  if location.starts_with('/') && !location.starts_with("//") {
+    return Some((port, location.to_string()));
+  }
+
+  let rest = location.strip_prefix("http://")?;
+  let (authority, path) = match rest.find('/') {
+    Some(index) => (&rest[..index], rest[index..].to_string()),
+    None => (rest, "/".to_string()),
+  };
+
+  let (name, target_port) = split_authority(authority)?;
+  let (host_name, _) = split_authority(host)?;
+  if !name.eq_ignore_ascii_case(host_name) {
+    return None;
+  }
+
+  Some((target_port, path))
+}
+
+/// Host and port of an authority (`example.com:8080`, `[fd00::1]`), port 80 when none is given
+fn split_authority(authority: &str) -> Option<(&str, u16)> {
+  let (name, port) = match authority.rfind(':') {
+    Some(index) if !authority[index..].contains(']') => (&authority[..index], Some(&authority[index + 1..])),
+    _ => (authority, None),
+  };
+
+  match port {
+    Some(port) => Some((name, port.parse().ok()?)),
+    None => Some((name, 80)),
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::{Read, Write};
+  use std::net::TcpListener;
+
+  /// Serve the given responses, one per connection, and return the address of the server
+  fn serve(responses: Vec<&'static str>) -> SocketAddr {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    std::thread::spawn(move || {
+      for response in responses {
+        let (mut stream, _) = listener.accept().unwrap();
+        let mut request = [0_u8; 1024];
+        let _ = stream.read(&mut request).unwrap();
+        stream.write_all(response.as_bytes()).unwrap();
+      }
+    });
+
+    address
+  }
+
+  #[test]
+  fn test_parse_response() {
+    let response = b"HTTP/1.1 200 OK\r\nContent-Type: text/html\r\n\r\n<html><head><TITLE>\n  Router   login\n</TITLE></head></html>";
+
+    assert_eq!(parse_response(response), Some(HttpInfo { status: 200, title: Some("Router login".to_string()), location: None }));
+  }
+
+  #[test]
+  fn test_parse_response_redirect() {
+    let response = b"HTTP/1.0 301 Moved Permanently\r\nlocation: https://example.com/\r\n\r\n";
+
+    assert_eq!(parse_response(response), Some(HttpInfo { status: 301, title: None, location: Some("https://example.com/".to_string()) }));
+  }
+
+  #[test]
+  fn test_parse_response_not_http() {
+    assert_eq!(parse_response(b"SSH-2.0-OpenSSH_8.9\r\n"), None);
+    assert_eq!(parse_response(b"HTTP/1.1 OK\r\n\r\n"), None);
+    assert_eq!(parse_response(b""), None);
+  }
+
+  #[test]
+  fn test_redirect_target() {
+    assert_eq!(redirect_target("/login", "192.168.1.1:8080", 8080), Some((8080, "/login".to_string())));
+    assert_eq!(redirect_target("http://router.lan/admin", "router.lan", 80), Some((80, "/admin".to_string())));
+    assert_eq!(redirect_target("http://router.lan:8000", "router.lan", 80), Some((8000, "/".to_string())));
+    assert_eq!(redirect_target("https://router.lan/", "router.lan", 80), None);
+    assert_eq!(redirect_target("http://example.com/", "router.lan", 80), None);
+    assert_eq!(redirect_target("//example.com/", "router.lan", 80), None);
+  }
+
+  #[test]
+  fn test_host_header() {
+    assert_eq!(host_header(None, "192.168.1.1:80".parse().unwrap()), "192.168.1.1");
+    assert_eq!(host_header(Some("router.lan"), "192.168.1.1:8080".parse().unwrap()), "router.lan:8080");
+    assert_eq!(host_header(None, "[fd00::1]:8080".parse().unwrap()), "[fd00::1]:8080");
+  }
+
+  #[tokio::test]
+  async fn test_probe_http_follows_redirect() {
+    let address = serve(vec![
+      "HTTP/1.1 302 Found\r\nLocation: /login\r\nContent-Length: 0\r\n\r\n",
+      "HTTP/1.1 200 OK\r\n\r\n<title>Login</title>",
+    ]);
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let info = probe_http(&mut stream, address, None, Duration::from_secs(1), TcpStream::connect::<SocketAddr>).await;
+
+    assert_eq!(info, Some(HttpInfo { status: 200, title: Some("Login".to_string()), location: Some("/login".to_string()) }));
+  }
+
+  #[test]
+  fn test_response_end() {
+    let head = b"HTTP/1.1 302 Found\r\nLocation: /login\r\nContent-Length: 5\r\n\r\n";
+    assert_eq!(response_end(head), None);
+    assert_eq!(response_end(&[&head[..], b"Found"].concat()), Some((head.len() + 5, true)));
+
+    let closed = b"HTTP/1.1 200 OK\r\nConnection: close\r\nContent-Length: 0\r\n\r\n";
+    assert_eq!(response_end(closed), Some((closed.len(), false)));
+    let empty = b"HTTP/1.0 204 No Content\r\n\r\n";
+    assert_eq!(response_end(empty), Some((empty.len(), false)));
+    let kept = b"HTTP/1.0 200 OK\r\nConnection: Keep-Alive\r\nContent-Length: 0\r\n\r\n";
+    assert_eq!(response_end(kept), Some((kept.len(), true)));
+
+    let chunked = b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n4\r\nabcd\r\n0\r\n\r\n";
+    assert_eq!(response_end(chunked), Some((chunked.len(), true)));
+    assert_eq!(response_end(&chunked[..chunked.len() - 2]), None);
+
+    // Only the server closing the connection ends it
+    assert_eq!(response_end(b"HTTP/1.1 200 OK\r\n\r\n<title>"), None);
+  }
+
+  #[tokio::test]
+  async fn test_probe_http_redirect_on_same_connection() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+    let server = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut request = [0_u8; 1024];
+      for response in ["HTTP/1.1 302 Found\r\nLocation: /login\r\nContent-Length: 0\r\n\r\n", "HTTP/1.1 200 OK\r\nContent-Length: 20\r\n\r\n<title>Login</title>"] {
+        let _ = stream.read(&mut request).unwrap();
+        stream.write_all(response.as_bytes()).unwrap();
+      }
+      // No other connection is accepted
+      listener.set_nonblocking(true).unwrap();
+      listener.accept().is_err()
+    });
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let info = probe_http(&mut stream, address, None, Duration::from_secs(1), TcpStream::connect::<SocketAddr>).await;
+
+    assert_eq!(info, Some(HttpInfo { status: 200, title: Some("Login".to_string()), location: Some("/login".to_string()) }));
+    assert!(server.join().unwrap());
+  }
+
+  #[tokio::test]
+  async fn test_probe_http_slow_server() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    // The head at once, then the page a few bytes at a time, never ending before the timeout
+    std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut request = [0_u8; 1024];
+      let _ = stream.read(&mut request).unwrap();
+      stream.write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 4096\r\n\r\n<title>Slow</title>").unwrap();
+      while stream.write_all(b"<p>").is_ok() {
+        std::thread::sleep(Duration::from_millis(30));
+      }
+    });
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let start = std::time::Instant::now();
+    let info = probe_http(&mut stream, address, None, Duration::from_millis(200), TcpStream::connect::<SocketAddr>).await;
+
+    assert!(start.elapsed() < Duration::from_millis(400), "{:?}", start.elapsed());
+    assert_eq!(info, Some(HttpInfo { status: 200, title: Some("Slow".to_string()), location: None }));
+  }
+
+  #[tokio::test]
+  async fn test_probe_http_not_http() {
+    let address = serve(vec!["220 mail.example.com ESMTP\r\n"]);
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+
+    assert_eq!(probe_http(&mut stream, address, None, Duration::from_secs(1), TcpStream::connect::<SocketAddr>).await, None);
+  }
+}
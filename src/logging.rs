@@ -0,0 +1,179 @@
+//! Diagnostic messages of the scanner, written to stderr
+//!
+//! The verbosity is set with `--quiet` and `--verbose`, or with `RUST_LOG`
+//! (`error`, `warn`, `info`, `debug`, `trace` or `off`), `skanner=debug`
+//! directives being understood as well so the variable can be shared with
+//! other tools. Messages logged while scanning
+//! a host are prefixed with its IP, the scans of several hosts running at the
+//! same time. The report itself stays on stdout.
+
+use std::fmt::{self, Display, Formatter};
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Environment variable setting the verbosity
+pub const LOG_ENV: &str = "RUST_LOG";
+
+/// Verbosity of a message, from the most to the least important
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Level {
+  Error = 1,
+  Warn,
+  Info,
+  Debug,
+  Trace,
+}
+
+impl Display for Level {
+  fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+    let name = match self {
+      Level::Error => "ERROR",
+      Level::Warn => "WARN",
+      Level::Info => "INFO",
+      Level::Debug => "DEBUG",
+      Level::Trace => "TRACE",
+    };
+    // Padded so the messages are aligned
+    f.pad(name)
+  }
+}
+
+/// Least important level written, 0 when nothing is
+static MAX_LEVEL: AtomicUsize = AtomicUsize::new(Level::Info as usize);
+
+tokio::task_local! {
+  /// Host scanned by the current task
+  static HOST: IpAddr;
+}
+
+/// Set the verbosity from `RUST_LOG`, defaulting to the one of the command line flags
+pub fn init(quiet: bool, verbose: u8) {
+  let level = std::env::var(LOG_ENV).ok().and_then(|filter| parse_filter(&filter)).unwrap_or(Some(default_level(quiet, verbose)));
+
+  set_max_level(level);
+}
+
+/// Verbosity of `--quiet` and of the number of `--verbose`, `info` when neither is given
+/// Quiet wins, only warnings and errors being written
+pub fn default_level(quiet: bool, verbose: u8) -> Level {
+  match (quiet, verbose) {
+    (true, _) => Level::Warn,
+    (false, 0) => Level::Info,
+    (false, 1) => Level::Debug,
+    (false, _) => Level::Trace,
+  }
+}
+
+/// Least important level written, `None` to write nothing
+pub fn set_max_level(level: Option<Level>) {
+  MAX_LEVEL.store(level.map_or(0, |level| level as usize), Ordering::Relaxed);
+}
+
+/// Whether messages of a level are written
+pub fn enabled(level: Level) -> bool {
+  level as usize <= MAX_LEVEL.load(Ordering::Relaxed)
+}
+
+/// Level of a `RUST_LOG` value, `Some(None)` for `off`
+/// Directives of other crates are ignored, `None` being returned when none is for this one
+pub fn parse_filter(filter: &str) -> Option<Option<Level>> {
+  filter.split(',').rev().find_map(|directive| {
+    let level = match directive.trim().split_once('=') {
+      Some((target, level)) if target.trim() == env!("CARGO_PKG_NAME") => level,
+      Some(_) => return None,
+      None => directive,
+    };
+
+    match level.trim().to_ascii_lowercase().as_str() {
+      "off" => Some(None),
+      "error" => Some(Some(Level::Error)),
+      "warn" => Some(Some(Level::Warn)),
+      "info" => Some(Some(Level::Info)),
+      "debug" => Some(Some(Level::Debug)),
+      "trace" => Some(Some(Level::Trace)),
+      _ => None,
+    }
+  })
+}
+
+/// Run a future with the messages it logs attributed to a host
+pub async fn with_host<F: Future>(ip: IpAddr, future: F) -> F::Output {
+  HOST.scope(ip, future).await
+}
+
+/// Prefix of a message, its level and the host being scanned if any
+fn prefix(level: Level) -> String {
+  match HOST.try_with(|ip| *ip) {
+    Ok(ip) => format!("{:<5} host{{ip={}}}:", level, ip),
+    Err(_) => format!("{:<5}", level),
+  }
+}
+
+/// Write a message to stderr if its level is enabled, use the macros rather than calling it
+///
+/// The `error!`, `warn!`, `info!`, `debug!` and `trace!` macros of this module
+/// are available in every module declared after it, taking `format!` arguments.
+pub fn write(level: Level, message: fmt::Arguments) {
+  if enabled(level) {
+    eprintln!("{} {}", prefix(level), message);
+  }
+}
+
+macro_rules! error {
+  ($($arg:tt)*) => { $crate::logging::write($crate::logging::Level::Error, format_args!($($arg)*)) };
+}
+
+macro_rules! warn {
+  ($($arg:tt)*) => { $crate::logging::write($crate::logging::Level::Warn, format_args!($($arg)*)) };
+}
+
+macro_rules! info {
+  ($($arg:tt)*) => { $crate::logging::write($crate::logging::Level::Info, format_args!($($arg)*)) };
+}
+
+macro_rules! debug {
+  ($($arg:tt)*) => { $crate::logging::write($crate::logging::Level::Debug, format_args!($($arg)*)) };
+}
+
+macro_rules! trace {
+  ($($arg:tt)*) => { $crate::logging::write($crate::logging::Level::Trace, format_args!($($arg)*)) };
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_filter() {
+    assert_eq!(parse_filter("debug"), Some(Some(Level::Debug)));
+    assert_eq!(parse_filter("OFF"), Some(None));
+    assert_eq!(parse_filter("hyper=warn,skanner=trace"), Some(Some(Level::Trace)));
+    assert_eq!(parse_filter("info,skanner=error"), Some(Some(Level::Error)));
+    assert_eq!(parse_filter("hyper=warn"), None);
+    assert_eq!(parse_filter("verbose"), None);
+  }
+
+  #[test]
+  fn test_default_level() {
+    assert_eq!(default_level(false, 0), Level::Info);
+    assert_eq!(default_level(false, 1), Level::Debug);
+    assert_eq!(default_level(false, 3), Level::Trace);
+    assert_eq!(default_level(true, 0), Level::Warn);
+    assert_eq!(default_level(true, 2), Level::Warn);
+  }
+
+  #[test]
+  fn test_level_order() {
+    assert!(Level::Error < Level::Warn && Level::Debug < Level::Trace);
+    assert_eq!(format!("{:<5}|", Level::Warn), "WARN |");
+  }
+
+  #[tokio::test]
+  async fn test_prefix_with_host() {
+    let ip: IpAddr = "192.168.1.1".parse().unwrap();
+
+    assert_eq!(prefix(Level::Info), "INFO ");
+    assert_eq!(with_host(ip, async { prefix(Level::Debug) }).await, "DEBUG host{ip=192.168.1.1}:");
+  }
+}
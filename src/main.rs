@@ -1,9 +1,24 @@
 use clap::Parser;
 
-use skanner::{Args, run};
+use skanner::{Args, EXIT_ERROR, dry_run, exit_code, run};
 
 #[tokio::main]
 async fn main() {
   let args = Args::parse();
-  run(args).await;
+  skanner::logging::init(args.quiet, args.verbose);
+
+  if args.dry_run {
+    if let Err(err) = dry_run(args).await {
+      eprintln!("Error: {}", err);
+      std::process::exit(EXIT_ERROR);
+    }
+    return;
+  }
+
+  let outcome = run(args).await;
+  if let Err(err) = &outcome {
+    eprintln!("Error: {}", err);
+  }
+
+  std::process::exit(exit_code(&outcome));
 }
@@ -0,0 +1,329 @@
+//! Report in the XML format of nmap, for the tools importing nmap scans
+//!
+//! Only the elements the scanner has something for are written: the scan
+//! info of each protocol, the hosts with their status, address, name and
+//! ports, and the run stats, following the nmap DTD.
+
+use std::fmt::Write;
+use std::time::{Duration, SystemTime};
+
+use crate::output;
+use crate::port::{PortResult, PortState, Protocol};
+use crate::scanner::{IpScanResult, Scanner};
+use crate::syn::ScanType;
+
+/// Version of the nmap XML output the report follows
+pub const XML_OUTPUT_VERSION: &str = "1.05";
+
+/// Name nmap gives to the state of a port
+pub fn state_name(state: PortState) -> &'static str {
+  match state {
+    PortState::Open => "open",
+    PortState::OpenFiltered => "open|filtered",
+    PortState::Filtered => "filtered",
+    PortState::Closed => "closed",
+    PortState::Unknown => "unknown",
+  }
+}
+
+/// Reason nmap gives for the state of a port found with a SYN, a connect or a UDP scan
+/// nmap reports an accepted connection as a `syn-ack` too, only a closed port tells the scans apart
+fn reason(state: PortState, protocol: Protocol, syn: bool) -> &'static str {
+  match (state, protocol) {
+    (PortState::Open, Protocol::Tcp) => "syn-ack",
+    (PortState::Open, Protocol::Udp) => "udp-response",
+    (PortState::Closed, Protocol::Tcp) if syn => "reset",
+    (PortState::Closed, Protocol::Tcp) => "conn-refused",
+    (PortState::Closed, Protocol::Udp) => "port-unreach",
+    (PortState::OpenFiltered | PortState::Filtered, _) => "no-response",
+    (PortState::Unknown, _) => "unknown",
+  }
+}
+
+/// Render the results of a scan finished at `time` as an nmap XML document
+///
+/// The hosts left out of the JSON report are left out too, and the filtered
+/// ports are only listed when showing them all.
+pub fn to_xml(scanner: &Scanner, time: SystemTime) -> String {
+  let elapsed = scanner.summary().map(|summary| summary.elapsed).unwrap_or_default();
+  let start = time.checked_sub(elapsed).unwrap_or(time);
+  let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<!DOCTYPE nmaprun>\n");
+
+  // Importers check the scanner is nmap, the version being the one of skanner
+  let _ = writeln!(
+    xml,
+    "<nmaprun scanner=\"nmap\" args=\"skanner {}\" start=\"{}\" startstr=\"{}\" version=\"{}\" xmloutputversion=\"{}\">",
+    escape(&scanner.target()),
+    seconds(start),
+    output::timestamp(start),
+    env!("CARGO_PKG_VERSION"),
+    XML_OUTPUT_VERSION
+  );
+
+  let probes = scanner.probes();
+  for protocol in [Protocol::Tcp, Protocol::Udp] {
+    let ports = probes.iter().filter(|(probe, _)| *probe == protocol).map(|(_, port)| port.to_string()).collect::<Vec<String>>();
+    if ports.is_empty() {
+      continue;
+    }
+
+    let kind = match (protocol, scanner.options.scan_type) {
+      (Protocol::Tcp, ScanType::Connect) => "connect",
+      (Protocol::Tcp, ScanType::Syn) => "syn",
+      (Protocol::Udp, _) => "udp",
+    };
+    let _ = writeln!(xml, "<scaninfo type=\"{}\" protocol=\"{}\" numservices=\"{}\" services=\"{}\"/>", kind, protocol, ports.len(), ports.join(","));
+  }
+  xml.push_str("<verbose level=\"0\"/>\n<debugging level=\"0\"/>\n");
+
+  let results = scanner.results().iter().filter(|result| scanner.include_down || output::is_reported(result)).collect::<Vec<&IpScanResult>>();
+  for result in &results {
+    // IPv6 hosts are connected to, SYN probes being IPv4 only
+    let syn = scanner.options.scan_type == ScanType::Syn && result.ip.is_ipv4();
+    write_host(&mut xml, result, scanner.show_all, syn);
+  }
+
+  let up = results.iter().filter(|result| result.up).count();
+  let (exit, message) = match scanner.summary() {
+    Some(summary) if summary.interrupted => ("error", " errormsg=\"interrupted\""),
+    Some(summary) if summary.deadline_reached => ("error", " errormsg=\"maximum duration reached\""),
+    _ => ("success", ""),
+  };
+  let _ = writeln!(
+    xml,
+    "<runstats><finished time=\"{}\" timestr=\"{}\" elapsed=\"{:.2}\" summary=\"skanner done: {} IP addresses ({} hosts up) scanned in {:.2} seconds\" exit=\"{}\"{}/><hosts up=\"{}\" down=\"{}\" total=\"{}\"/></runstats>",
+    seconds(time),
+    output::timestamp(time),
+    elapsed.as_secs_f64(),
+    results.len(),
+    up,
+    elapsed.as_secs_f64(),
+    exit,
+    message,
+    up,
+    results.len() - up,
+    results.len()
+  );
+  xml.push_str("</nmaprun>\n");
+
+  xml
+}
+
+fn write_host(xml: &mut String, result: &IpScanResult, show_all: bool, syn: bool) {
+  let (state, reason) = match result.up {
+    true => ("up", "syn-ack"),
+    false => ("down", "no-response"),
+  };
+  let family = match result.ip.is_ipv4() {
+    true => "ipv4",
+    false => "ipv6",
+  };
+
+  xml.push_str("<host>");
+  let _ = write!(xml, "<status state=\"{}\" reason=\"{}\" reason_ttl=\"0\"/>", state, reason);
+  let _ = writeln!(xml, "<address addr=\"{}\" addrtype=\"{}\"/>", result.ip, family);
+  match &result.hostname {
+    Some(hostname) => {
+      let _ = writeln!(xml, "<hostnames><hostname name=\"{}\" type=\"PTR\"/></hostnames>", escape(hostname));
+    }
+    None => xml.push_str("<hostnames/>\n"),
+  }
+
+  let mut ports = result.open_ports.iter().collect::<Vec<&PortResult>>();
+  if show_all {
+    ports.extend(&result.filtered_ports);
+  }
+  ports.sort_by_key(|port| (port.protocol, port.port));
+
+  xml.push_str("<ports>");
+  for port in ports {
+    write_port(xml, port, syn);
+  }
+  xml.push_str("</ports>\n");
+
+  if let Some(latency) = host_latency(result) {
+    let _ = writeln!(xml, "<times srtt=\"{}\" rttvar=\"0\" to=\"{}\"/>", latency.as_micros(), latency.as_micros() * 4);
+  }
+  xml.push_str("</host>\n");
+}
+
+fn write_port(xml: &mut String, port: &PortResult, syn: bool) {
+  let _ = write!(xml, "<port protocol=\"{}\" portid=\"{}\">", port.protocol, port.port);
+  let _ = write!(xml, "<state state=\"{}\" reason=\"{}\" reason_ttl=\"0\"/>", state_name(port.state), reason(port.state, port.protocol, syn));
+
+  if let Some(service) = &port.service {
+    let _ = write!(xml, "<service name=\"{}\"", escape(&service.name));
+    if let Some(product) = &service.product {
+      let _ = write!(xml, " product=\"{}\"", escape(product));
+    }
+    if let Some(version) = &service.version {
+      let _ = write!(xml, " version=\"{}\"", escape(version));
+    }
+    xml.push_str(" method=\"probed\" conf=\"10\"/>");
+  }
+  // As the banner script of nmap reports it
+  if let Some(banner) = &port.banner {
+    let _ = write!(xml, "<script id=\"banner\" output=\"{}\"/>", escape(banner));
+  }
+
+  xml.push_str("</port>\n");
+}
+
+/// Smallest latency of the open ports of a host, as the round trip time nmap reports
+fn host_latency(result: &IpScanResult) -> Option<Duration> {
+  result.open_ports.iter().filter_map(|port| port.latency).min()
+}
+
+fn seconds(time: SystemTime) -> u64 {
+  time.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+/// Escape a value for an attribute, dropping the control characters XML can't hold
+fn escape(value: &str) -> String {
+  let mut escaped = String::with_capacity(value.len());
+  for c in value.chars() {
+    match c {
+      '&' => escaped.push_str("&amp;"),
+      '<' => escaped.push_str("&lt;"),
+      '>' => escaped.push_str("&gt;"),
+      '"' => escaped.push_str("&quot;"),
+      '\'' => escaped.push_str("&apos;"),
+      '\t' | '\n' | '\r' => escaped.push_str(&format!("&#{};", c as u32)),
+      c if c.is_control() => {}
+      c => escaped.push(c),
+    }
+  }
+  escaped
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Check the tags of a document are balanced and its attributes quoted, returning the names of the elements
+  fn elements(xml: &str) -> Result<Vec<String>, String> {
+    let mut open: Vec<String> = Vec::new();
+    let mut names = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+      if rest[..start].contains('>') {
+        return Err(format!("stray > before {}", &rest[start..]));
+      }
+      let end = rest[start..].find('>').ok_or("unclosed tag")? + start;
+      let tag = &rest[start + 1..end];
+      rest = &rest[end + 1..];
+
+      if tag.starts_with('?') || tag.starts_with('!') {
+        continue;
+      }
+      if let Some(name) = tag.strip_prefix('/') {
+        if open.pop().as_deref() != Some(name) {
+          return Err(format!("unexpected </{}>", name));
+        }
+        continue;
+      }
+
+      let (tag, empty) = match tag.strip_suffix('/') {
+        Some(tag) => (tag, true),
+        None => (tag, false),
+      };
+      let (name, mut attributes) = tag.split_once(' ').unwrap_or((tag, ""));
+      while let Some((key, value)) = attributes.trim_start().split_once("=\"") {
+        if key.is_empty() || key.contains(' ') {
+          return Err(format!("invalid attribute {} of <{}>", key, name));
+        }
+        let (value, next) = value.split_once('"').ok_or(format!("unquoted attribute {} of <{}>", key, name))?;
+        if value.contains('<') || value.replace("&amp;", "").replace("&lt;", "").replace("&gt;", "").replace("&quot;", "").replace("&apos;", "").contains('&') {
+          return Err(format!("unescaped value {} of <{}>", value, name));
+        }
+        attributes = next;
+      }
+      if !attributes.trim().is_empty() {
+        return Err(format!("invalid attributes {} of <{}>", attributes, name));
+      }
+
+      names.push(name.to_string());
+      if !empty {
+        open.push(name.to_string());
+      }
+    }
+
+    match open.is_empty() && names.first().map(String::as_str) == Some("nmaprun") {
+      true => Ok(names),
+      false => Err(format!("unclosed elements {:?}", open)),
+    }
+  }
+
+  fn scanner() -> Scanner {
+    let ssh = PortResult {
+      banner: Some("SSH-2.0-OpenSSH_9.6 \"x\" & y".to_string()),
+      service: crate::fingerprint::identify("SSH-2.0-OpenSSH_9.6"),
+      ..PortResult::new(22, Protocol::Tcp, PortState::Open)
+    };
+    let http = PortResult { latency: Some(Duration::from_micros(1500)), ..PortResult::new(80, Protocol::Tcp, PortState::Open) };
+
+    let mut scanner = Scanner::new("192.168.1.0/24".to_string(), Some(vec![22, 80])).unwrap();
+    scanner.result = vec![
+      IpScanResult {
+        hostname: Some("nas <home>".to_string()),
+        open_ports: vec![http, ssh],
+        ..IpScanResult::new("192.168.1.10".parse().unwrap())
+      },
+      IpScanResult::new("192.168.1.20".parse().unwrap()),
+    ];
+    scanner
+  }
+
+  #[test]
+  fn test_nmap_xml() {
+    let time = SystemTime::UNIX_EPOCH + Duration::from_secs(1_791_972_903);
+    let xml = to_xml(&scanner(), time);
+
+    let names = elements(&xml).unwrap_or_else(|err| panic!("{}\n{}", err, xml));
+    assert_eq!(names.iter().filter(|name| *name == "host").count(), 1);
+    assert_eq!(names.iter().filter(|name| *name == "port").count(), 2);
+
+    assert!(xml.contains("<scaninfo type=\"connect\" protocol=\"tcp\" numservices=\"2\" services=\"22,80\"/>"), "{}", xml);
+    assert!(xml.contains("<address addr=\"192.168.1.10\" addrtype=\"ipv4\"/>"));
+    assert!(xml.contains("<hostname name=\"nas &lt;home&gt;\" type=\"PTR\"/>"));
+    assert!(xml.contains("<port protocol=\"tcp\" portid=\"22\"><state state=\"open\" reason=\"syn-ack\" reason_ttl=\"0\"/><service name=\"ssh\" product=\"OpenSSH\" version=\"9.6\" method=\"probed\" conf=\"10\"/>"));
+    assert!(xml.contains("output=\"SSH-2.0-OpenSSH_9.6 &quot;x&quot; &amp; y\""));
+    assert!(xml.contains("<times srtt=\"1500\" rttvar=\"0\" to=\"6000\"/>"));
+    assert!(xml.contains("<finished time=\"1791972903\" timestr=\"2026-10-14T10:15:03Z\" elapsed=\"0.00\""));
+    assert!(xml.contains("<hosts up=\"1\" down=\"0\" total=\"1\"/>"));
+  }
+
+  #[test]
+  fn test_nmap_xml_show_all() {
+    let mut scanner = scanner();
+    scanner.include_down = true;
+    scanner.show_all = true;
+    scanner.result[1].filtered_ports.push(PortResult::new(22, Protocol::Tcp, PortState::Filtered));
+
+    let xml = to_xml(&scanner, SystemTime::UNIX_EPOCH);
+    elements(&xml).unwrap_or_else(|err| panic!("{}\n{}", err, xml));
+    assert!(xml.contains("<state state=\"filtered\" reason=\"no-response\" reason_ttl=\"0\"/>"));
+    assert!(xml.contains("<hosts up=\"2\" down=\"0\" total=\"2\"/>"));
+  }
+
+  #[test]
+  fn test_nmap_xml_syn() {
+    let mut scanner = scanner();
+    scanner.options.scan_type = ScanType::Syn;
+
+    let xml = to_xml(&scanner, SystemTime::UNIX_EPOCH);
+    assert!(xml.contains("<scaninfo type=\"syn\" protocol=\"tcp\" numservices=\"2\" services=\"22,80\"/>"), "{}", xml);
+    assert!(xml.contains("reason=\"syn-ack\""));
+
+    assert_eq!(reason(PortState::Closed, Protocol::Tcp, true), "reset");
+    assert_eq!(reason(PortState::Closed, Protocol::Tcp, false), "conn-refused");
+  }
+
+  #[test]
+  fn test_state_names() {
+    assert_eq!(state_name(PortState::OpenFiltered), "open|filtered");
+    assert_eq!(state_name(PortState::Closed), "closed");
+    assert_eq!(escape("a\u{7}b\tc"), "ab&#9;c");
+  }
+}
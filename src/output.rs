@@ -0,0 +1,662 @@
+use std::borrow::Cow;
+use std::net::IpAddr;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use clap::ValueEnum;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::error::ScannerError;
+use crate::port::{PortResult, PortState, Protocol};
+use crate::scanner::{IpScanResult, Report, ScanMeta, Scanner};
+
+/// Format of a serialized report
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+  Yaml,
+  Json,
+  Text,
+  Csv,
+  Markdown,
+  Jsonl,
+  NmapXml,
+  Table,
+}
+
+impl OutputFormat {
+  /// Infer the format from the extension of a file
+  /// Unknown extensions fall back to YAML
+  pub fn from_path(path: &str) -> Self {
+    let extension = Path::new(path)
+      .extension()
+      .and_then(|extension| extension.to_str())
+      .map(|extension| extension.to_lowercase());
+
+    match extension.as_deref() {
+      Some("json") => OutputFormat::Json,
+      Some("txt") => OutputFormat::Text,
+      Some("csv") => OutputFormat::Csv,
+      Some("md") | Some("markdown") => OutputFormat::Markdown,
+      Some("jsonl") | Some("ndjson") => OutputFormat::Jsonl,
+      Some("xml") => OutputFormat::NmapXml,
+      _ => OutputFormat::Yaml,
+    }
+  }
+}
+
+/// What the findings of a report are listed by
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum GroupBy {
+  /// Each host with its open ports
+  #[default]
+  Host,
+
+  /// Each open port with the hosts exposing it
+  Port,
+}
+
+/// Open port with every host exposing it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PortGroup {
+  pub port: u16,
+  pub protocol: Protocol,
+
+  /// Hosts the port is open on, sorted by IP
+  pub hosts: Vec<IpAddr>,
+}
+
+/// Open ports of every host, sorted by protocol and port, each listed once with its hosts
+pub fn group_by_port(results: &[IpScanResult]) -> Vec<PortGroup> {
+  let mut groups: Vec<PortGroup> = Vec::new();
+
+  for result in results {
+    for port in &result.open_ports {
+      match groups.iter_mut().find(|group| group.port == port.port && group.protocol == port.protocol) {
+        Some(group) => group.hosts.push(result.ip),
+        None => groups.push(PortGroup { port: port.port, protocol: port.protocol, hosts: vec![result.ip] }),
+      }
+    }
+  }
+
+  groups.sort_by_key(|group| (group.protocol, group.port));
+  for group in &mut groups {
+    group.hosts.sort();
+  }
+  groups
+}
+
+/// Scanner serialized in JSON and YAML, with its open ports grouped by port as `byPort` when grouping by port
+/// Only the hosts with open ports are serialized unless the hosts without any are included
+#[derive(Serialize)]
+pub(crate) struct SerializedScanner<'a> {
+  #[serde(flatten)]
+  scanner: Cow<'a, Scanner>,
+
+  #[serde(rename = "byPort", skip_serializing_if = "Option::is_none")]
+  by_port: Option<Vec<PortGroup>>,
+}
+
+impl<'a> SerializedScanner<'a> {
+  pub(crate) fn new(scanner: &'a Scanner) -> Self {
+    let by_port = (scanner.group_by == GroupBy::Port).then(|| group_by_port(scanner.results()));
+
+    let scanner = match scanner.include_down || scanner.results().iter().all(is_reported) {
+      true => Cow::Borrowed(scanner),
+      false => {
+        let mut scanner = scanner.clone();
+        scanner.result.retain(is_reported);
+        Cow::Owned(scanner)
+      }
+    };
+
+    Self { scanner, by_port }
+  }
+}
+
+/// Whether a host is in the structured reports without including every host, having open ports
+/// A host that timed out is kept too, its unscanned ports being in an unknown state rather than closed
+pub fn is_reported(result: &IpScanResult) -> bool {
+  !result.open_ports.is_empty() || result.timed_out
+}
+
+/// Text report of a scan with one line per open port, listing the hosts exposing it
+pub struct PortReport<'a>(pub &'a Scanner);
+
+impl Report for PortReport<'_> {
+  fn report(&self) -> String {
+    let scanner = self.0;
+    let mut report = scanner.meta().map(ScanMeta::to_string).unwrap_or_default();
+
+    report.push_str(&format!("Scanner for {}\n", scanner.target()));
+    report.push_str(&format!("Ports: {:?}\n", scanner.ports()));
+    report.push_str("=========================\n");
+
+    let groups = group_by_port(scanner.results());
+    let labels = groups.iter().map(|group| format!("{}/{}:", group.port, group.protocol)).collect::<Vec<String>>();
+    let width = labels.iter().map(|label| label.len()).max().unwrap_or(0);
+
+    for (label, group) in labels.iter().zip(&groups) {
+      let hosts = group.hosts.iter().map(|ip| ip.to_string()).collect::<Vec<String>>();
+      report.push_str(&format!("{:<width$} {}\n", label, hosts.join(", "), width = width));
+    }
+
+    if let Some(summary) = scanner.summary() {
+      report.push_str("=========================\n");
+      report.push_str(&format!("{}\n", summary));
+    }
+    if !scanner.connection_errors().is_empty() {
+      report.push_str(&format!("Connection errors: {}\n", scanner.connection_errors()));
+    }
+
+    report
+  }
+}
+
+/// Columns of the CSV output
+pub const CSV_HEADER: &str = "ip,port,protocol,latency_ms,banner";
+
+/// Render the results as CSV, one row per open port
+pub fn to_csv(results: &[IpScanResult]) -> String {
+  let mut csv = format!("{}\n", CSV_HEADER);
+
+  for result in results {
+    for port in &result.open_ports {
+      let latency = port.latency.map(|latency| format!("{:.3}", latency.as_secs_f64() * 1000.0)).unwrap_or_default();
+      let banner = port.banner.as_deref().unwrap_or_default();
+
+      csv.push_str(&format!("{},{},{},{},{}\n", result.ip, port.port, port.protocol, latency, csv_field(banner)));
+    }
+  }
+
+  csv
+}
+
+/// Render the result of a host as a single line of JSON, without the line break
+pub fn to_json_line(result: &IpScanResult) -> Result<String, ScannerError> {
+  serde_json::to_string(result).map_err(|err| ScannerError::Serialization(err.to_string()))
+}
+
+/// Render the results as JSON Lines, one host per line
+pub fn to_jsonl(results: &[IpScanResult]) -> Result<String, ScannerError> {
+  results.iter().map(|result| to_json_line(result).map(|line| line + "\n")).collect()
+}
+
+/// Placeholder of an output path replaced by the time of the scan
+pub const TIMESTAMP_PLACEHOLDER: &str = "{timestamp}";
+
+/// Time as an RFC 3339 UTC timestamp, `2026-10-14T10:15:03Z`
+pub fn timestamp(time: SystemTime) -> String {
+  let (date, (hours, minutes, seconds)) = utc(time);
+
+  format!("{}T{:02}:{:02}:{:02}Z", date, hours, minutes, seconds)
+}
+
+/// Replace `{timestamp}` in an output path by the time, in a compact form
+/// without separators that is valid in file names on every system (`20261014T101503Z`)
+pub fn expand_path(path: &str, time: SystemTime) -> String {
+  if !path.contains(TIMESTAMP_PLACEHOLDER) {
+    return path.to_string();
+  }
+
+  let (date, (hours, minutes, seconds)) = utc(time);
+  let stamp = format!("{}T{:02}{:02}{:02}Z", date.replace('-', ""), hours, minutes, seconds);
+
+  path.replace(TIMESTAMP_PLACEHOLDER, &stamp)
+}
+
+/// UTC date (`YYYY-MM-DD`) and time of day of a time, times before 1970 being the epoch
+fn utc(time: SystemTime) -> (String, (u64, u64, u64)) {
+  let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+  let (days, seconds) = (seconds / 86_400, seconds % 86_400);
+
+  // Civil date of a number of days since the epoch, from Howard Hinnant's `civil_from_days`
+  let days = days as i64 + 719_468;
+  let era = days.div_euclid(146_097);
+  let day_of_era = days.rem_euclid(146_097);
+  let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+  let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+  let month_index = (5 * day_of_year + 2) / 153;
+  let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+  let month = if month_index < 10 { month_index + 3 } else { month_index - 9 };
+  let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+
+  let date = format!("{:04}-{:02}-{:02}", year, month, day);
+  (date, (seconds / 3600, seconds % 3600 / 60, seconds % 60))
+}
+
+/// Report of a scan as a Markdown table, one row per host
+/// Hosts are filtered like in the text report, every enriched field of a port going to the details
+pub struct MarkdownReport<'a>(pub &'a Scanner);
+
+/// Header and separator rows of the Markdown table
+pub const MARKDOWN_HEADER: &str = "| IP | Hostname | Open Ports | Details |\n| --- | --- | --- | --- |\n";
+
+impl Report for MarkdownReport<'_> {
+  fn report(&self) -> String {
+    let scanner = self.0;
+    let mut report = format!("# Scan of {}\n\n", markdown_escape(&scanner.target()));
+
+    let ports = scanner.ports().iter().map(|port| port.to_string()).collect::<Vec<String>>().join(", ");
+    report.push_str(&format!("Ports: {}\n\n", ports));
+    report.push_str(MARKDOWN_HEADER);
+
+    for result in scanner.results().iter().filter(|result| scanner.show_all || !result.open_ports.is_empty()) {
+      let mut ports = result.open_ports.iter().collect::<Vec<&PortResult>>();
+      if scanner.show_all {
+        ports.extend(&result.filtered_ports);
+      }
+
+      let open_ports = match (result.up, ports.is_empty()) {
+        (false, _) => "host down".to_string(),
+        (true, true) => "none".to_string(),
+        (true, false) => ports.iter().map(|port| port_label(port)).collect::<Vec<String>>().join(", "),
+      };
+      let details = ports.iter().filter_map(|port| port_details(port)).collect::<Vec<String>>().join("<br>");
+
+      report.push_str(&format!(
+        "| {} | {} | {} | {} |\n",
+        result.ip,
+        markdown_escape(result.hostname.as_deref().unwrap_or_default()),
+        open_ports,
+        markdown_escape(&details)
+      ));
+    }
+
+    if let Some(summary) = scanner.summary() {
+      report.push_str(&format!("\n{}\n", summary));
+    }
+    if !scanner.connection_errors().is_empty() {
+      report.push_str(&format!("\nConnection errors: {}\n", scanner.connection_errors()));
+    }
+
+    report
+  }
+}
+
+/// Port and protocol of a port, with its state when it's not open and whether it's unstable
+fn port_label(port: &PortResult) -> String {
+  let label = match port.state {
+    PortState::Open => format!("{}/{}", port.port, port.protocol),
+    state => format!("{}/{} ({})", port.port, port.protocol, state),
+  };
+
+  match port.unstable {
+    true => label + " (unstable)",
+    false => label,
+  }
+}
+
+/// Enriched fields of a port (service, banner, TLS, HTTP), `None` when it has none
+fn port_details(port: &PortResult) -> Option<String> {
+  let mut details = Vec::new();
+  if let Some(service) = &port.service {
+    details.push(service.to_string());
+  }
+  if let Some(banner) = &port.banner {
+    details.push(format!("`{}`", banner.replace('`', "'")));
+  }
+  if let Some(tls) = &port.tls {
+    details.push(tls.to_string());
+  }
+  if let Some(http) = &port.http {
+    details.push(http.to_string());
+  }
+
+  if details.is_empty() {
+    None
+  } else {
+    Some(format!("{}/{}: {}", port.port, port.protocol, details.join(", ")))
+  }
+}
+
+/// Report of a scan as a table with aligned columns, one row per host
+/// Hosts are filtered like in the text report, the hostname column only being
+/// there when a host has a name
+pub struct TableReport<'a>(pub &'a Scanner);
+
+/// Space between two columns of the table
+const COLUMN_GAP: &str = "  ";
+
+impl TableReport<'_> {
+  /// Table with the lines longer than `width` cut, none being cut when `None`
+  pub fn render(&self, width: Option<usize>) -> String {
+    let scanner = self.0;
+    let hostnames = scanner.results().iter().any(|result| result.hostname.is_some());
+
+    let mut rows = vec![vec!["IP".to_string(), "HOSTNAME".to_string(), "OPEN PORTS".to_string(), "SERVICES".to_string()]];
+    for result in scanner.results().iter().filter(|result| scanner.show_all || !result.open_ports.is_empty()) {
+      let mut ports = result.open_ports.iter().collect::<Vec<&PortResult>>();
+      if scanner.show_all {
+        ports.extend(&result.filtered_ports);
+      }
+
+      let open_ports = match (result.up, ports.is_empty()) {
+        (false, _) => "host down".to_string(),
+        (true, true) => "none".to_string(),
+        (true, false) => ports.iter().map(|port| port_label(port)).collect::<Vec<String>>().join(", "),
+      };
+      let services = ports.iter().filter_map(|port| port.service.as_ref().map(|service| format!("{}: {}", port.port, service))).collect::<Vec<String>>();
+
+      rows.push(vec![result.ip.to_string(), result.hostname.clone().unwrap_or_default(), open_ports, services.join(", ")]);
+    }
+    if !hostnames {
+      rows.iter_mut().for_each(|row| drop(row.remove(1)));
+    }
+
+    let widths = (0..rows[0].len()).map(|column| rows.iter().map(|row| row[column].chars().count()).max().unwrap_or_default()).collect::<Vec<usize>>();
+    let separator = widths.iter().map(|width| "-".repeat(*width)).collect::<Vec<String>>();
+    rows.insert(1, separator);
+
+    let mut report = String::new();
+    for row in rows {
+      let cells = row.iter().zip(&widths).map(|(cell, width)| format!("{:<width$}", cell, width = width)).collect::<Vec<String>>();
+      let line = cells.join(COLUMN_GAP);
+      report.push_str(&cut(line.trim_end(), width));
+      report.push('\n');
+    }
+
+    if let Some(summary) = scanner.summary() {
+      report.push_str(&format!("\n{}\n", summary));
+    }
+    if !scanner.connection_errors().is_empty() {
+      report.push_str(&format!("Connection errors: {}\n", scanner.connection_errors()));
+    }
+
+    report
+  }
+}
+
+impl Report for TableReport<'_> {
+  fn report(&self) -> String {
+    self.render(None)
+  }
+}
+
+/// Report with a line per host with open ports, `ip: port/service, …`, the
+/// protocol standing in for the service when it is not known
+pub struct CompactReport<'a>(pub &'a Scanner);
+
+impl Report for CompactReport<'_> {
+  fn report(&self) -> String {
+    let mut report = String::new();
+
+    for result in self.0.results() {
+      let ports = result.open_ports.iter().filter(|port| port.state == PortState::Open).map(|port| match &port.service {
+        Some(service) => format!("{}/{}", port.port, service.name),
+        None => format!("{}/{}", port.port, port.protocol),
+      }).collect::<Vec<String>>();
+      if ports.is_empty() {
+        continue;
+      }
+
+      match &result.hostname {
+        Some(hostname) => report.push_str(&format!("{} ({}): {}\n", result.ip, hostname, ports.join(", "))),
+        None => report.push_str(&format!("{}: {}\n", result.ip, ports.join(", "))),
+      }
+    }
+
+    report
+  }
+}
+
+/// Line cut to `width` characters, ending with an ellipsis when cut
+fn cut(line: &str, width: Option<usize>) -> Cow<'_, str> {
+  match width {
+    Some(width) if line.chars().count() > width => Cow::Owned(line.chars().take(width.saturating_sub(1)).chain(['…']).collect()),
+    _ => Cow::Borrowed(line),
+  }
+}
+
+/// Width of the terminal stdout is written to, from `COLUMNS` or the terminal itself
+/// `None` when stdout is not a terminal, the table then not being cut
+pub fn terminal_width() -> Option<usize> {
+  std::env::var("COLUMNS").ok().and_then(|columns| columns.parse().ok()).filter(|columns| *columns > 0).or_else(terminal_columns)
+}
+
+#[cfg(unix)]
+fn terminal_columns() -> Option<usize> {
+  let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+
+  // Fails when stdout is redirected to a file or a pipe
+  let status = unsafe { libc::ioctl(libc::STDOUT_FILENO, libc::TIOCGWINSZ, &mut size) };
+  (status == 0 && size.ws_col > 0).then_some(usize::from(size.ws_col))
+}
+
+#[cfg(not(unix))]
+fn terminal_columns() -> Option<usize> {
+  None
+}
+
+/// Escape the characters breaking a table cell, pipes being column separators
+fn markdown_escape(text: &str) -> String {
+  text.replace('\\', "\\\\").replace('|', "\\|").replace(['\n', '\r'], " ")
+}
+
+/// Quote a CSV field when it contains a separator, a quote or a new line
+fn csv_field(field: &str) -> String {
+  if field.contains([',', '"', '\n', '\r']) {
+    format!("\"{}\"", field.replace('"', "\"\""))
+  } else {
+    field.to_string()
+  }
+}
+
+/// Serialize a duration as a number of milliseconds
+pub(crate) fn serialize_ms<S: Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+  serializer.serialize_u64(duration.as_millis() as u64)
+}
+
+/// Deserialize a number of milliseconds
+pub(crate) fn deserialize_ms<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Duration, D::Error> {
+  u64::deserialize(deserializer).map(Duration::from_millis)
+}
+
+/// Serialize an optional duration as a number of milliseconds
+pub(crate) fn serialize_optional_ms<S: Serializer>(duration: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+  match duration {
+    Some(duration) => serialize_ms(duration, serializer),
+    None => serializer.serialize_none(),
+  }
+}
+
+/// Serialize a latency as a fractional number of milliseconds
+pub(crate) fn serialize_latency<S: Serializer>(latency: &Option<Duration>, serializer: S) -> Result<S::Ok, S::Error> {
+  match latency {
+    Some(latency) => serializer.serialize_f64(latency.as_secs_f64() * 1000.0),
+    None => serializer.serialize_none(),
+  }
+}
+
+/// Deserialize a latency given as a fractional number of milliseconds
+pub(crate) fn deserialize_latency<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Option<Duration>, D::Error> {
+  let latency = Option::<f64>::deserialize(deserializer)?;
+
+  Ok(latency.filter(|ms| ms.is_finite() && *ms >= 0.0).map(|ms| Duration::from_secs_f64(ms / 1000.0)))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_to_csv() {
+    let results = vec![
+      IpScanResult {
+        open_ports: vec![
+          PortResult { latency: Some(Duration::from_micros(1500)), banner: Some("Hello, \"world\"".to_string()), ..PortResult::new(22, Protocol::Tcp, PortState::Open) },
+          PortResult::new(53, Protocol::Udp, PortState::OpenFiltered),
+        ],
+        ..IpScanResult::new("192.168.1.1".parse().unwrap())
+      },
+      IpScanResult::new("192.168.1.2".parse().unwrap()),
+    ];
+
+    let csv = to_csv(&results);
+    let mut reader = csv::Reader::from_reader(csv.as_bytes());
+
+    assert_eq!(reader.headers().unwrap().len(), 5);
+
+    let rows = reader.records().map(|row| row.unwrap()).collect::<Vec<_>>();
+    assert_eq!(rows.len(), 2);
+    assert!(rows.iter().all(|row| row.len() == 5));
+    assert_eq!(&rows[0][0], "192.168.1.1");
+    assert_eq!(&rows[0][3], "1.500");
+    assert_eq!(&rows[0][4], "Hello, \"world\"");
+    assert_eq!(&rows[1][2], "udp");
+  }
+
+  #[test]
+  fn test_to_csv_empty() {
+    assert_eq!(to_csv(&[]), "ip,port,protocol,latency_ms,banner\n");
+  }
+
+  #[test]
+  fn test_timestamp() {
+    assert_eq!(timestamp(UNIX_EPOCH), "1970-01-01T00:00:00Z");
+    assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(951_782_400)), "2000-02-29T00:00:00Z");
+    assert_eq!(timestamp(UNIX_EPOCH + Duration::from_secs(1_791_972_903)), "2026-10-14T10:15:03Z");
+  }
+
+  #[test]
+  fn test_expand_path() {
+    let time = UNIX_EPOCH + Duration::from_secs(1_791_972_903);
+
+    assert_eq!(expand_path("scans/{timestamp}.json", time), "scans/20261014T101503Z.json");
+    assert_eq!(expand_path("report.json", time), "report.json");
+  }
+
+  #[test]
+  fn test_table_report() {
+    let mut scanner = Scanner::new("192.168.1.0/24".to_string(), Some(vec![22, 80])).unwrap();
+    let host = |ip: &str, ports: Vec<PortResult>| IpScanResult { open_ports: ports, ..IpScanResult::new(ip.parse().unwrap()) };
+    let ssh = PortResult { service: crate::fingerprint::identify("SSH-2.0-OpenSSH_9.6"), ..PortResult::new(22, Protocol::Tcp, PortState::Open) };
+    scanner.result = vec![
+      host("192.168.1.5", vec![PortResult::new(80, Protocol::Tcp, PortState::Open)]),
+      host("192.168.1.200", vec![ssh, PortResult::new(80, Protocol::Tcp, PortState::Open)]),
+      host("192.168.1.201", vec![]),
+    ];
+
+    let report = TableReport(&scanner).render(None);
+    let lines = report.lines().collect::<Vec<&str>>();
+    let service = scanner.result[1].open_ports[0].service.as_ref().unwrap();
+
+    assert_eq!(lines[0], "IP             OPEN PORTS      SERVICES");
+    assert_eq!(lines[1], format!("-------------  --------------  {}", "-".repeat(4 + service.to_string().len())));
+    assert_eq!(lines[2], "192.168.1.5    80/tcp");
+    assert_eq!(lines[3], format!("192.168.1.200  22/tcp, 80/tcp  22: {}", service));
+    assert_eq!(lines.len(), 4);
+
+    // The columns start at the same offset on every row
+    let offset = |line: &str, column: &str| line.find(column).unwrap();
+    assert_eq!(offset(lines[0], "OPEN PORTS"), offset(lines[2], "80/tcp"));
+    assert_eq!(offset(lines[0], "SERVICES"), offset(lines[3], "22: "));
+  }
+
+  #[test]
+  fn test_compact_report() {
+    let mut scanner = Scanner::new("192.168.1.0/24".to_string(), Some(vec![22, 53, 80])).unwrap();
+    let ssh = PortResult { service: crate::fingerprint::identify("SSH-2.0-OpenSSH_9.6"), ..PortResult::new(22, Protocol::Tcp, PortState::Open) };
+    scanner.result = vec![
+      IpScanResult { hostname: Some("router.lan".to_string()), open_ports: vec![ssh, PortResult::new(53, Protocol::Udp, PortState::OpenFiltered), PortResult::new(80, Protocol::Tcp, PortState::Open)], ..IpScanResult::new("192.168.1.1".parse().unwrap()) },
+      IpScanResult { open_ports: vec![PortResult::new(53, Protocol::Udp, PortState::OpenFiltered)], ..IpScanResult::new("192.168.1.2".parse().unwrap()) },
+      IpScanResult { open_ports: vec![PortResult::new(80, Protocol::Tcp, PortState::Open)], ..IpScanResult::new("192.168.1.3".parse().unwrap()) },
+    ];
+
+    assert_eq!(CompactReport(&scanner).report(), "192.168.1.1 (router.lan): 22/ssh, 80/tcp\n192.168.1.3: 80/tcp\n");
+  }
+
+  #[test]
+  fn test_table_report_hostnames_and_width() {
+    let mut scanner = Scanner::new("10.0.0.0/24".to_string(), Some(vec![22])).unwrap();
+    scanner.show_all = true;
+    scanner.result = vec![
+      IpScanResult { hostname: Some("gateway.lan".to_string()), open_ports: vec![PortResult::new(22, Protocol::Tcp, PortState::Open)], ..IpScanResult::new("10.0.0.1".parse().unwrap()) },
+      IpScanResult { up: false, ..IpScanResult::new("10.0.0.2".parse().unwrap()) },
+    ];
+
+    let report = TableReport(&scanner).render(Some(25));
+    let lines = report.lines().collect::<Vec<&str>>();
+    assert_eq!(lines[0], "IP        HOSTNAME     O…");
+    assert_eq!(lines[2], "10.0.0.1  gateway.lan  2…");
+    assert_eq!(lines[3], "10.0.0.2               h…");
+    assert!(lines.iter().all(|line| line.chars().count() <= 25));
+  }
+
+  #[test]
+  fn test_markdown_report() {
+    let mut scanner = Scanner::new("192.168.1.0/30".to_string(), Some(vec![22, 80])).unwrap();
+    scanner.result = vec![
+      IpScanResult {
+        hostname: Some("router.lan".to_string()),
+        open_ports: vec![
+          PortResult { banner: Some("SSH-2.0-OpenSSH_8.9 | x".to_string()), ..PortResult::new(22, Protocol::Tcp, PortState::Open) },
+          PortResult::new(80, Protocol::Tcp, PortState::Open),
+        ],
+        ..IpScanResult::new("192.168.1.1".parse().unwrap())
+      },
+      IpScanResult::new("192.168.1.2".parse().unwrap()),
+    ];
+
+    let report = MarkdownReport(&scanner).report();
+    let lines = report.lines().collect::<Vec<&str>>();
+
+    assert_eq!(lines[0], "# Scan of 192.168.1.0/30");
+    assert_eq!(lines[2], "Ports: 22, 80");
+    assert_eq!(lines[4], "| IP | Hostname | Open Ports | Details |");
+    assert_eq!(lines[5], "| --- | --- | --- | --- |");
+    assert_eq!(lines[6], "| 192.168.1.1 | router.lan | 22/tcp, 80/tcp | 22/tcp: `SSH-2.0-OpenSSH_8.9 \\| x` |");
+    assert_eq!(lines.len(), 7);
+
+    // Every row has as many cells as the header, escaped pipes not counting
+    let cells = |line: &str| line.replace("\\|", "").matches('|').count();
+    assert!(lines[4..].iter().all(|line| cells(line) == 5));
+  }
+
+  #[test]
+  fn test_group_by_port() {
+    let mut scanner = Scanner::new("192.168.1.0/30".to_string(), Some(vec![22, 445])).unwrap();
+    let host = |ip: &str, ports: &[u16]| IpScanResult {
+      open_ports: ports.iter().map(|port| PortResult::new(*port, Protocol::Tcp, PortState::Open)).collect(),
+      ..IpScanResult::new(ip.parse().unwrap())
+    };
+    scanner.result = vec![host("192.168.1.1", &[22, 445]), host("192.168.1.2", &[445])];
+    scanner.group_by = GroupBy::Port;
+
+    let groups = group_by_port(scanner.results());
+    assert_eq!(groups.len(), 2);
+    assert_eq!((groups[0].port, groups[0].hosts.len()), (22, 1));
+    assert_eq!((groups[1].port, groups[1].hosts.len()), (445, 2));
+
+    assert_eq!(
+      scanner.report(),
+      "Scanner for 192.168.1.0/30\nPorts: [22, 445]\n=========================\n22/tcp:  192.168.1.1\n445/tcp: 192.168.1.1, 192.168.1.2\n"
+    );
+
+    let json: serde_json::Value = serde_json::from_str(&scanner.serialize(OutputFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["byPort"][1], serde_json::json!({ "port": 445, "protocol": "tcp", "hosts": ["192.168.1.1", "192.168.1.2"] }));
+    assert_eq!(json["result"].as_array().unwrap().len(), 2);
+
+    scanner.group_by = GroupBy::Host;
+    assert!(!scanner.serialize(OutputFormat::Json).unwrap().contains("byPort"));
+  }
+
+  #[test]
+  fn test_markdown_escape() {
+    assert_eq!(markdown_escape("a|b\\c\nd"), "a\\|b\\\\c d");
+  }
+
+  #[test]
+  fn test_format_from_path() {
+    assert_eq!(OutputFormat::from_path("report.json"), OutputFormat::Json);
+    assert_eq!(OutputFormat::from_path("report.JSON"), OutputFormat::Json);
+    assert_eq!(OutputFormat::from_path("report.txt"), OutputFormat::Text);
+    assert_eq!(OutputFormat::from_path("report.csv"), OutputFormat::Csv);
+    assert_eq!(OutputFormat::from_path("report.md"), OutputFormat::Markdown);
+    assert_eq!(OutputFormat::from_path("report.jsonl"), OutputFormat::Jsonl);
+    assert_eq!(OutputFormat::from_path("report.ndjson"), OutputFormat::Jsonl);
+    assert_eq!(OutputFormat::from_path("report.yaml"), OutputFormat::Yaml);
+    assert_eq!(OutputFormat::from_path("report.yml"), OutputFormat::Yaml);
+    assert_eq!(OutputFormat::from_path("report.xml"), OutputFormat::NmapXml);
+    assert_eq!(OutputFormat::from_path("report"), OutputFormat::Yaml);
+  }
+}
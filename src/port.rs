@@ -0,0 +1,659 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use std::time::Duration;
+
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+
+use crate::error::ScannerError;
+use crate::fingerprint::Service;
+use crate::http::HttpInfo;
+use crate::tls::TlsInfo;
+
+/// Transport protocol used to probe a port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Protocol {
+  Tcp,
+  Udp,
+}
+
+impl Display for Protocol {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Protocol::Tcp => write!(f, "tcp"),
+      Protocol::Udp => write!(f, "udp"),
+    }
+  }
+}
+
+/// Protocols selected on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ProtocolMode {
+  Tcp,
+  Udp,
+  Both,
+}
+
+impl ProtocolMode {
+  /// List of protocols to scan for this mode
+  pub fn protocols(&self) -> Vec<Protocol> {
+    match self {
+      ProtocolMode::Tcp => vec![Protocol::Tcp],
+      ProtocolMode::Udp => vec![Protocol::Udp],
+      ProtocolMode::Both => vec![Protocol::Tcp, Protocol::Udp],
+    }
+  }
+}
+
+/// Order the ports of each host are probed in, the report listing them sorted anyway
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum PortOrder {
+  /// The most frequently open ports first, as ranked in `TOP_PORTS`, then the others in ascending order
+  Common,
+
+  /// Ascending port numbers
+  Numeric,
+
+  /// A random order, reproducible with the seed of the scan
+  Random,
+}
+
+impl PortOrder {
+  /// Sort the ports in this order, a random order being left to the caller and its seed
+  pub fn sort(&self, ports: &mut [u16]) {
+    match self {
+      PortOrder::Common => ports.sort_by_key(|port| (common_rank(*port), *port)),
+      PortOrder::Numeric => ports.sort(),
+      PortOrder::Random => {}
+    }
+  }
+}
+
+/// Rank of a port in `TOP_PORTS`, the ports not listed coming after all the listed ones
+pub fn common_rank(port: u16) -> usize {
+  TOP_PORTS.iter().position(|top| *top == port).unwrap_or(TOP_PORTS.len())
+}
+
+/// State of a scanned port
+///
+/// A TCP port refusing the connection is closed while a port not answering
+/// at all is filtered, its packets being dropped by a firewall.
+///
+/// UDP has no handshake: a port answering the probe is open and a port
+/// answering with an ICMP port unreachable is closed, but silence can mean
+/// either an open port ignoring the probe or a firewall dropping it. Those
+/// ports are reported as `OpenFiltered` rather than guessed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum PortState {
+  Open,
+  OpenFiltered,
+  Filtered,
+  Closed,
+
+  /// Not probed, the host having timed out first
+  Unknown,
+}
+
+impl Display for PortState {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      PortState::Open => write!(f, "open"),
+      PortState::OpenFiltered => write!(f, "open|filtered"),
+      PortState::Filtered => write!(f, "filtered"),
+      PortState::Closed => write!(f, "closed"),
+      PortState::Unknown => write!(f, "unknown"),
+    }
+  }
+}
+
+/// A port found on a host, with the protocol it was probed with
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct PortResult {
+  pub port: u16,
+  pub protocol: Protocol,
+  pub state: PortState,
+
+  /// Time the TCP connection took to be established
+  #[serde(rename = "latencyMs", default, skip_serializing_if = "Option::is_none", serialize_with = "crate::output::serialize_latency", deserialize_with = "crate::output::deserialize_latency")]
+  pub latency: Option<Duration>,
+
+  /// Banner sent by the service, when banner grabbing is enabled
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub banner: Option<String>,
+
+  /// Service guessed from the banner
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub service: Option<Service>,
+
+  /// TLS service found on the port, when TLS detection is enabled
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub tls: Option<TlsInfo>,
+
+  /// Web server found on the port, when HTTP probing is enabled
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub http: Option<HttpInfo>,
+
+  /// Virtual host the TLS handshake and the HTTP request were sent for, none being sent without it
+  #[serde(rename = "serverName", default, skip_serializing_if = "Option::is_none")]
+  pub server_name: Option<String>,
+
+  /// Whether the port was no longer open when probed again to confirm it,
+  /// as a load balancer or a flapping service does
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub unstable: bool,
+
+  /// Problem met probing the port that didn't stop its probe, moved to the errors of its host once recorded
+  #[serde(skip)]
+  pub error: Option<String>,
+}
+
+impl PortResult {
+  pub fn new(port: u16, protocol: Protocol, state: PortState) -> Self {
+    Self {
+      port,
+      protocol,
+      state,
+      latency: None,
+      banner: None,
+      service: None,
+      tls: None,
+      http: None,
+      server_name: None,
+      unstable: false,
+      error: None,
+    }
+  }
+}
+
+/// The alternate form (`{:#}`) includes the latency
+impl Display for PortResult {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}/{}", self.port, self.protocol)?;
+    if self.state != PortState::Open {
+      write!(f, " ({})", self.state)?;
+    }
+    if self.unstable {
+      write!(f, " (unstable)")?;
+    }
+    if let (true, Some(latency)) = (f.alternate(), self.latency) {
+      write!(f, " {:.2}ms", latency.as_secs_f64() * 1000.0)?;
+    }
+    if let Some(service) = &self.service {
+      write!(f, " {}", service)?;
+    }
+    if let Some(banner) = &self.banner {
+      write!(f, " [{}]", banner)?;
+    }
+    if let Some(tls) = &self.tls {
+      write!(f, " [{}]", tls)?;
+    }
+    if let Some(http) = &self.http {
+      write!(f, " [{}]", http)?;
+    }
+    Ok(())
+  }
+}
+
+/// Most common TCP ports, from the most to the least frequently open
+///
+/// This is the ranking used by nmap's `--top-ports 100`, computed by the nmap
+/// project from the open frequency of each port in the `nmap-services` file
+/// (<https://nmap.org/book/nmap-services.html>).
+pub const TOP_PORTS: [u16; 100] = [
+  80, 23, 443, 21, 22, 25, 3389, 110, 445, 139,
+  143, 53, 135, 3306, 8080, 1723, 111, 995, 993, 5900,
+  1025, 587, 8888, 199, 1720, 465, 548, 113, 81, 6001,
+  10000, 514, 5060, 179, 1026, 2000, 8443, 8000, 32768, 554,
+  26, 1433, 49152, 2001, 515, 8008, 49154, 1027, 5666, 646,
+  5000, 5631, 631, 49153, 8081, 2049, 88, 79, 5800, 106,
+  2121, 1110, 49155, 6000, 513, 990, 5357, 427, 49156, 543,
+  544, 5101, 144, 7, 389, 8009, 3128, 444, 9999, 5009,
+  7070, 5190, 3000, 5432, 1900, 3986, 13, 1029, 9, 5051,
+  6646, 49157, 1028, 873, 1755, 2717, 4899, 9100, 119, 37,
+];
+
+/// The `count` most common TCP ports, at most the whole `TOP_PORTS` table
+pub fn top_ports(count: usize) -> Vec<u16> {
+  TOP_PORTS.iter().take(count).copied().collect()
+}
+
+/// Ports scanned when none are given
+pub const DEFAULT_PORTS: &[u16] = &[22, 80, 443, 8080];
+
+/// Ports never scanned unless the denylist is replaced, the ones of industrial control systems
+/// that may misbehave when probed: S7comm (102), Modbus (502), IEC 60870-5-104 (2404),
+/// DNP3 (20000), EtherNet/IP (44818) and BACnet (47808)
+pub const DEFAULT_DENIED_PORTS: &[u16] = &[102, 502, 2404, 20000, 44818, 47808];
+
+/// Ports of a scan as given on the command line, `resolve` turning them into the list to scan
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PortSpec {
+  /// Ports and ranges listed explicitly, as with `--ports`
+  pub ranges: Option<Vec<PortRange>>,
+
+  /// Number of the most common ports scanned instead, as with `--top-ports`
+  pub top: Option<usize>,
+
+  /// Ports scanned in addition to the others, as with `--add-ports`
+  pub added: Vec<PortRange>,
+}
+
+impl PortSpec {
+  /// Explicit list of ports, the default ones being scanned when `None`
+  pub fn list(ports: Option<Vec<u16>>) -> Self {
+    let ranges = ports.map(|ports| ports.into_iter().map(|port| PortRange { start: port, end: port }).collect());
+
+    Self { ranges, ..Self::default() }
+  }
+
+  /// Ports to scan, each once in ascending order, `--order` or randomizing the ports reordering them for the scan
+  ///
+  /// The most common ports and an explicit list exclude each other, the
+  /// default ports being scanned when neither is given. The added ports are
+  /// scanned with them. Port 0, an inverted range or an empty list is an error.
+  pub fn resolve(&self) -> Result<Vec<u16>, ScannerError> {
+    let error = |message: String| Err(ScannerError::InvalidPorts(message));
+
+    let mut ranges = match (&self.ranges, self.top) {
+      (Some(_), Some(_)) => return error("an explicit list and the most common ports can't be combined".to_string()),
+      (Some(ranges), None) if ranges.is_empty() => return error("the list of ports is empty".to_string()),
+      (Some(ranges), None) => ranges.clone(),
+      (None, Some(count)) if count == 0 || count > TOP_PORTS.len() => return error(format!("{} common ports, expected 1 to {}", count, TOP_PORTS.len())),
+      (None, Some(count)) => top_ports(count).into_iter().map(|port| PortRange { start: port, end: port }).collect(),
+      (None, None) => DEFAULT_PORTS.iter().map(|port| PortRange { start: *port, end: *port }).collect(),
+    };
+    ranges.extend(self.added.iter().copied());
+
+    if let Some(range) = ranges.iter().find(|range| range.start == 0 || range.start > range.end) {
+      return error(format!("{}-{} is not a valid range, ports go from 1 to 65535", range.start, range.end));
+    }
+    let mut ports = expand_ranges(&ranges);
+    ports.sort_unstable();
+    Ok(ports)
+  }
+}
+
+/// Inclusive range of ports given on the command line (`8000-8100`, or a single `22`)
+/// Port 0 is reserved and never used by a service, it can't be part of a range
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PortRange {
+  pub start: u16,
+  pub end: u16,
+}
+
+impl FromStr for PortRange {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let s = s.trim();
+    if s.is_empty() {
+      return Err("empty port, expected a port or a range of ports such as `22` or `8000-8100`".to_string());
+    }
+
+    let (start, end) = match s.split_once('-') {
+      Some((start, end)) => (parse_port(start)?, parse_port(end)?),
+      None => {
+        let port = parse_port(s)?;
+        (port, port)
+      }
+    };
+
+    if start > end {
+      return Err(format!("invalid port range `{}`, {} is greater than {}", s, start, end));
+    }
+
+    Ok(Self { start, end })
+  }
+}
+
+/// Comma separated list of ports and ranges, each probed with the protocol given before it, if any
+///
+/// A `tcp:` or `udp:` prefix applies to its port and the ones after it in the
+/// list (`tcp:22,80,udp:53`), the ports before any prefix being probed with the
+/// protocols of the scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortList(pub Vec<(Option<Protocol>, PortRange)>);
+
+impl FromStr for PortList {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut protocol = None;
+    let mut ranges = Vec::new();
+
+    for item in s.split(',') {
+      let range = match item.split_once(':') {
+        Some((prefix, range)) => {
+          protocol = Some(match prefix.trim().to_lowercase().as_str() {
+            "tcp" => Protocol::Tcp,
+            "udp" => Protocol::Udp,
+            _ => return Err(format!("unknown protocol `{}` in `{}`, expected tcp or udp", prefix.trim(), item.trim())),
+          });
+          range
+        }
+        None => item,
+      };
+      ranges.push((protocol, range.parse::<PortRange>()?));
+    }
+
+    Ok(Self(ranges))
+  }
+}
+
+impl PortList {
+  /// Ranges of the list, without their protocol
+  pub fn ranges(&self) -> impl Iterator<Item = PortRange> + '_ {
+    self.0.iter().map(|(_, range)| *range)
+  }
+}
+
+/// Lists of ports read from a file given with `--ports-file`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PortsFile(pub Vec<PortList>);
+
+/// Parse the lists of a ports file, returning the number of the first invalid line and its error
+///
+/// Each line holds ports and ranges separated by commas, a `tcp:` or `udp:`
+/// prefix applying to the end of its line. Everything after a `#` is a comment.
+pub fn parse_ports_file(content: &str) -> Result<PortsFile, (usize, String)> {
+  let mut lists = Vec::new();
+
+  for (index, line) in content.lines().enumerate() {
+    let line = line.split('#').next().unwrap_or_default();
+    // A trailing comma is left by lists split on several lines
+    let items = line.split(',').map(str::trim).filter(|item| !item.is_empty()).collect::<Vec<&str>>();
+    if items.is_empty() {
+      continue;
+    }
+
+    lists.push(items.join(",").parse::<PortList>().map_err(|err| (index + 1, err))?);
+  }
+
+  Ok(PortsFile(lists))
+}
+
+/// Clap value parser for a ports file, read when the command line is parsed
+pub fn read_ports_file(path: &str) -> Result<PortsFile, String> {
+  let content = std::fs::read_to_string(path).map_err(|err| err.to_string())?;
+
+  parse_ports_file(&content).map_err(|(line, err)| format!("line {}: {}", line, err))
+}
+
+/// Protocols of each port of the lists, the ports without a prefix being probed with `protocols`
+/// Empty when no port has its protocol given, every port being probed with `protocols`
+pub fn port_protocols(lists: &[PortList], protocols: &[Protocol]) -> BTreeMap<u16, Vec<Protocol>> {
+  let mut port_protocols: BTreeMap<u16, Vec<Protocol>> = BTreeMap::new();
+  if lists.iter().all(|list| list.0.iter().all(|(protocol, _)| protocol.is_none())) {
+    return port_protocols;
+  }
+
+  for (protocol, range) in lists.iter().flat_map(|list| &list.0) {
+    let given = protocol.map(|protocol| vec![protocol]).unwrap_or_else(|| protocols.to_vec());
+    for port in range.start..=range.end {
+      let entry = port_protocols.entry(port).or_default();
+      entry.extend(given.iter().filter(|protocol| !entry.contains(protocol)).copied().collect::<Vec<Protocol>>());
+      entry.sort();
+    }
+  }
+
+  port_protocols
+}
+
+/// Every port to probe with its protocol, following the order of `ports` for each protocol
+/// The ports of `port_protocols` are probed with their own protocols, the others with `protocols`
+pub fn probes(ports: &[u16], protocols: &[Protocol], port_protocols: &BTreeMap<u16, Vec<Protocol>>) -> Vec<(Protocol, u16)> {
+  if port_protocols.is_empty() {
+    return protocols.iter().flat_map(|protocol| ports.iter().map(move |port| (*protocol, *port))).collect();
+  }
+
+  let protocols_of = |port: &u16| port_protocols.get(port).map(Vec::as_slice).unwrap_or(protocols);
+  [Protocol::Tcp, Protocol::Udp]
+    .into_iter()
+    .flat_map(|protocol| ports.iter().filter(move |port| protocols_of(port).contains(&protocol)).map(move |port| (protocol, *port)))
+    .collect()
+}
+
+/// Clap value parser for a list of ports, ranges and protocols
+pub fn parse_port_list(s: &str) -> Result<PortList, String> {
+  s.parse::<PortList>()
+}
+
+/// Clap value parser for a single port, between 1 and 65535
+pub fn parse_port(s: &str) -> Result<u16, String> {
+  let s = s.trim();
+
+  match s.parse::<u16>() {
+    Ok(0) => Err("port 0 is reserved and can't be scanned, expected a number between 1 and 65535".to_string()),
+    Ok(port) => Ok(port),
+    Err(_) => Err(format!("invalid port `{}`, expected a number between 1 and 65535", s)),
+  }
+}
+
+/// Clap value parser for a port or a range of ports
+pub fn parse_port_range(s: &str) -> Result<PortRange, String> {
+  s.parse::<PortRange>()
+}
+
+/// Ports that may be scanned, the ones of the allowlist if any that are not denied,
+/// followed by the ones removed
+pub fn filter_ports(ports: Vec<u16>, allowed: Option<&[u16]>, denied: &[u16]) -> (Vec<u16>, Vec<u16>) {
+  ports.into_iter().partition(|port| allowed.is_none_or(|allowed| allowed.contains(port)) && !denied.contains(port))
+}
+
+/// Ports in the syntax of `--ports`, each run of consecutive ports being a range (`22,80,8000-8100`)
+pub fn compact_ports(ports: &[u16]) -> String {
+  let mut ranges: Vec<PortRange> = Vec::new();
+  for port in ports {
+    match ranges.last_mut() {
+      Some(range) if range.end.checked_add(1) == Some(*port) => range.end = *port,
+      _ => ranges.push(PortRange { start: *port, end: *port }),
+    }
+  }
+
+  ranges
+    .iter()
+    .map(|range| match range.start == range.end {
+      true => range.start.to_string(),
+      false => format!("{}-{}", range.start, range.end),
+    })
+    .collect::<Vec<String>>()
+    .join(",")
+}
+
+/// Expand ranges into a list of ports, keeping the first occurrence of each port
+pub fn expand_ranges(ranges: &[PortRange]) -> Vec<u16> {
+  let mut seen = HashSet::new();
+
+  ranges
+    .iter()
+    .flat_map(|range| range.start..=range.end)
+    .filter(|port| seen.insert(*port))
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_port_spec() {
+    let range = |start, end| PortRange { start, end };
+
+    assert_eq!(PortSpec::default().resolve(), Ok(DEFAULT_PORTS.to_vec()));
+    assert_eq!(PortSpec::list(Some(vec![22, 80, 22])).resolve(), Ok(vec![22, 80]));
+    assert_eq!(PortSpec { top: Some(3), ..PortSpec::default() }.resolve(), Ok(vec![23, 80, 443]));
+    assert_eq!(PortSpec::list(Some(vec![443, 22, 80, 81, 22])).resolve(), Ok(vec![22, 80, 81, 443]));
+
+    // The added ports are sorted with the default ones when no list is given
+    let added = vec![range(443, 443), range(3000, 3001), range(21, 21)];
+    assert_eq!(PortSpec { added: added.clone(), ..PortSpec::default() }.resolve(), Ok(vec![21, 22, 80, 443, 3000, 3001, 8080]));
+    assert_eq!(PortSpec { top: Some(2), added: added.clone(), ..PortSpec::default() }.resolve(), Ok(vec![21, 23, 80, 443, 3000, 3001]));
+    assert_eq!(PortSpec { ranges: Some(vec![range(20, 22)]), added, ..PortSpec::default() }.resolve(), Ok(vec![20, 21, 22, 443, 3000, 3001]));
+  }
+
+  #[test]
+  fn test_port_spec_invalid() {
+    let invalid = |spec: PortSpec| matches!(spec.resolve(), Err(ScannerError::InvalidPorts(_)));
+
+    assert!(invalid(PortSpec { ranges: Some(vec![PortRange { start: 22, end: 22 }]), top: Some(10), ..PortSpec::default() }));
+    assert!(invalid(PortSpec::list(Some(vec![]))));
+    assert!(invalid(PortSpec::list(Some(vec![0, 22]))));
+    assert!(invalid(PortSpec { top: Some(0), ..PortSpec::default() }));
+    assert!(invalid(PortSpec { top: Some(TOP_PORTS.len() + 1), ..PortSpec::default() }));
+    assert!(invalid(PortSpec { added: vec![PortRange { start: 100, end: 1 }], ..PortSpec::default() }));
+  }
+
+  #[test]
+  fn test_port_order() {
+    let mut ports = vec![8000, 443, 1, 22, 8080, 80];
+    PortOrder::Common.sort(&mut ports);
+    assert_eq!(ports, vec![80, 443, 22, 8080, 8000, 1]);
+
+    PortOrder::Numeric.sort(&mut ports);
+    assert_eq!(ports, vec![1, 22, 80, 443, 8000, 8080]);
+
+    PortOrder::Random.sort(&mut ports);
+    assert_eq!(ports, vec![1, 22, 80, 443, 8000, 8080]);
+  }
+
+  #[test]
+  fn test_parse_port_range() {
+    assert_eq!(parse_port_range("22"), Ok(PortRange { start: 22, end: 22 }));
+    assert_eq!(parse_port_range("8000-8100"), Ok(PortRange { start: 8000, end: 8100 }));
+    assert_eq!(parse_port_range(" 1 - 1024 "), Ok(PortRange { start: 1, end: 1024 }));
+  }
+
+  #[test]
+  fn test_parse_port_range_errors() {
+    assert_eq!(parse_port_range("100-1"), Err("invalid port range `100-1`, 100 is greater than 1".to_string()));
+    assert_eq!(parse_port_range("70000"), Err("invalid port `70000`, expected a number between 1 and 65535".to_string()));
+    assert!(parse_port_range("80-").is_err());
+    assert!(parse_port_range("http").is_err());
+  }
+
+  #[test]
+  fn test_parse_port_list() {
+    let list = parse_port_list("22,tcp:80,8000-8001,UDP:53").unwrap();
+    assert_eq!(
+      list.0,
+      vec![
+        (None, PortRange { start: 22, end: 22 }),
+        (Some(Protocol::Tcp), PortRange { start: 80, end: 80 }),
+        (Some(Protocol::Tcp), PortRange { start: 8000, end: 8001 }),
+        (Some(Protocol::Udp), PortRange { start: 53, end: 53 }),
+      ]
+    );
+
+    assert_eq!(parse_port_list("sctp:22"), Err("unknown protocol `sctp` in `sctp:22`, expected tcp or udp".to_string()));
+    assert!(parse_port_list("udp:").is_err());
+    assert!(parse_port_list("tcp:22,udp:0").is_err());
+  }
+
+  #[test]
+  fn test_parse_ports_file() {
+    let content = "# databases\n3306, 5432 # mysql, postgres\n\n27017-27019,\n  udp:11211\n";
+    let file = parse_ports_file(content).unwrap();
+    assert_eq!(file.0.iter().flat_map(PortList::ranges).collect::<Vec<PortRange>>(), vec![
+      PortRange { start: 3306, end: 3306 },
+      PortRange { start: 5432, end: 5432 },
+      PortRange { start: 27017, end: 27019 },
+      PortRange { start: 11211, end: 11211 },
+    ]);
+    assert_eq!(file.0[2].0[0].0, Some(Protocol::Udp));
+
+    assert_eq!(parse_ports_file("22\n80\nhttp\n").unwrap_err(), (3, "invalid port `http`, expected a number between 1 and 65535".to_string()));
+    assert_eq!(parse_ports_file("22,0").unwrap_err().0, 1);
+    assert_eq!(parse_ports_file("# nothing\n"), Ok(PortsFile(vec![])));
+  }
+
+  #[test]
+  fn test_port_protocols() {
+    let lists = vec![parse_port_list("tcp:22,80").unwrap(), parse_port_list("udp:53,161").unwrap(), parse_port_list("443,udp:80").unwrap()];
+    let protocols = port_protocols(&lists, &[Protocol::Tcp]);
+
+    assert_eq!(protocols[&22], vec![Protocol::Tcp]);
+    assert_eq!(protocols[&80], vec![Protocol::Tcp, Protocol::Udp]);
+    assert_eq!(protocols[&161], vec![Protocol::Udp]);
+    assert_eq!(protocols[&443], vec![Protocol::Tcp]);
+
+    let ports = vec![22, 80, 53, 161, 443, 8080];
+    assert_eq!(
+      probes(&ports, &[Protocol::Tcp], &protocols),
+      vec![(Protocol::Tcp, 22), (Protocol::Tcp, 80), (Protocol::Tcp, 443), (Protocol::Tcp, 8080), (Protocol::Udp, 80), (Protocol::Udp, 53), (Protocol::Udp, 161)]
+    );
+
+    // Without any protocol given, every port is probed with the protocols of the scan
+    assert!(port_protocols(&[parse_port_list("22,53").unwrap()], &[Protocol::Tcp]).is_empty());
+    assert_eq!(probes(&[22], &[Protocol::Udp, Protocol::Tcp], &BTreeMap::new()), vec![(Protocol::Udp, 22), (Protocol::Tcp, 22)]);
+  }
+
+  #[test]
+  fn test_parse_port_range_zero_rejected() {
+    assert_eq!(parse_port_range("0"), Err("port 0 is reserved and can't be scanned, expected a number between 1 and 65535".to_string()));
+    assert!(parse_port_range("0-1024").is_err());
+    assert_eq!(parse_port_range("1-1024"), Ok(PortRange { start: 1, end: 1024 }));
+  }
+
+  #[test]
+  fn test_parse_port_range_empty_rejected() {
+    assert!(parse_port_range("").unwrap_err().starts_with("empty port"));
+    assert!(parse_port_range("  ").unwrap_err().starts_with("empty port"));
+  }
+
+  #[test]
+  fn test_expand_ranges_dedup() {
+    let ranges = ["22", "80", "443", "8000-8003", "8002-8005", "80"]
+      .iter()
+      .map(|range| parse_port_range(range).unwrap())
+      .collect::<Vec<PortRange>>();
+
+    assert_eq!(expand_ranges(&ranges), vec![22, 80, 443, 8000, 8001, 8002, 8003, 8004, 8005]);
+  }
+
+  #[test]
+  fn test_filter_ports() {
+    assert_eq!(filter_ports(vec![22, 502, 80, 102], None, DEFAULT_DENIED_PORTS), (vec![22, 80], vec![502, 102]));
+    assert_eq!(filter_ports(vec![22, 502, 80], Some(&[80, 502]), &[502]), (vec![80], vec![22, 502]));
+    assert_eq!(filter_ports(vec![22, 502], None, &[]), (vec![22, 502], vec![]));
+  }
+
+  #[test]
+  fn test_compact_ports() {
+    assert_eq!(compact_ports(&[22, 80, 8000, 8001, 8002, 443, 65534, 65535]), "22,80,8000-8002,443,65534-65535");
+    assert_eq!(compact_ports(&[]), "");
+  }
+
+  #[test]
+  fn test_top_ports() {
+    assert_eq!(top_ports(5), vec![80, 23, 443, 21, 22]);
+    assert_eq!(top_ports(1000).len(), TOP_PORTS.len());
+  }
+
+  #[test]
+  fn test_serialize_latency() {
+    let result = PortResult { latency: Some(Duration::from_micros(1500)), ..PortResult::new(22, Protocol::Tcp, PortState::Open) };
+
+    assert_eq!(serde_json::to_string(&result).unwrap(), r#"{"port":22,"protocol":"tcp","state":"open","latencyMs":1.5}"#);
+  }
+
+  #[test]
+  fn test_display_port_result() {
+    let tcp = PortResult::new(53, Protocol::Tcp, PortState::Open);
+    let udp = PortResult::new(53, Protocol::Udp, PortState::OpenFiltered);
+    let ssh = PortResult { banner: Some("SSH-2.0-OpenSSH_8.9".to_string()), ..PortResult::new(22, Protocol::Tcp, PortState::Open) };
+
+    assert_eq!(tcp.to_string(), "53/tcp");
+    assert_eq!(udp.to_string(), "53/udp (open|filtered)");
+    assert_eq!(ssh.to_string(), "22/tcp [SSH-2.0-OpenSSH_8.9]");
+
+    let identified = PortResult { service: crate::fingerprint::identify("SSH-2.0-OpenSSH_8.9"), ..ssh };
+    assert_eq!(identified.to_string(), "22/tcp ssh/OpenSSH 8.9 [SSH-2.0-OpenSSH_8.9]");
+
+    let unstable = PortResult { unstable: true, ..tcp };
+    assert_eq!(unstable.to_string(), "53/tcp (unstable)");
+    assert_eq!(serde_json::to_string(&unstable).unwrap(), r#"{"port":53,"protocol":"tcp","state":"open","unstable":true}"#);
+  }
+}
@@ -0,0 +1,70 @@
+use std::time::Duration;
+
+use clap::ValueEnum;
+
+use crate::scanner::{DEFAULT_CONCURRENCY, DEFAULT_RETRIES, DEFAULT_TIMEOUT};
+
+/// Settings of a profile, each overridden by its own option when given
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ProfileSettings {
+  /// Time to wait for a port to answer
+  pub timeout: Duration,
+
+  /// Number of ports probed at the same time
+  pub concurrency: usize,
+
+  /// Number of retries of a port after a transient failure
+  pub retries: u32,
+
+  /// Read the banner of open TCP ports
+  pub banner: bool,
+}
+
+/// Short timeout and many probes in flight without retries, for a quick look at a responsive network
+pub const FAST: ProfileSettings = ProfileSettings { timeout: Duration::from_millis(300), concurrency: 1024, retries: 0, banner: false };
+
+/// Defaults of the scanner
+pub const NORMAL: ProfileSettings = ProfileSettings { timeout: DEFAULT_TIMEOUT, concurrency: DEFAULT_CONCURRENCY, retries: DEFAULT_RETRIES, banner: false };
+
+/// Long timeout, few probes in flight and several retries, with the banners, for slow or lossy networks
+pub const THOROUGH: ProfileSettings = ProfileSettings { timeout: Duration::from_millis(3000), concurrency: 64, retries: 3, banner: true };
+
+/// Preset of the timeout, concurrency, retries and banner grabbing of a scan
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ScanProfile {
+  /// 300ms timeout, 1024 probes in flight, no retries
+  Fast,
+
+  /// 1000ms timeout, 256 probes in flight, 1 retry
+  #[default]
+  Normal,
+
+  /// 3000ms timeout, 64 probes in flight, 3 retries and the banners
+  Thorough,
+}
+
+impl ScanProfile {
+  pub fn settings(&self) -> ProfileSettings {
+    match self {
+      ScanProfile::Fast => FAST,
+      ScanProfile::Normal => NORMAL,
+      ScanProfile::Thorough => THOROUGH,
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_profile_settings() {
+    assert_eq!(ScanProfile::default().settings(), NORMAL);
+    assert_eq!(NORMAL.timeout, DEFAULT_TIMEOUT);
+
+    // From the quickest to the most careful
+    let profiles = [ScanProfile::Fast, ScanProfile::Normal, ScanProfile::Thorough].map(|profile| profile.settings());
+    assert!(profiles.windows(2).all(|pair| pair[0].timeout < pair[1].timeout && pair[0].concurrency > pair[1].concurrency && pair[0].retries < pair[1].retries));
+    assert_eq!(profiles.map(|settings| settings.banner), [false, false, true]);
+  }
+}
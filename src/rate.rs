@@ -0,0 +1,74 @@
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Token bucket limiting the number of operations per second, shared between tasks
+///
+/// The bucket holds a single token so operations are evenly spaced rather than
+/// sent in bursts, which is what intrusion detection systems pick up the most.
+#[derive(Debug)]
+pub struct RateLimiter {
+  /// Time for a new token to be available
+  interval: Duration,
+
+  /// Moment the next token is available
+  next: Mutex<Instant>,
+}
+
+impl RateLimiter {
+  /// Create a limiter allowing `rate` operations per second
+  pub fn new(rate: u32) -> Self {
+    Self {
+      interval: Duration::from_secs(1) / rate.max(1),
+      next: Mutex::new(Instant::now()),
+    }
+  }
+
+  /// Take the next token, returning how long to wait before it can be used
+  fn reserve(&self) -> Duration {
+    let mut next = self.next.lock().unwrap();
+    let now = Instant::now();
+
+    let slot = (*next).max(now);
+    *next = slot + self.interval;
+
+    slot - now
+  }
+
+  /// Wait until a token is available without blocking the runtime
+  pub async fn acquire(&self) {
+    let wait = self.reserve();
+    if !wait.is_zero() {
+      tokio::time::sleep(wait).await;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::sync::Arc;
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_rate_limiter_spacing() {
+    let limiter = Arc::new(RateLimiter::new(10));
+    let start = Instant::now();
+
+    let handles = (0..4).map(|_| {
+      let limiter = limiter.clone();
+      tokio::spawn(async move {
+        for _ in 0..3 {
+          limiter.acquire().await;
+        }
+      })
+    }).collect::<Vec<_>>();
+
+    for handle in handles {
+      handle.await.unwrap();
+    }
+
+    // 12 tokens at 10 per second, the first one being immediate
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(1050), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(1500), "{:?}", elapsed);
+  }
+}
@@ -0,0 +1,105 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+
+use crate::cidr::Cidr;
+
+/// Special purpose range of addresses, not hosts that can be scanned like the others
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Reserved {
+  /// `0.0.0.0/8` and `::`, addresses of "this host on this network" never used as a destination
+  ThisNetwork,
+
+  /// `127.0.0.0/8` and `::1`, this machine
+  Loopback,
+
+  /// `224.0.0.0/4` and `ff00::/8`, groups that don't accept TCP connections
+  Multicast,
+
+  /// `255.255.255.255`, the limited broadcast address
+  Broadcast,
+
+  /// `240.0.0.0/4`, reserved for future use and not routed
+  Future,
+}
+
+impl Display for Reserved {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    let name = match self {
+      Reserved::ThisNetwork => "this network",
+      Reserved::Loopback => "loopback",
+      Reserved::Multicast => "multicast",
+      Reserved::Broadcast => "broadcast",
+      Reserved::Future => "reserved",
+    };
+    write!(f, "{}", name)
+  }
+}
+
+impl Reserved {
+  /// Whether targets in the range are refused unless reserved ranges are allowed
+  /// Scanning this machine is legitimate, only warned about
+  pub fn is_refused(&self) -> bool {
+    *self != Reserved::Loopback
+  }
+}
+
+/// Special purpose ranges, the broadcast address before the range including it
+const RANGES: [(&str, Reserved); 8] = [
+  ("0.0.0.0/8", Reserved::ThisNetwork),
+  ("127.0.0.0/8", Reserved::Loopback),
+  ("224.0.0.0/4", Reserved::Multicast),
+  ("255.255.255.255/32", Reserved::Broadcast),
+  ("240.0.0.0/4", Reserved::Future),
+  ("::/128", Reserved::ThisNetwork),
+  ("::1/128", Reserved::Loopback),
+  ("ff00::/8", Reserved::Multicast),
+];
+
+fn ranges() -> impl Iterator<Item = (Cidr, Reserved)> {
+  RANGES.iter().map(|(network, kind)| (network.parse().expect("valid reserved range"), *kind))
+}
+
+/// Special purpose range of an IP, if any
+pub fn classify(ip: &IpAddr) -> Option<Reserved> {
+  ranges().find(|(network, _)| network.contains(ip)).map(|(_, kind)| kind)
+}
+
+/// Special purpose ranges with addresses between `first` and `last`, both included
+pub fn overlapping(first: &IpAddr, last: &IpAddr) -> Vec<(Cidr, Reserved)> {
+  ranges()
+    .filter(|(network, _)| network.ip.is_ipv4() == first.is_ipv4())
+    .filter(|(network, _)| network.network() <= *last && *first <= network.broadcast())
+    .collect()
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn ip(s: &str) -> IpAddr {
+    s.parse().unwrap()
+  }
+
+  #[test]
+  fn test_classify() {
+    assert_eq!(classify(&ip("0.1.2.3")), Some(Reserved::ThisNetwork));
+    assert_eq!(classify(&ip("127.0.0.1")), Some(Reserved::Loopback));
+    assert_eq!(classify(&ip("239.255.255.250")), Some(Reserved::Multicast));
+    assert_eq!(classify(&ip("255.255.255.255")), Some(Reserved::Broadcast));
+    assert_eq!(classify(&ip("250.0.0.1")), Some(Reserved::Future));
+    assert_eq!(classify(&ip("ff02::1")), Some(Reserved::Multicast));
+    assert_eq!(classify(&ip("::")), Some(Reserved::ThisNetwork));
+    assert_eq!(classify(&ip("192.168.1.1")), None);
+    assert_eq!(classify(&ip("fd00::1")), None);
+  }
+
+  #[test]
+  fn test_overlapping() {
+    let kinds = |first: &str, last: &str| overlapping(&ip(first), &ip(last)).into_iter().map(|(_, kind)| kind).collect::<Vec<Reserved>>();
+
+    assert_eq!(kinds("0.0.0.1", "255.255.255.254"), vec![Reserved::ThisNetwork, Reserved::Loopback, Reserved::Multicast, Reserved::Future]);
+    assert_eq!(kinds("223.0.0.0", "224.0.0.0"), vec![Reserved::Multicast]);
+    assert_eq!(kinds("10.0.0.0", "10.255.255.255"), Vec::<Reserved>::new());
+    assert_eq!(kinds("fe80::1", "ff02::1"), vec![Reserved::Multicast]);
+  }
+}
@@ -0,0 +1,149 @@
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::{OnceCell, Semaphore};
+
+/// Number of reverse lookups running at the same time when none is given
+pub const DEFAULT_RESOLVE_CONCURRENCY: usize = 32;
+
+/// Reverse DNS resolver caching the name of every IP looked up
+///
+/// Concurrent lookups of the same IP wait for the first one rather than
+/// querying the resolver again, and failed lookups are cached too.
+#[derive(Debug)]
+pub struct Resolver {
+  /// Name of each IP looked up, `None` when it has none
+  cache: Mutex<HashMap<IpAddr, Arc<OnceCell<Option<String>>>>>,
+
+  /// Lookups in flight
+  lookups: Semaphore,
+}
+
+impl Resolver {
+  /// Create a resolver running at most `concurrency` lookups at the same time
+  pub fn new(concurrency: usize) -> Self {
+    Self {
+      cache: Mutex::new(HashMap::new()),
+      lookups: Semaphore::new(concurrency.max(1)),
+    }
+  }
+
+  /// Name of an IP, `None` when the lookup failed or the IP has no PTR record
+  pub async fn lookup(&self, ip: IpAddr) -> Option<String> {
+    let cell = self.cache.lock().unwrap().entry(ip).or_default().clone();
+
+    cell
+      .get_or_init(|| async {
+        let _permit = self.lookups.acquire().await.unwrap();
+
+        // The system resolver is blocking, keep it off the runtime threads
+        tokio::task::spawn_blocking(move || reverse_lookup(ip)).await.ok().flatten()
+      })
+      .await
+      .clone()
+  }
+
+  /// Number of IPs looked up so far
+  pub fn len(&self) -> usize {
+    self.cache.lock().unwrap().len()
+  }
+
+  /// Whether no IP was looked up yet
+  pub fn is_empty(&self) -> bool {
+    self.len() == 0
+  }
+}
+
+impl Default for Resolver {
+  fn default() -> Self {
+    Self::new(DEFAULT_RESOLVE_CONCURRENCY)
+  }
+}
+
+/// Reverse lookup of an IP with the system resolver (`/etc/hosts`, DNS PTR records)
+/// Return `None` when the IP has no name
+#[cfg(unix)]
+pub fn reverse_lookup(ip: IpAddr) -> Option<String> {
+  use std::ffi::CStr;
+  use std::mem;
+
+  // `getnameinfo` wants a `sockaddr`, the port is not used
+  let (storage, length) = unsafe {
+    let mut storage: libc::sockaddr_storage = mem::zeroed();
+
+    let length = match SocketAddr::new(ip, 0) {
+      SocketAddr::V4(address) => {
+        let sockaddr = &mut *(&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in);
+        sockaddr.sin_family = libc::AF_INET as libc::sa_family_t;
+        sockaddr.sin_addr = libc::in_addr { s_addr: u32::from_ne_bytes(address.ip().octets()) };
+        mem::size_of::<libc::sockaddr_in>()
+      }
+      SocketAddr::V6(address) => {
+        let sockaddr = &mut *(&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in6);
+        sockaddr.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+        sockaddr.sin6_addr = libc::in6_addr { s6_addr: address.ip().octets() };
+        mem::size_of::<libc::sockaddr_in6>()
+      }
+    };
+
+    (storage, length as libc::socklen_t)
+  };
+
+  let mut host = [0 as libc::c_char; libc::NI_MAXHOST as usize];
+
+  // NI_NAMEREQD makes the lookup fail instead of returning the IP itself
+  let status = unsafe {
+    libc::getnameinfo(
+      &storage as *const libc::sockaddr_storage as *const libc::sockaddr,
+      length,
+      host.as_mut_ptr(),
+      host.len() as libc::socklen_t,
+      std::ptr::null_mut(),
+      0,
+      libc::NI_NAMEREQD,
+    )
+  };
+
+  if status != 0 {
+    return None;
+  }
+
+  let name = unsafe { CStr::from_ptr(host.as_ptr()) }.to_string_lossy().into_owned();
+  if name.is_empty() {
+    None
+  } else {
+    Some(name)
+  }
+}
+
+/// Reverse lookups are only supported with the system resolver of unix systems
+#[cfg(not(unix))]
+pub fn reverse_lookup(_ip: IpAddr) -> Option<String> {
+  None
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::net::Ipv4Addr;
+
+  #[test]
+  fn test_reverse_lookup_localhost() {
+    let name = reverse_lookup(Ipv4Addr::LOCALHOST.into()).unwrap();
+
+    assert!(name.starts_with("localhost"), "{}", name);
+  }
+
+  #[tokio::test]
+  async fn test_resolver_cache() {
+    let resolver = Resolver::new(2);
+    let ip: IpAddr = Ipv4Addr::LOCALHOST.into();
+
+    let (first, second) = tokio::join!(resolver.lookup(ip), resolver.lookup(ip));
+
+    assert!(first.is_some());
+    assert_eq!(first, second);
+    assert_eq!(resolver.len(), 1);
+  }
+}
@@ -1,237 +1,4380 @@
-use std::net::{Ipv4Addr};
-use std::net::{TcpStream, SocketAddr};
+use std::net::{IpAddr, Ipv4Addr};
+use std::net::SocketAddr;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashSet};
 use std::fmt::{Display, Formatter};
-use std::time::Duration;
+use std::future::Future;
+use std::io::{self, ErrorKind};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
 
-use serde::{Serialize};
-use serde_yaml;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+use tokio::io::Interest;
+use tokio::net::{TcpStream, UdpSocket};
+use tokio::sync::mpsc::{self, Receiver};
+
+use crate::arp::{ArpTable, DiscoveryMethod};
+use crate::banner::{self, BANNER_TIMEOUT};
+use crate::cancel::CancelToken;
+use crate::cidr::{Cidr, IpRange, IpWildcard};
+use crate::color;
+use crate::connection::{ConnectionErrors, ErrorCounter, Source};
+use crate::error::ScannerError;
+use crate::fingerprint;
+use crate::http::{self, HTTP_PORTS};
+use crate::logging::{self, Level};
+use crate::output::{GroupBy, MarkdownReport, OutputFormat, PortReport, SerializedScanner, TableReport};
+use crate::port::{self, PortOrder, PortResult, PortSpec, PortState, Protocol};
+use crate::proxy::{self, Proxy};
+use crate::reserved::{self, Reserved};
+use crate::rate::RateLimiter;
+use crate::resolve::Resolver;
+use crate::syn::{ScanType, SynScanner};
+use crate::targets::Target;
+use crate::timeout::ConnectTimeout;
+use crate::tls::{self, TLS_TIMEOUT};
+
+/// Ports scanned on each host when none are given
+pub use crate::port::{DEFAULT_DENIED_PORTS, DEFAULT_PORTS};
+
+/// Time to wait for a port to answer when none is given
+pub const DEFAULT_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Number of IPs, and of connections, in flight at the same time when none is given
+pub const DEFAULT_CONCURRENCY: usize = 256;
+
+/// Largest network accepted, as many addresses as the whole IPv4 space
+pub const MAX_NETWORK_SIZE: u128 = 1 << 32;
+
+/// Host names of the targets resolved at the same time
+pub const MAX_RESOLUTIONS: usize = 32;
+
+/// Number of retries of a port that failed for a transient reason when none is given
+pub const DEFAULT_RETRIES: u32 = 1;
+
+/// Wait before the first retry of a port, doubled for each following retry
+pub const RETRY_BACKOFF: Duration = Duration::from_millis(50);
+
+/// Attempts of a port waiting for file descriptors to be freed, on top of its retries
+pub const EXHAUSTED_RETRIES: u32 = 5;
+
+/// Ports probed to check if a host is up, the most likely to answer on any kind of machine
+pub const DISCOVERY_PORTS: [u16; 4] = [80, 443, 22, 445];
+
+/// Default timeout of a scan of this machine, which answers without a round trip on the network
+pub const LOOPBACK_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Time left to the hosts in progress to finish once a scan is interrupted
+pub const INTERRUPT_GRACE: Duration = Duration::from_secs(2);
 
 /// Scanner for an IP
 #[derive(Debug, Clone, Serialize)]
 pub struct Scanner {
-  pub ip: Ipv4Addr,
+  /// How the last scan was run, at the top of the reports
+  #[serde(skip_serializing_if = "Option::is_none")]
+  meta: Option<ScanMeta>,
+
+  pub ip: IpAddr,
+
+  /// Network to scan when the IP was given in CIDR notation
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub prefix: Option<u8>,
+
+  /// Range of addresses to scan when one was given, the IP being its start
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub range: Option<IpRange>,
+
+  /// Addresses with wildcard octets to scan when given, the IP being the lowest one
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub wildcard: Option<IpWildcard>,
+
+  /// Host name given instead of an IP
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub hostname: Option<String>,
+
+  /// Targets scanned together, when several were given
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub targets: Vec<String>,
+
+  /// Every address of the host name, all of them being scanned
+  #[serde(skip)]
+  addresses: Vec<IpAddr>,
+
+  /// Hosts of each target when several were given, enumerated one target after the other
+  #[serde(skip)]
+  combined: Vec<TargetHosts>,
+
   ports: Vec<u16>,
 
-  #[serde(rename(deserialize = "results"))]
-  result: Vec<IpScanResult>,
-}
+  /// Options of the scan, the seed being set by `scan` when randomizing without one
+  #[serde(flatten)]
+  pub options: ScanOptions,
 
-/// Trait for reporting the result of a scan
-pub trait Report {
-  fn report(&self) -> String;
+  /// List hosts without open ports in the report
+  #[serde(skip)]
+  pub show_all: bool,
+
+  /// Keep the hosts without open ports in the JSON, JSON Lines and YAML reports, left out otherwise
+  #[serde(skip)]
+  pub include_down: bool,
+
+  /// Show the connection latency of the open ports in the report
+  #[serde(skip)]
+  pub show_latency: bool,
+
+  /// List the findings of the text report by host or by port, JSON and YAML
+  /// reports also having them by port when grouping by port
+  #[serde(skip)]
+  pub group_by: GroupBy,
+
+  /// Don't write the progress of the scan to stderr
+  #[serde(skip)]
+  pub quiet: bool,
+
+  /// Reverse lookups already done, kept between scans
+  #[serde(skip)]
+  resolver: Arc<Resolver>,
+
+  pub(crate) result: Vec<IpScanResult>,
+
+  /// Counts of the last scan
+  #[serde(skip_serializing_if = "Option::is_none")]
+  summary: Option<ScanSummary>,
+
+  /// Reasons the connections of the last scan failed for
+  #[serde(rename = "connectionErrors", skip_serializing_if = "ConnectionErrors::is_empty")]
+  connection_errors: ConnectionErrors,
+
+  /// Timeout the last scan adapted to the round trip times, when adaptive and enough connections succeeded
+  #[serde(rename = "adaptedTimeoutMs", skip_serializing_if = "Option::is_none", serialize_with = "crate::output::serialize_optional_ms")]
+  adapted_timeout: Option<Duration>,
+
+  /// Results of the hosts a previous run already scanned, skipped by the scan
+  /// and merged with its results
+  #[serde(skip)]
+  resumed: Vec<IpScanResult>,
 }
 
-/// Result of a scan on a single IP
-/// Return the IP and the open ports
-#[derive(Debug, Clone, Serialize)]
-pub struct IpScanResult {
-  /// IP scanned
-  pub ip: Ipv4Addr,
+/// Options of a scan, applied to every host
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ScanOptions {
+  /// Protocols to probe each port with
+  pub protocols: Vec<Protocol>,
 
-  /// List of open ports
-  #[serde(rename = "openPorts")]
-  pub open_ports: Vec<u16>,
+  /// Protocols of the ports given with their own, such as UDP for `udp:53`,
+  /// the other ports being probed with `protocols`
+  #[serde(rename = "portProtocols", skip_serializing_if = "BTreeMap::is_empty")]
+  pub port_protocols: BTreeMap<u16, Vec<Protocol>>,
+
+  /// Time to wait for a port to answer
+  #[serde(rename = "timeoutMs", serialize_with = "crate::output::serialize_ms")]
+  pub timeout: Duration,
+
+  /// Adapt the time to wait for a TCP connection to the round trip times, never going below
+  /// this duration nor above `timeout`, which the retries still wait
+  #[serde(skip)]
+  pub adaptive_timeout: Option<Duration>,
+
+  /// Time to wait for the banner or the HTTP response of an open port, once connected
+  #[serde(rename = "readTimeoutMs", serialize_with = "crate::output::serialize_ms")]
+  pub read_timeout: Duration,
+
+  /// Number of ports probed at the same time, across every host, each probe opening a single connection
+  pub concurrency: usize,
+
+  /// Number of retries of a port that failed for a transient reason
+  pub retries: u32,
+
+  /// Probe each open port a second time, flagging it unstable when it's no longer open
+  #[serde(skip_serializing_if = "std::ops::Not::not")]
+  pub confirm: bool,
+
+  /// Maximum number of connection attempts per second, unlimited when not set
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub rate: Option<u32>,
+
+  /// Maximum number of ports probed per second across the whole scan, unlimited when not set
+  #[serde(rename = "portsPerSecond", skip_serializing_if = "Option::is_none")]
+  pub pps: Option<u32>,
+
+  /// Maximum time spent on a host, its ports not probed by then being unscanned
+  #[serde(rename = "hostTimeoutMs", skip_serializing_if = "Option::is_none", serialize_with = "crate::output::serialize_optional_ms")]
+  pub host_timeout: Option<Duration>,
+
+  /// Number of open ports, across every host, the scan stops at, the results being partial
+  #[serde(rename = "maxOpen", skip_serializing_if = "Option::is_none")]
+  pub max_open: Option<usize>,
+
+  /// Maximum duration of the whole scan, the hosts and ports not probed by then being missing or unscanned
+  #[serde(rename = "maxDurationMs", skip_serializing_if = "Option::is_none", serialize_with = "crate::output::serialize_optional_ms")]
+  pub max_duration: Option<Duration>,
+
+  /// How the TCP ports are probed, with a full connection by default
+  #[serde(skip)]
+  pub scan_type: ScanType,
+
+  /// Check each host is up before scanning its ports
+  pub discovery: bool,
+
+  /// How the hosts are checked to be up, with TCP by default
+  #[serde(skip)]
+  pub discovery_method: DiscoveryMethod,
+
+  /// Read the banner of open TCP ports
+  pub banner: bool,
+
+  /// Try a TLS handshake on open TCP ports
+  pub tls: bool,
+
+  /// Request `/` on the open TCP ports of `http_ports`
+  pub http: bool,
+
+  /// Ports probed with an HTTP request
+  #[serde(rename = "httpPorts")]
+  pub http_ports: Vec<u16>,
+
+  /// Name of the virtual host sent in the TLS handshake and the `Host` header of the HTTP requests,
+  /// the host name of the target or, when resolving, the name of each host being sent otherwise
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub sni: Option<String>,
+
+  /// SOCKS5 proxy the TCP connections go through, UDP probes being sent directly
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub proxy: Option<Proxy>,
+
+  /// Local address or interface the connections are made from, the address family
+  /// being checked against the targets when the scanner is built
+  #[serde(skip_serializing_if = "Source::is_default")]
+  pub source: Source,
+
+  /// Reverse DNS lookup of the hosts with open ports
+  pub resolve: bool,
+
+  /// Networks and IPs never scanned
+  #[serde(skip_serializing_if = "Vec::is_empty")]
+  pub exclude: Vec<Cidr>,
+
+  /// Only ports that may be scanned, the others being removed, any port when not set
+  #[serde(skip)]
+  pub allowed_ports: Option<Vec<u16>>,
+
+  /// Ports never scanned whatever the ports given, [`DEFAULT_DENIED_PORTS`] by default
+  #[serde(skip)]
+  pub denied_ports: Vec<u16>,
+
+  /// Scan the hosts in a random order
+  #[serde(skip)]
+  pub randomize: bool,
+
+  /// Scan the ports of each host in a random order
+  #[serde(skip)]
+  pub randomize_ports: bool,
+
+  /// Order the ports of each host are probed in, ascending when not set
+  #[serde(skip)]
+  pub port_order: Option<PortOrder>,
+
+  /// Seed of the random order, for reproducible scans
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub seed: Option<u64>,
+
+  /// Scan the targets in reserved ranges (multicast, broadcast, `0.0.0.0/8`, …), checked when the scanner is built
+  #[serde(skip)]
+  pub allow_reserved: bool,
 }
 
-impl Display for IpScanResult {
-  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    if self.open_ports.len() == 0 {
-      return Ok(());
+impl Default for ScanOptions {
+  fn default() -> Self {
+    Self {
+      protocols: vec![Protocol::Tcp],
+      port_protocols: BTreeMap::new(),
+      timeout: DEFAULT_TIMEOUT,
+      adaptive_timeout: None,
+      read_timeout: BANNER_TIMEOUT,
+      concurrency: DEFAULT_CONCURRENCY,
+      retries: DEFAULT_RETRIES,
+      confirm: false,
+      rate: None,
+      pps: None,
+      host_timeout: None,
+      max_open: None,
+      max_duration: None,
+      scan_type: ScanType::Connect,
+      discovery: true,
+      discovery_method: DiscoveryMethod::Tcp,
+      banner: false,
+      tls: false,
+      http: false,
+      http_ports: HTTP_PORTS.to_vec(),
+      sni: None,
+      proxy: None,
+      source: Source::default(),
+      resolve: false,
+      exclude: Vec::new(),
+      allowed_ports: None,
+      denied_ports: DEFAULT_DENIED_PORTS.to_vec(),
+      randomize: false,
+      randomize_ports: false,
+      port_order: None,
+      seed: None,
+      allow_reserved: false,
     }
-    let formatted_ports = self.open_ports.iter().map(|x| x.to_string()).collect::<Vec<String>>().join(", ");
-    write!(f, "{}: {:>15}\n", self.ip, formatted_ports)
   }
 }
 
-impl Display for Scanner {
-  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
-    write!(f, "Scanner for {}\n", self.ip).unwrap();
-    write!(f, "Ports: {:?}\n", self.ports).unwrap();
-    Ok(())
-  }
+/// Builder of a scanner, every option not set keeping its default value
+/// The setters of the scan options are named after the fields of [`ScanOptions`]
+///
+/// ```
+/// use std::time::Duration;
+/// use skanner::Scanner;
+///
+/// let scanner = Scanner::builder()
+///   .ip("192.168.1.0/24")
+///   .ports(vec![22, 80, 443])
+///   .timeout(Duration::from_millis(500))
+///   .concurrency(64)
+///   .build()
+///   .unwrap();
+///
+/// assert_eq!(scanner.target(), "192.168.1.0/24");
+/// assert_eq!(scanner.get_ips().len(), 254);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ScannerBuilder {
+  /// Targets as parsed, an invalid one failing the build
+  targets: Vec<Result<Target, ScannerError>>,
+  ports: PortSpec,
+  options: ScanOptions,
+  show_all: bool,
+  include_down: bool,
+  show_latency: bool,
+  group_by: GroupBy,
+  quiet: bool,
+
+  /// Whether `timeout` was called, the default timeout being shorter on loopback otherwise
+  timeout_set: bool,
 }
 
-impl Scanner {
-  pub fn new(ip: String, ports: Option<Vec<u16>>) -> Self {
-    let ip = ip.parse::<Ipv4Addr>().unwrap();
+impl ScannerBuilder {
+  /// IP, network in CIDR notation or host name to scan, parsed as a [`Target`]
+  /// Called more than once, the hosts of every target are scanned
+  pub fn ip(mut self, ip: impl AsRef<str>) -> Self {
+    self.targets.push(ip.as_ref().parse());
+    self
+  }
 
-    let ports = match ports {
-      Some(ports) => ports,
-      None => vec![80, 22, 443, 8080]
-    };
+  /// Several targets to scan
+  pub fn targets<I: IntoIterator<Item = Target>>(mut self, targets: I) -> Self {
+    self.targets.extend(targets.into_iter().map(Ok));
+    self
+  }
 
-    Self {
-      ip,
-      ports,
-      result: Vec::new(),
-    }
+  /// Ports to scan instead of the default ones
+  pub fn ports(mut self, ports: Vec<u16>) -> Self {
+    self.ports.ranges = PortSpec::list(Some(ports)).ranges;
+    self
   }
 
-  pub async fn scan(&mut self) {
-    let ips = self.get_ips();
-    let mut results: Vec<IpScanResult> = Vec::new();
+  /// Ports to scan as given on the command line, with the most common ones or the added ones
+  pub fn port_spec(mut self, ports: PortSpec) -> Self {
+    self.ports = ports;
+    self
+  }
 
-    println!("Scanning {} IPs for {} ports", ips.len(), self.ports.len());
+  /// Replace every option of the scan at once, the default timeout being shorter on loopback as when not set
+  pub fn options(mut self, options: ScanOptions) -> Self {
+    self.options = options;
+    self.timeout_set = false;
+    self
+  }
 
-    let mut handles = Vec::with_capacity(ips.len());
-    for ip in ips {
-      let ports = self.ports.clone();
+  pub fn protocols(mut self, protocols: Vec<Protocol>) -> Self {
+    self.options.protocols = protocols;
+    self
+  }
 
-      handles.push(tokio::spawn(async move {
-        scan_ip(ip, ports)
-      }));
-    }
+  pub fn port_protocols(mut self, port_protocols: BTreeMap<u16, Vec<Protocol>>) -> Self {
+    self.options.port_protocols = port_protocols;
+    self
+  }
 
-    for handle in handles {
-      let result = handle.await.unwrap();
-      results.push(result);
-    }
+  /// Time to wait for a port to answer, kept as is on loopback
+  pub fn timeout(mut self, timeout: Duration) -> Self {
+    self.options.timeout = timeout;
+    self.timeout_set = true;
+    self
+  }
 
+  pub fn concurrency(mut self, concurrency: usize) -> Self {
+    self.options.concurrency = concurrency;
+    self
+  }
 
-    self.result = results;
+  pub fn retries(mut self, retries: u32) -> Self {
+    self.options.retries = retries;
+    self
   }
 
-  pub fn get_ips(&self) -> Vec<Ipv4Addr> {
-    let mut ips: Vec<Ipv4Addr> = Vec::new();
-    let base_ips: [u8; 4] = self.ip.octets();
+  pub fn confirm(mut self, confirm: bool) -> Self {
+    self.options.confirm = confirm;
+    self
+  }
 
-    let number_of_groups = base_ips.iter().filter(|x| **x == 0).count() as u32;
-    if number_of_groups == 0 {
-      return vec![self.ip];
-    }
+  pub fn rate(mut self, rate: u32) -> Self {
+    self.options.rate = Some(rate);
+    self
+  }
 
-    let number_of_ips = 256_u32.pow(number_of_groups);
+  pub fn pps(mut self, pps: u32) -> Self {
+    self.options.pps = Some(pps);
+    self
+  }
 
+  pub fn adaptive_timeout(mut self, min_timeout: Duration) -> Self {
+    self.options.adaptive_timeout = Some(min_timeout);
+    self
+  }
 
-    // Handle the case where the number of IPs is 256 to exclude the broadcast address
-    let number_of_ips = if number_of_ips == 256 {
-      number_of_ips - 1
-    } else {
-      number_of_ips
-    };
+  pub fn read_timeout(mut self, read_timeout: Duration) -> Self {
+    self.options.read_timeout = read_timeout;
+    self
+  }
 
-    for i in 1..number_of_ips {
-      let mut ip = base_ips.clone();
-      let l1 = i % 255;
-      let l2 = i / 255 % 256;
-      let l3 = i / 255 / 255 % 256;
-      let l4 = i / 255 / 255 / 255 % 256;
+  pub fn host_timeout(mut self, host_timeout: Duration) -> Self {
+    self.options.host_timeout = Some(host_timeout);
+    self
+  }
 
-      if ip[0] == 0 {
-        ip[0] = l4 as u8;
-      }
+  pub fn max_open(mut self, max_open: usize) -> Self {
+    self.options.max_open = Some(max_open);
+    self
+  }
 
-      if ip[1] == 0 {
-        ip[1] = l3 as u8;
-      }
+  pub fn max_duration(mut self, max_duration: Duration) -> Self {
+    self.options.max_duration = Some(max_duration);
+    self
+  }
+
+  pub fn scan_type(mut self, scan_type: ScanType) -> Self {
+    self.options.scan_type = scan_type;
+    self
+  }
+
+  pub fn discovery(mut self, discovery: bool) -> Self {
+    self.options.discovery = discovery;
+    self
+  }
+
+  pub fn discovery_method(mut self, discovery_method: DiscoveryMethod) -> Self {
+    self.options.discovery_method = discovery_method;
+    self
+  }
+
+  pub fn banner(mut self, banner: bool) -> Self {
+    self.options.banner = banner;
+    self
+  }
+
+  pub fn tls(mut self, tls: bool) -> Self {
+    self.options.tls = tls;
+    self
+  }
+
+  pub fn http(mut self, http: bool) -> Self {
+    self.options.http = http;
+    self
+  }
+
+  pub fn http_ports(mut self, http_ports: Vec<u16>) -> Self {
+    self.options.http_ports = http_ports;
+    self
+  }
+
+  pub fn sni(mut self, sni: impl Into<String>) -> Self {
+    self.options.sni = Some(sni.into());
+    self
+  }
+
+  pub fn proxy(mut self, proxy: Proxy) -> Self {
+    self.options.proxy = Some(proxy);
+    self
+  }
+
+  pub fn source(mut self, source: Source) -> Self {
+    self.options.source = source;
+    self
+  }
+
+  pub fn resolve(mut self, resolve: bool) -> Self {
+    self.options.resolve = resolve;
+    self
+  }
+
+  pub fn exclude(mut self, exclude: Vec<Cidr>) -> Self {
+    self.options.exclude = exclude;
+    self
+  }
+
+  pub fn allowed_ports(mut self, allowed_ports: Vec<u16>) -> Self {
+    self.options.allowed_ports = Some(allowed_ports);
+    self
+  }
+
+  pub fn denied_ports(mut self, denied_ports: Vec<u16>) -> Self {
+    self.options.denied_ports = denied_ports;
+    self
+  }
+
+  pub fn randomize(mut self, randomize: bool) -> Self {
+    self.options.randomize = randomize;
+    self
+  }
+
+  pub fn randomize_ports(mut self, randomize_ports: bool) -> Self {
+    self.options.randomize_ports = randomize_ports;
+    self
+  }
+
+  pub fn port_order(mut self, port_order: PortOrder) -> Self {
+    self.options.port_order = Some(port_order);
+    self
+  }
+
+  pub fn seed(mut self, seed: u64) -> Self {
+    self.options.seed = Some(seed);
+    self
+  }
+
+  pub fn allow_reserved(mut self, allow_reserved: bool) -> Self {
+    self.options.allow_reserved = allow_reserved;
+    self
+  }
+
+  pub fn show_all(mut self, show_all: bool) -> Self {
+    self.show_all = show_all;
+    self
+  }
+
+  pub fn include_down(mut self, include_down: bool) -> Self {
+    self.include_down = include_down;
+    self
+  }
+
+  pub fn show_latency(mut self, show_latency: bool) -> Self {
+    self.show_latency = show_latency;
+    self
+  }
+
+  pub fn group_by(mut self, group_by: GroupBy) -> Self {
+    self.group_by = group_by;
+    self
+  }
+
+  pub fn quiet(mut self, quiet: bool) -> Self {
+    self.quiet = quiet;
+    self
+  }
+
+  /// Create the scanner for an IP or a network, see [`ScannerBuilder::lookup`] for host names
+  pub fn build(self) -> Result<Scanner, ScannerError> {
+    let scanners = self.parsed_targets()?.into_iter().map(|target| Scanner::from_target(target, Vec::new())).collect::<Result<Vec<Scanner>, ScannerError>>()?;
+    let scanner = Scanner::combine(scanners, self.ports.resolve()?)?;
+
+    self.configure(scanner)
+  }
+
+  /// Create the scanner, resolving the target first when it is a host name
+  pub async fn lookup(self) -> Result<Scanner, ScannerError> {
+    let scanners = Scanner::lookup_each(self.parsed_targets()?, self.quiet).await?;
+    let scanner = Scanner::combine(scanners, self.ports.resolve()?)?;
+
+    self.configure(scanner)
+  }
+
+  /// Targets given, or the error of the first invalid one
+  fn parsed_targets(&self) -> Result<Vec<Target>, ScannerError> {
+    self.targets.iter().cloned().collect()
+  }
+
+  /// Apply the options, removing the ports not allowed or denied, refusing the targets
+  /// in reserved ranges unless they are allowed and the ones a source address can't reach
+  fn configure(self, mut scanner: Scanner) -> Result<Scanner, ScannerError> {
+    scanner.options = self.options.clone();
+    // A closed port of this machine answers at once, only a dropped probe waits for the whole timeout
+    if !self.timeout_set && scanner.options.timeout == DEFAULT_TIMEOUT && scanner.is_loopback() {
+      scanner.options.timeout = LOOPBACK_TIMEOUT;
+    }
+    scanner.show_all = self.show_all;
+    scanner.include_down = self.include_down;
+    scanner.show_latency = self.show_latency;
+    scanner.group_by = self.group_by;
+    scanner.quiet = self.quiet;
 
-      if ip[2] == 0 {
-        ip[2] = l2 as u8;
+    let removed = scanner.remove_ports(self.options.allowed_ports.as_deref(), &self.options.denied_ports);
+    if !removed.is_empty() {
+      if scanner.ports.is_empty() {
+        return Err(ScannerError::InvalidPorts("every port given is denied or not allowed".to_string()));
+      }
+      if !scanner.quiet {
+        warn!("Not scanning {}, denied or not allowed", removed.iter().map(|port| port.to_string()).collect::<Vec<String>>().join(", "));
       }
+    }
 
-      if ip[3] == 0 {
-        ip[3] = l1 as u8;
+    if !self.options.allow_reserved {
+      for (network, kind) in scanner.reserved_ranges() {
+        if kind.is_refused() {
+          return Err(ScannerError::ReservedTarget { network: network.to_string(), kind });
+        }
+        if !scanner.quiet {
+          warn!("Scanning addresses of {} ({}), which is this machine", network, kind);
+        }
       }
+    }
 
-      ips.push(Ipv4Addr::new(ip[0], ip[1], ip[2], ip[3]));
+    if let Some(source) = scanner.options.source.ip {
+      // A host name can still be scanned on its addresses of the family of the source
+      if scanner.hostname.is_some() && scanner.addresses.iter().any(|ip| scanner.options.source.reaches(ip)) {
+        let source = scanner.options.source.clone();
+        scanner.addresses.retain(|ip| source.reaches(ip));
+        scanner.ip = scanner.addresses[0];
+      }
+      if let Some((first, _)) = scanner.bounds().into_iter().find(|(first, _)| !scanner.options.source.reaches(first)) {
+        return Err(ScannerError::SourceFamily { source, target: first.to_string() });
+      }
     }
 
-    ips
+    Ok(scanner)
   }
 }
 
-impl Report for Scanner {
-  fn report(&self) -> String {
-    let mut report = String::new();
+/// Counts of a scan, for a quick look at what was found
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanSummary {
+  /// Hosts scanned, excluded ones not included
+  pub hosts_scanned: usize,
 
-    report.push_str(&format!("Scanner for {}\n", self.ip));
-    report.push_str(&format!("Ports: {:?}\n", self.ports));
-    report.push_str(&format!("=========================\n"));
+  /// Hosts that answered the discovery probe
+  pub hosts_up: usize,
+
+  /// Hosts with at least one open port
+  pub hosts_with_open_ports: usize,
+
+  /// Open ports found on every host
+  pub open_ports: usize,
+
+  /// Wall-clock time of the scan
+  #[serde(rename = "elapsedMs", serialize_with = "crate::output::serialize_ms", deserialize_with = "crate::output::deserialize_ms")]
+  pub elapsed: Duration,
+
+  /// Whether the scan was interrupted, the counts being the ones of the hosts done
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub interrupted: bool,
+
+  /// Whether the scan stopped once the maximum number of open ports was found, the results being partial
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub max_open_reached: bool,
 
-    for result in &self.result {
-      let result = format!("{}\n", result);
-      report.push_str(&result);
+  /// Whether the scan stopped at its maximum duration, the results being partial
+  #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+  pub deadline_reached: bool,
+
+  /// Hosts and ports new since the baseline of `--alert-on-new`, `None` without one
+  #[serde(skip)]
+  pub new_since_baseline: Option<usize>,
+}
+
+impl ScanSummary {
+  /// Count the results of a scan that took `elapsed`
+  pub fn new(results: &[IpScanResult], elapsed: Duration) -> Self {
+    Self {
+      hosts_scanned: results.len(),
+      hosts_up: results.iter().filter(|result| result.up).count(),
+      hosts_with_open_ports: results.iter().filter(|result| !result.open_ports.is_empty()).count(),
+      open_ports: results.iter().map(|result| result.open_ports.len()).sum(),
+      elapsed,
+      interrupted: false,
+      max_open_reached: false,
+      deadline_reached: false,
+      new_since_baseline: None,
     }
+  }
+}
 
-    report
+impl Display for ScanSummary {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(
+      f,
+      "{} hosts scanned, {} up, {} with open ports, {} open ports in {:.2}s",
+      self.hosts_scanned,
+      self.hosts_up,
+      self.hosts_with_open_ports,
+      self.open_ports,
+      self.elapsed.as_secs_f64()
+    )?;
+    if self.interrupted {
+      write!(f, ", interrupted")?;
+    }
+    if self.max_open_reached {
+      write!(f, ", stopped at the maximum of open ports")?;
+    }
+    if self.deadline_reached {
+      write!(f, ", stopped at the maximum duration")?;
+    }
+    Ok(())
   }
 }
 
-/// Scan an IP for a list of ports
-/// Use TCP
-/// Return the IP and the open ports
-fn scan_ip(ip: Ipv4Addr, ports: Vec<u16>) -> IpScanResult {
-    let mut open_ports: Vec<u16> = Vec::new();
+/// How a scan was run, recorded at the top of its reports so they can be audited and compared later
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ScanMeta {
+  /// Version of skanner the scan was run with
+  pub version: String,
 
-    println!("Scanning {}…", ip);
+  /// Time the scan started, as an RFC 3339 UTC timestamp
+  pub started_at: String,
 
-    for port in &ports {
-      let is_open = scan_port(ip.clone(), *port);
-      if is_open {
-        open_ports.push(*port);
-      }
-    }
+  /// Target of the scan
+  pub target: String,
 
-    IpScanResult {
-      ip,
-      open_ports,
+  /// Ports scanned, in the syntax of `--ports`
+  pub ports: String,
+
+  /// Time waited for each port
+  #[serde(rename = "timeoutMs", serialize_with = "crate::output::serialize_ms", deserialize_with = "crate::output::deserialize_ms")]
+  pub timeout: Duration,
+
+  /// Ports probed at the same time
+  pub concurrency: usize,
+}
+
+impl ScanMeta {
+  /// Metadata of a scan of `scanner` started at `started`
+  pub fn new(scanner: &Scanner, started: SystemTime) -> Self {
+    Self {
+      version: env!("CARGO_PKG_VERSION").to_string(),
+      started_at: crate::output::timestamp(started),
+      target: scanner.target(),
+      ports: port::compact_ports(&scanner.ports),
+      timeout: scanner.options.timeout,
+      concurrency: scanner.options.concurrency,
     }
   }
+}
+
+/// Comment lines of the text reports, `#` starting each line
+impl Display for ScanMeta {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "# skanner {}", self.version)?;
+    writeln!(f, "# Started: {}", self.started_at)?;
+    writeln!(f, "# Target: {}", self.target)?;
+    writeln!(f, "# Ports: {}", self.ports)?;
+    writeln!(f, "# Timeout: {}ms", self.timeout.as_millis())?;
+    writeln!(f, "# Concurrency: {}", self.concurrency)
+  }
+}
+
+/// Results of a previous scan, read back from its JSON or YAML report
+/// to be compared with a new scan or reported again in another format
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct ScanResults {
+  /// IP of the target, the first one when several were scanned
+  pub ip: IpAddr,
+
+  /// Network scanned when the IP was given in CIDR notation
+  #[serde(default)]
+  pub prefix: Option<u8>,
+
+  /// Range of addresses scanned when one was given
+  #[serde(default)]
+  pub range: Option<IpRange>,
+
+  /// Addresses with wildcard octets scanned when given
+  #[serde(default)]
+  pub wildcard: Option<IpWildcard>,
+
+  /// Host name given instead of an IP
+  #[serde(default)]
+  pub hostname: Option<String>,
+
+  /// Targets scanned together, when several were
+  #[serde(default)]
+  pub targets: Vec<String>,
 
-  fn scan_port(ip: Ipv4Addr, port: u16) -> bool {
-    let address =  SocketAddr::from((ip.octets(), port));
+  /// Ports scanned on each host
+  pub ports: Vec<u16>,
 
-    let result = TcpStream::connect_timeout(&address, Duration::new(1, 0));
+  /// Result of each host reported
+  #[serde(rename = "result")]
+  pub results: Vec<IpScanResult>,
 
-    let is_open = match result {
-      Ok(_) => true,
-      Err(_) => false,
+  /// Counts of the scan
+  #[serde(default)]
+  pub summary: Option<ScanSummary>,
+
+  /// How the scan was run, missing from the reports of older versions
+  #[serde(default)]
+  pub meta: Option<ScanMeta>,
+}
+
+impl ScanResults {
+  /// Read the results from a report in JSON or YAML
+  /// A file of appended reports gives the last one
+  pub fn parse(report: &str, format: OutputFormat) -> Result<Self, ScannerError> {
+    let error = |message: String| ScannerError::InvalidReport { path: None, message };
+
+    let last = match format {
+      OutputFormat::Json => serde_json::Deserializer::from_str(report).into_iter::<ScanResults>().last().map(|results| results.map_err(|err| err.to_string())),
+      OutputFormat::Yaml => serde_yaml::Deserializer::from_str(report).map(ScanResults::deserialize).last().map(|results| results.map_err(|err| err.to_string())),
+      format => return Err(error(format!("a {:?} report can't be read back, only JSON and YAML ones", format))),
     };
 
-    is_open
+    last.unwrap_or_else(|| Err("empty report".to_string())).map_err(error)
   }
 
+  /// Read the results from a report file, the format being inferred from its extension
+  pub fn read(path: &str) -> Result<Self, ScannerError> {
+    let error = |message: String| ScannerError::InvalidReport { path: Some(path.to_string()), message };
+    let report = std::fs::read_to_string(path).map_err(|err| error(err.to_string()))?;
 
-#[cfg(test)]
-mod tests {
-  use super::*;
+    Self::parse(&report, OutputFormat::from_path(path)).map_err(|err| match err {
+      ScannerError::InvalidReport { message, .. } => error(message),
+      err => err,
+    })
+  }
+}
 
-  fn test_get_one_ip() {
-    let scanner = Scanner::new("192.168.1.1".to_string());
-    let ips = scanner.get_ips();
+/// Scanner holding the results read back, to report them again
+impl From<ScanResults> for Scanner {
+  fn from(results: ScanResults) -> Self {
+    let mut scanner = Scanner::with_prefix(results.ip, results.prefix, results.ports);
+    scanner.range = results.range;
+    scanner.wildcard = results.wildcard;
+    scanner.hostname = results.hostname;
+    scanner.targets = results.targets;
+    scanner.result = results.results;
+    scanner.meta = results.meta;
+    scanner.summary = results.summary;
 
-    assert_eq!(ips.len(), 1);
-    assert_eq!(ips[0], "192.168.1.1".parse::<Ipv4Addr>().unwrap());
+    scanner
   }
+}
 
-  #[test]
-  fn test_get_ips_24() {
-    let scanner = Scanner::new("192.168.1.0".to_string());
-    let ips = scanner.get_ips();
+/// Trait for reporting the result of a scan
+pub trait Report {
+  fn report(&self) -> String;
+}
 
-    assert_eq!(ips.len(), 254);
+/// Result of a scan on a single IP
+/// Return the IP and the open ports
+/// Results are ordered by IP, numerically and IPv4 first, a result of a same IP
+/// being ordered by its other fields
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct IpScanResult {
+  // The IP is the first field so the derived order is by IP first
+  /// IP scanned
+  pub ip: IpAddr,
 
-    for i in 0..=253 {
-      assert_eq!(ips[i], format!("192.168.1.{}", i + 1).parse::<Ipv4Addr>().unwrap());
-    }
-  }
+  /// Name of the host from a reverse DNS lookup, when resolving and the lookup succeeded
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub hostname: Option<String>,
 
-  #[test]
-  fn test_get_ips_8() {
-    let scanner = Scanner::new("192.0.0.0".to_string());
-    let ips = scanner.get_ips();
-    let ips_to_compare = vec![
-      "192.1.0.0",
-      "192.1.1.1",
-      "192.1.1.254",
-      "192.254.254.254",
-      "192.255.255.254",
-      "192.254.128.254",
-    ];
+  /// Whether the host answered the discovery probe, always true when discovery is disabled
+  pub up: bool,
 
+  /// List of open ports, UDP ones included even when only open|filtered
+  #[serde(rename = "openPorts")]
+  pub open_ports: Vec<PortResult>,
 
-    for ip in ips_to_compare {
-      assert_eq!(ips.contains(&ip.parse::<Ipv4Addr>().unwrap()), true, "Should contain [{}]", ip);
+  /// List of TCP ports that didn't answer before the timeout
+  #[serde(rename = "filteredPorts", default, skip_serializing_if = "Vec::is_empty")]
+  pub filtered_ports: Vec<PortResult>,
+
+  /// Whether the host took longer than the host timeout, the scan of its ports being abandoned
+  #[serde(rename = "timedOut", default, skip_serializing_if = "std::ops::Not::not")]
+  pub timed_out: bool,
+
+  /// Ports not probed before the host timed out or the scan reached its maximum of open ports, in the `unknown` state
+  #[serde(rename = "unscannedPorts", default, skip_serializing_if = "Vec::is_empty")]
+  pub unscanned_ports: Vec<PortResult>,
+
+  /// Problems met scanning the host that didn't stop its scan, such as an open HTTP port not answering the request
+  /// or a connection failing for a lack of file descriptors, each one listed once
+  #[serde(default, skip_serializing_if = "Vec::is_empty")]
+  pub errors: Vec<String>,
+}
+
+impl IpScanResult {
+  /// Result of a host up without any port found yet, the fields being set on top of it
+  pub fn new(ip: IpAddr) -> Self {
+    Self {
+      ip,
+      hostname: None,
+      up: true,
+      open_ports: Vec::new(),
+      filtered_ports: Vec::new(),
+      timed_out: false,
+      unscanned_ports: Vec::new(),
+      errors: Vec::new(),
+    }
+  }
+
+  /// IP of the host, followed by its name when known
+  fn label(&self) -> String {
+    match &self.hostname {
+      Some(hostname) => format!("{} ({})", self.ip, hostname),
+      None => self.ip.to_string(),
+    }
+  }
+}
+
+/// The width (`{:20}`) is the one of the IP column, left justified, so the
+/// ports of a list of hosts are aligned
+/// The alternate form (`{:#}`) includes the latency of the ports
+impl Display for IpScanResult {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    self.write_line(f, false)
+  }
+}
+
+impl IpScanResult {
+  /// Line of the text report, with the ports in the color of their state when `color`
+  fn write_line(&self, f: &mut Formatter<'_>, color: bool) -> std::fmt::Result {
+    let column = format!("{}:", self.label());
+    // The colon is part of the column, so the width is the one of the label
+    let width = f.width().map_or(0, |width| width + 1);
+    write!(f, "{:<width$} ", column, width = width)?;
+
+    if !self.up {
+      return writeln!(f, "{}", if self.timed_out { "host timed out" } else { "host down" });
+    }
+
+    // Filtered ports come after the open ones, with their state
+    let formatted_ports = match self.open_ports.is_empty() && self.filtered_ports.is_empty() {
+      true => "no open ports".to_string(),
+      false => self
+        .open_ports
+        .iter()
+        .chain(&self.filtered_ports)
+        .map(|x| {
+          let port = if f.alternate() { format!("{:#}", x) } else { x.to_string() };
+          if color { color::paint(&port, x.state) } else { port }
+        })
+        .collect::<Vec<String>>()
+        .join(", "),
+    };
+
+    match self.timed_out {
+      true => writeln!(f, "{} (timed out, {} ports unscanned)", formatted_ports, self.unscanned_ports.len()),
+      false => writeln!(f, "{}", formatted_ports),
+    }
+  }
+}
+
+/// Result of a host as in the text report printed to a terminal, its ports in the color of their state
+pub struct ColoredResult<'a>(pub &'a IpScanResult);
+
+impl Display for ColoredResult<'_> {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    self.0.write_line(f, true)
+  }
+}
+
+impl Display for Scanner {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    writeln!(f, "Scanner for {}", self.target()).unwrap();
+    writeln!(f, "Ports: {:?}", self.ports).unwrap();
+    Ok(())
+  }
+}
+
+impl Scanner {
+  /// Start configuring a scanner
+  pub fn builder() -> ScannerBuilder {
+    ScannerBuilder::default()
+  }
+
+  /// Create a scanner for an IP, a network in CIDR notation, a range of IPv4 addresses (`192.168.1.50-192.168.1.90`)
+  /// or IPv4 addresses with wildcard octets (`192.168.*.*`), as parsed by [`Target`]
+  /// The default ports are scanned when `ports` is `None`
+  pub fn new(ip: String, ports: Option<Vec<u16>>) -> Result<Self, ScannerError> {
+    Self::from_target(ip.parse()?, PortSpec::list(ports).resolve()?)
+  }
+
+  /// Create a scanner for a target that needs no resolution, scanning `ports` as resolved by [`PortSpec`]
+  /// A host name is an invalid IP here, [`Scanner::lookup`] resolving it
+  pub fn from_target(target: Target, ports: Vec<u16>) -> Result<Self, ScannerError> {
+    match target {
+      Target::Single(ip) => Ok(Self::with_prefix(ip, None, ports)),
+      Target::Cidr(cidr) => Ok(Self::from_cidr(cidr, ports)),
+      Target::Range(range) => Ok(Self::from_range(range, ports)),
+      Target::Wildcard(wildcard) => Ok(Self::from_wildcard(wildcard, ports)),
+      Target::Host(host) => Err(ScannerError::InvalidIp(host)),
+    }
+  }
+
+  /// Create a scanner for a target, the default ports being scanned when `ports` is `None`
+  /// A host name is resolved first and every address it resolves to is scanned
+  pub async fn lookup(target: Target, ports: Option<Vec<u16>>) -> Result<Self, ScannerError> {
+    let ports = PortSpec::list(ports).resolve()?;
+    let Target::Host(host) = target else { return Self::from_target(target, ports) };
+
+    let resolution_error = |message: String| ScannerError::Resolution { host: host.clone(), message };
+
+    let mut addresses: Vec<IpAddr> = Vec::new();
+    for address in tokio::net::lookup_host((host.as_str(), 0)).await.map_err(|err| resolution_error(err.to_string()))? {
+      if !addresses.contains(&address.ip()) {
+        addresses.push(address.ip());
+      }
+    }
+
+    if addresses.is_empty() {
+      return Err(resolution_error("no address found".to_string()));
+    }
+
+    let mut scanner = Self::with_prefix(addresses[0], None, ports);
+    scanner.hostname = Some(host);
+    scanner.addresses = addresses;
+
+    Ok(scanner)
+  }
+
+  /// Create a scanner for several targets
+  /// The hosts of every target are scanned together, as a single target, the host names
+  /// that can't be resolved being left out as long as another target remains
+  pub async fn lookup_all(targets: Vec<Target>, ports: Option<Vec<u16>>) -> Result<Self, ScannerError> {
+    let scanners = Self::lookup_each(targets, false).await?;
+
+    Self::combine(scanners, PortSpec::list(ports).resolve()?)
+  }
+
+  /// Scanner of each target, with the default ports, in the order of the targets
+  /// The host names are resolved concurrently, at most `MAX_RESOLUTIONS` at a time, the ones
+  /// that fail being skipped with a warning unless no target is left, the first failure being returned then
+  async fn lookup_each(targets: Vec<Target>, quiet: bool) -> Result<Vec<Scanner>, ScannerError> {
+    let permits = Arc::new(tokio::sync::Semaphore::new(MAX_RESOLUTIONS));
+    let mut lookups = tokio::task::JoinSet::new();
+    for (index, target) in targets.into_iter().enumerate() {
+      let permits = permits.clone();
+      lookups.spawn(async move {
+        let _permit = permits.acquire_owned().await;
+        (index, Self::lookup(target, None).await)
+      });
+    }
+
+    let mut scanners: Vec<(usize, Scanner)> = Vec::new();
+    let mut unresolved: Vec<(usize, ScannerError)> = Vec::new();
+    while let Some(lookup) = lookups.join_next().await {
+      match lookup.expect("the lookup of a target panicked") {
+        (index, Ok(scanner)) => scanners.push((index, scanner)),
+        (index, Err(err @ ScannerError::Resolution { .. })) => unresolved.push((index, err)),
+        (_, Err(err)) => return Err(err),
+      }
+    }
+    scanners.sort_by_key(|(index, _)| *index);
+    unresolved.sort_by_key(|(index, _)| *index);
+
+    if scanners.is_empty() {
+      if let Some((_, err)) = unresolved.into_iter().next() {
+        return Err(err);
+      }
+    } else if !quiet {
+      for (_, err) in &unresolved {
+        warn!("{}, not scanning it", err);
+      }
+    }
+
+    Ok(scanners.into_iter().map(|(_, scanner)| scanner).collect())
+  }
+
+  /// Scanner for the hosts of several scanners, a single one being kept as is
+  fn combine(mut scanners: Vec<Scanner>, ports: Vec<u16>) -> Result<Self, ScannerError> {
+    if scanners.len() <= 1 {
+      let mut scanner = scanners.pop().ok_or(ScannerError::MissingTarget)?;
+      scanner.ports = ports;
+      return Ok(scanner);
+    }
+
+    let mut scanner = Self::with_prefix(scanners[0].ip, None, ports);
+    scanner.targets = scanners.iter().map(|target| target.target()).collect();
+    scanner.combined = scanners.iter().map(|target| target.target_hosts()).collect();
+
+    Ok(scanner)
+  }
+
+  /// Remove the ports not in `allowed`, if given, and the `denied` ones, returning them
+  fn remove_ports(&mut self, allowed: Option<&[u16]>, denied: &[u16]) -> Vec<u16> {
+    let (ports, removed) = port::filter_ports(std::mem::take(&mut self.ports), allowed, denied);
+    self.ports = ports;
+    self.options.port_protocols.retain(|port, _| !removed.contains(port));
+
+    removed
+  }
+
+  /// Create a scanner for a network given in CIDR notation, scanning `ports` as resolved by [`PortSpec`]
+  pub fn from_cidr(cidr: Cidr, ports: Vec<u16>) -> Self {
+    Self::with_prefix(cidr.network(), Some(cidr.prefix), ports)
+  }
+
+  /// Create a scanner for an inclusive range of addresses, scanning `ports` as resolved by [`PortSpec`]
+  pub fn from_range(range: IpRange, ports: Vec<u16>) -> Self {
+    let mut scanner = Self::with_prefix(IpAddr::V4(range.start), None, ports);
+    scanner.range = Some(range);
+
+    scanner
+  }
+
+  /// Create a scanner for IPv4 addresses with wildcard octets, scanning `ports` as resolved by [`PortSpec`]
+  pub fn from_wildcard(wildcard: IpWildcard, ports: Vec<u16>) -> Self {
+    let mut scanner = Self::with_prefix(IpAddr::V4(wildcard.bounds().0), None, ports);
+    scanner.wildcard = Some(wildcard);
+
+    scanner
+  }
+
+  fn with_prefix(ip: IpAddr, prefix: Option<u8>, ports: Vec<u16>) -> Self {
+    Self {
+      ip,
+      prefix,
+      range: None,
+      wildcard: None,
+      hostname: None,
+      targets: Vec::new(),
+      addresses: Vec::new(),
+      combined: Vec::new(),
+      ports,
+      options: ScanOptions::default(),
+      show_all: false,
+      include_down: false,
+      show_latency: false,
+      group_by: GroupBy::Host,
+      quiet: false,
+      resolver: Arc::new(Resolver::default()),
+      result: Vec::new(),
+      meta: None,
+      summary: None,
+      connection_errors: ConnectionErrors::default(),
+      adapted_timeout: None,
+      resumed: Vec::new(),
+    }
+  }
+
+  /// Ports scanned on each host, in ascending order as resolved by [`PortSpec::resolve`]
+  pub fn ports(&self) -> &[u16] {
+    &self.ports
+  }
+
+  /// Every port probed on each host with its protocol, the ports given with their own
+  /// protocols being probed with them and the others with `protocols`
+  pub fn probes(&self) -> Vec<(Protocol, u16)> {
+    port::probes(&self.ports, &self.options.protocols, &self.options.port_protocols)
+  }
+
+  /// Results of the last scan, sorted by IP
+  pub fn results(&self) -> &[IpScanResult] {
+    &self.result
+  }
+
+  /// Take the results of the last scan, sorted by IP
+  pub fn into_results(self) -> Vec<IpScanResult> {
+    self.result
+  }
+
+  /// Target as given to the scanner, with its prefix if any
+  pub fn target(&self) -> String {
+    if !self.targets.is_empty() {
+      return self.targets.join(", ");
+    }
+
+    if let Some(hostname) = &self.hostname {
+      return hostname.clone();
+    }
+
+    if let Some(range) = &self.range {
+      return range.to_string();
+    }
+
+    if let Some(wildcard) = &self.wildcard {
+      return wildcard.to_string();
+    }
+
+    match self.prefix {
+      Some(prefix) => format!("{}/{}", self.ip, prefix),
+      None => self.ip.to_string(),
+    }
+  }
+
+  /// Resume a previous run, the hosts of `results` not being scanned again
+  /// Their results are merged with the ones of the next scan
+  pub fn resume(&mut self, results: Vec<IpScanResult>) {
+    self.resumed = results;
+  }
+
+  /// Scan every IP and store the results, sorted by IP
+  /// Progress is written to stderr unless the scanner is quiet
+  pub async fn scan(&mut self) {
+    self.scan_with(|_| {}).await;
+  }
+
+  /// Scan every IP like `scan`, calling `on_result` with the result of each host
+  /// as soon as it is done, in completion order, whether it has open ports or not
+  pub async fn scan_with<C: FnMut(&IpScanResult)>(&mut self, on_result: C) {
+    self.scan_until_each(std::future::pending::<()>(), on_result).await;
+  }
+
+  /// Scan every IP until `stop` completes, storing the results of the hosts done
+  ///
+  /// Once stopped no new host is scanned, the ones in progress having
+  /// `INTERRUPT_GRACE` to finish. Return whether the scan was interrupted,
+  /// which is also recorded in its summary.
+  pub async fn scan_until<F: Future>(&mut self, stop: F) -> bool {
+    self.scan_until_each(stop, |_| {}).await
+  }
+
+  /// Scan every IP until `stop` completes like `scan_until`, calling `on_result`
+  /// with the result of each host as soon as it is done, in completion order
+  pub async fn scan_until_each<F, C>(&mut self, stop: F, on_result: C) -> bool
+  where
+    F: Future,
+    C: FnMut(&IpScanResult),
+  {
+    self.scan_cancellable(stop, on_result, CancelToken::new()).await
+  }
+
+  /// Scan every IP until `cancel` is cancelled, storing the results of the hosts done
+  ///
+  /// Unlike an interruption, the probes in flight are dropped too, their ports
+  /// being unscanned, so the scan returns as soon as they are. Return whether
+  /// the scan was cancelled, recorded in its summary as interrupted.
+  pub async fn scan_with_cancel(&mut self, cancel: CancelToken) -> bool {
+    let stop = cancel.clone();
+    self.scan_cancellable(async move { stop.cancelled().await }, |_| {}, cancel).await
+  }
+
+  /// Check a single TCP port of an IP with one connection, without any discovery, retry or probe of its service
+  ///
+  /// A refused connection or no answer before `timeout` means the port isn't
+  /// open. Any other failure, an unreachable network or no file descriptor
+  /// left for instance, tells nothing about the port and is an error.
+  ///
+  /// ```no_run
+  /// # async fn check() -> Result<(), skanner::error::ScannerError> {
+  /// use std::time::Duration;
+  /// use skanner::Scanner;
+  ///
+  /// let open = Scanner::is_port_open("192.168.1.10".parse().unwrap(), 443, Duration::from_secs(1)).await?;
+  /// # Ok(())
+  /// # }
+  /// ```
+  pub async fn is_port_open(ip: IpAddr, port: u16, timeout: Duration) -> Result<bool, ScannerError> {
+    let address = SocketAddr::new(ip, port);
+
+    match connect(address, timeout, None, &Source::default()).await {
+      Ok(_) => Ok(true),
+      Err(err) if matches!(err.kind(), ErrorKind::ConnectionRefused | ErrorKind::TimedOut) => Ok(false),
+      Err(err) => Err(ScannerError::Connection { address, message: err.to_string() }),
+    }
+  }
+
+  /// Scan every IP until `stop` completes like `scan_until_each`, the probes in flight being dropped once `cancel` is cancelled
+  pub(crate) async fn scan_cancellable<F, C>(&mut self, stop: F, mut on_result: C, cancel: CancelToken) -> bool
+  where
+    F: Future,
+    C: FnMut(&IpScanResult),
+  {
+    self.ensure_seed();
+
+    let start = Instant::now();
+    self.meta = Some(ScanMeta::new(self, SystemTime::now()));
+    let (total, excluded) = self.count_ips();
+
+    if !self.quiet {
+      if excluded > 0 {
+        info!("Excluding {} IPs", excluded);
+      }
+      if !self.resumed.is_empty() {
+        info!("Resuming, {} hosts already scanned", self.resumed.len());
+      }
+      info!("Scanning {} IPs for {} ports", total, self.ports.len());
+    }
+    // The progress line would be broken by the messages of each host
+    let progress = !self.quiet && !logging::enabled(Level::Debug);
+
+    let (ips, ports) = self.ordered();
+    let options = Arc::new(HostOptions { cancel, ..self.host_options(ports) });
+    let errors = options.errors.clone();
+    let connect_timeout = options.connect_timeout.clone();
+    let limit = options.open_limit.clone();
+    let scan_deadline = options.scan_deadline;
+    // Checked before queueing each host, the hosts already queued being scanned anyway
+    // unless the open ports limit or the deadline is reached, their probes not started then being dropped
+    let stopped = Arc::new(AtomicBool::new(false));
+    let queued = stopped.clone();
+    let queued_options = options.clone();
+    let ips = ips.take_while(move |_| !queued.load(Ordering::SeqCst) && !queued_options.limit_reached() && !queued_options.deadline_reached() && !queued_options.cancel.is_cancelled());
+    let mut receiver = scan_hosts(ips, options, self.options.concurrency);
+    let mut results: Vec<IpScanResult> = Vec::new();
+
+    tokio::pin!(stop);
+    let mut interrupted = false;
+    let deadline = tokio::time::sleep(Duration::MAX);
+    tokio::pin!(deadline);
+    let mut deadline_reached = false;
+    let max_duration = tokio::time::sleep_until(scan_deadline.unwrap_or_else(|| tokio::time::Instant::now() + Duration::from_secs(u32::MAX.into())));
+    tokio::pin!(max_duration);
+
+    loop {
+      let result = tokio::select! {
+        result = receiver.recv() => result,
+        _ = &mut stop, if !interrupted => {
+          interrupted = true;
+          // No new host is queued, the probes of the ones in progress are still done
+          stopped.store(true, Ordering::SeqCst);
+          deadline.as_mut().reset(tokio::time::Instant::now() + INTERRUPT_GRACE);
+
+          if progress {
+            eprintln!();
+          }
+          warn!("Interrupted, waiting {}s for the hosts in progress", INTERRUPT_GRACE.as_secs());
+          continue;
+        }
+        _ = &mut max_duration, if scan_deadline.is_some() && !deadline_reached && !interrupted => {
+          deadline_reached = true;
+          // The probes not started are dropped, the ones in progress get the same grace as an interruption
+          deadline.as_mut().reset(tokio::time::Instant::now() + INTERRUPT_GRACE);
+
+          if progress {
+            eprintln!();
+          }
+          warn!("Maximum duration reached, waiting {}s for the probes in progress", INTERRUPT_GRACE.as_secs());
+          continue;
+        }
+        _ = &mut deadline => break,
+      };
+
+      let Some(result) = result else { break };
+      on_result(&result);
+      results.push(result);
+
+      if progress {
+        eprint!("\r{}/{} hosts scanned", results.len(), total);
+      }
+    }
+
+    if progress {
+      eprintln!();
+    }
+    if interrupted {
+      warn!("Scan interrupted after {} of {} hosts", results.len(), total);
+    } else if deadline_reached {
+      warn!("Scan stopped at its maximum duration after {} of {} hosts", results.len(), total);
+    }
+
+    results.extend(self.resumed.iter().cloned());
+    self.result = merge_results(results);
+    self.connection_errors = errors.counts();
+    self.adapted_timeout = connect_timeout.adapted();
+    let max_open_reached = limit.is_some_and(|limit| limit.reached());
+    self.summary = Some(ScanSummary { interrupted, max_open_reached, deadline_reached, ..ScanSummary::new(&self.result, start.elapsed()) });
+
+    interrupted
+  }
+
+  /// Counts of the last scan, `None` before the first one
+  pub fn summary(&self) -> Option<&ScanSummary> {
+    self.summary.as_ref()
+  }
+
+  /// How the last scan was run, `None` before the first one
+  pub fn meta(&self) -> Option<&ScanMeta> {
+    self.meta.as_ref()
+  }
+
+  /// Number of failed connections of the last scan per reason, empty before the first one
+  pub fn connection_errors(&self) -> &ConnectionErrors {
+    &self.connection_errors
+  }
+
+  /// Timeout the last scan adapted to the round trip times, `None` when fixed
+  pub fn adapted_timeout(&self) -> Option<Duration> {
+    self.adapted_timeout
+  }
+
+  /// Scan every IP in the background and send each result as soon as its host is done
+  /// Results come in completion order and are not stored on the scanner
+  /// Must be called from within a tokio runtime
+  pub fn scan_stream(&self) -> Receiver<IpScanResult> {
+    let (ips, ports) = self.ordered();
+
+    scan_hosts(ips, Arc::new(self.host_options(ports)), self.options.concurrency)
+  }
+
+  /// Write the IPs and ports a scan would probe, in the order it would, without connecting
+  ///
+  /// The settings of the scan come first as `#` comments, followed by one IP
+  /// per line, every IP being probed on the ports in the order listed. With a
+  /// seed the plan is the same on every run, so two plans can be diffed.
+  /// Return the number of IPs written.
+  pub fn write_plan<W: io::Write>(&mut self, out: &mut W) -> io::Result<usize> {
+    self.ensure_seed();
+    let (total, excluded) = self.count_ips();
+
+    writeln!(out, "# Target: {}", self.target())?;
+    writeln!(out, "# IPs: {}", total)?;
+    if excluded > 0 {
+      writeln!(out, "# Excluded: {}", excluded)?;
+    }
+    if let Some(seed) = self.options.seed {
+      writeln!(out, "# Seed: {}", seed)?;
+    }
+    let (ips, ports) = self.ordered();
+    let probes = port::probes(&ports, &self.options.protocols, &self.options.port_protocols);
+
+    let mut protocols = probes.iter().map(|(protocol, _)| *protocol).collect::<Vec<Protocol>>();
+    protocols.dedup();
+    let protocols = protocols.iter().map(Protocol::to_string).collect::<Vec<String>>().join(",");
+    writeln!(out, "# Protocols: {}", protocols)?;
+
+    // The ports given with their protocol are listed with it, in the order they are probed
+    let ports = match self.options.port_protocols.is_empty() {
+      true => ports.iter().map(u16::to_string).collect::<Vec<String>>(),
+      false => probes.iter().map(|(protocol, port)| format!("{}/{}", port, protocol)).collect::<Vec<String>>(),
+    };
+    writeln!(out, "# Ports: {}", ports.join(","))?;
+
+    let mut count = 0;
+    for ip in ips {
+      writeln!(out, "{}", ip)?;
+      count += 1;
+    }
+
+    Ok(count)
+  }
+
+  /// Pick a seed when randomizing without one
+  /// Keeping the seed used lets the scan order be reproduced from the report
+  fn ensure_seed(&mut self) {
+    if (self.options.randomize || self.shuffles_ports()) && self.options.seed.is_none() {
+      self.options.seed = Some(rand::random());
+    }
+  }
+
+  /// Whether the ports of each host are probed in a random order
+  fn shuffles_ports(&self) -> bool {
+    self.options.randomize_ports || self.options.port_order == Some(PortOrder::Random)
+  }
+
+  /// Order of the IPs and ports to scan, shuffled when randomizing
+  /// The same seed always gives the same order
+  /// The IPs are only listed up front to be shuffled, otherwise they are enumerated as scanned
+  fn ordered(&self) -> (Box<dyn Iterator<Item = IpAddr> + Send>, Vec<u16>) {
+    let mut ports = self.ports.clone();
+    if let Some(order) = self.options.port_order {
+      order.sort(&mut ports);
+    }
+
+    if !self.options.randomize && !self.shuffles_ports() {
+      return (Box::new(self.ips()), ports);
+    }
+
+    let mut rng = match self.options.seed {
+      Some(seed) => ChaCha8Rng::seed_from_u64(seed),
+      None => ChaCha8Rng::from_entropy(),
+    };
+
+    let ips: Box<dyn Iterator<Item = IpAddr> + Send> = if self.options.randomize {
+      let mut ips = self.get_ips();
+      ips.shuffle(&mut rng);
+      Box::new(ips.into_iter())
+    } else {
+      Box::new(self.ips())
+    };
+    if self.shuffles_ports() {
+      ports.shuffle(&mut rng);
+    }
+
+    (ips, ports)
+  }
+
+  fn host_options(&self, ports: Vec<u16>) -> HostOptions {
+    HostOptions {
+      ports,
+      protocols: self.options.protocols.clone(),
+      port_protocols: self.options.port_protocols.clone(),
+      timeout: self.options.timeout,
+      connect_timeout: Arc::new(match self.options.adaptive_timeout {
+        Some(min_timeout) => ConnectTimeout::adaptive(min_timeout, self.options.timeout),
+        None => ConnectTimeout::fixed(self.options.timeout),
+      }),
+      read_timeout: self.options.read_timeout,
+      retries: self.options.retries,
+      confirm: self.options.confirm,
+      rate_limiter: self.options.rate.map(|rate| Arc::new(RateLimiter::new(rate))),
+      port_limiter: self.options.pps.map(|pps| Arc::new(RateLimiter::new(pps))),
+      host_timeout: self.options.host_timeout,
+      open_limit: self.options.max_open.map(|max| Arc::new(OpenLimit::new(max))),
+      scan_deadline: self.options.max_duration.map(|max_duration| tokio::time::Instant::now() + max_duration),
+      syn: match self.options.scan_type {
+        ScanType::Syn => self.syn_scanner(),
+        ScanType::Connect => None,
+      },
+      arp: match self.options.discovery_method {
+        DiscoveryMethod::Arp if self.options.discovery => self.arp_table(),
+        _ => None,
+      },
+      discovery: self.options.discovery,
+      banner: self.options.banner,
+      tls: self.options.tls,
+      http_ports: if self.options.http { self.options.http_ports.clone() } else { Vec::new() },
+      proxy: self.options.proxy.clone(),
+      source: self.options.source.clone(),
+      server_name: self.options.sni.clone().or_else(|| self.hostname.clone()),
+      resolver: self.options.resolve.then(|| self.resolver.clone()),
+      errors: Arc::new(ErrorCounter::default()),
+      connections: Arc::new(tokio::sync::Semaphore::new(self.options.concurrency.max(1))),
+      cancel: CancelToken::new(),
+    }
+  }
+
+  /// Raw socket of a SYN scan, `None` when it can't be used, the scan then connecting to the ports
+  fn syn_scanner(&self) -> Option<Arc<SynScanner>> {
+    if self.options.proxy.is_some() {
+      warn!("A SYN scan can't go through the proxy, connecting to the ports instead");
+      return None;
+    }
+
+    match SynScanner::open(&self.options.source) {
+      Ok(syn) => Some(Arc::new(syn)),
+      Err(err) if err.kind() == ErrorKind::PermissionDenied => {
+        warn!("A SYN scan needs root or the CAP_NET_RAW capability, connecting to the ports instead");
+        None
+      }
+      Err(err) => {
+        warn!("Unable to open a raw socket for the SYN scan ({}), connecting to the ports instead", err);
+        None
+      }
+    }
+  }
+
+  /// ARP table of an ARP discovery, `None` when it can't be read, the hosts then being discovered with TCP
+  fn arp_table(&self) -> Option<Arc<ArpTable>> {
+    if self.options.proxy.is_some() {
+      warn!("The hosts behind the proxy can't be discovered with ARP, using TCP instead");
+      return None;
+    }
+
+    match ArpTable::open(&self.options.source) {
+      Ok(arp) => Some(Arc::new(arp)),
+      Err(err) => {
+        warn!("Unable to read the ARP table ({}), discovering the hosts with TCP instead", err);
+        None
+      }
+    }
+  }
+
+  /// List the IPs to scan, without the excluded ones
+  /// An address without a prefix or a range is a single host, whatever its octets
+  pub fn get_ips(&self) -> Vec<IpAddr> {
+    self.ips().collect()
+  }
+
+  /// IPs to scan, without the excluded ones and the ones resumed, enumerated one
+  /// at a time so even the largest networks don't have to be listed in memory
+  pub fn ips(&self) -> impl Iterator<Item = IpAddr> + Send + 'static {
+    let exclude = self.options.exclude.clone();
+    let resumed = self.resumed.iter().map(|result| result.ip).collect::<HashSet<IpAddr>>();
+
+    self.all_ips().filter(move |ip| !exclude.iter().any(|network| network.contains(ip)) && !resumed.contains(ip))
+  }
+
+  /// Special purpose ranges the scan would reach, loopback included, the excluded ones ignored
+  /// Checked on the bounds of each target so a large network isn't listed
+  pub fn reserved_ranges(&self) -> Vec<(Cidr, Reserved)> {
+    let mut found: Vec<(Cidr, Reserved)> = Vec::new();
+
+    for (first, last) in self.bounds() {
+      for (network, kind) in reserved::overlapping(&first, &last) {
+        let excluded = self.options.exclude.iter().any(|exclude| exclude.contains(&network.network()) && exclude.contains(&network.broadcast()));
+        if !excluded && !found.contains(&(network, kind)) {
+          found.push((network, kind));
+        }
+      }
+    }
+
+    found
+  }
+
+  /// Whether every address scanned is a loopback one, the scan not leaving this machine
+  pub fn is_loopback(&self) -> bool {
+    self.bounds().iter().all(|(first, last)| first.is_loopback() && last.is_loopback())
+  }
+
+  /// First and last IPs of each range of addresses scanned
+  fn bounds(&self) -> Vec<(IpAddr, IpAddr)> {
+    match self.combined.is_empty() {
+      true => self.target_hosts().bounds(),
+      false => self.combined.iter().flat_map(TargetHosts::bounds).collect(),
+    }
+  }
+
+  /// Hosts of the target, the first one when several were given
+  fn target_hosts(&self) -> TargetHosts {
+    if !self.addresses.is_empty() {
+      return TargetHosts::Addresses(self.addresses.clone());
+    }
+
+    if let Some(range) = self.range {
+      return TargetHosts::Range(range);
+    }
+
+    if let Some(wildcard) = self.wildcard {
+      return TargetHosts::Wildcard(wildcard);
+    }
+
+    if let Some(prefix) = self.prefix {
+      return TargetHosts::Network(Cidr { ip: self.ip, prefix });
+    }
+
+    TargetHosts::Single(self.ip)
+  }
+
+  /// Number of IPs to scan and number of IPs excluded, counted without listing them
+  fn count_ips(&self) -> (usize, usize) {
+    let total = self.all_ips().count();
+    if self.options.exclude.is_empty() && self.resumed.is_empty() {
+      return (total, 0);
+    }
+
+    // The resumed hosts are not excluded
+    let count = self.ips().count();
+    (count, (total - count).saturating_sub(self.resumed.len()))
+  }
+
+  fn all_ips(&self) -> Box<dyn Iterator<Item = IpAddr> + Send> {
+    match self.combined.is_empty() {
+      true => self.target_hosts().iter(),
+      false => combined_ips(self.combined.clone()),
+    }
+  }
+
+  /// Serialize the scanner and its results in the given format
+  pub fn serialize(&self, format: OutputFormat) -> Result<String, ScannerError> {
+    match format {
+      OutputFormat::Yaml => serde_yaml::to_string(&SerializedScanner::new(self)).map_err(|err| ScannerError::Serialization(err.to_string())),
+      OutputFormat::Json => serde_json::to_string_pretty(&SerializedScanner::new(self)).map_err(|err| ScannerError::Serialization(err.to_string())),
+      OutputFormat::Text => Ok(self.report()),
+      OutputFormat::Csv => Ok(crate::output::to_csv(&self.result)),
+      OutputFormat::Markdown => Ok(MarkdownReport(self).report()),
+      OutputFormat::NmapXml => Ok(crate::nmap::to_xml(self, SystemTime::now())),
+      OutputFormat::Table => Ok(TableReport(self).report()),
+      OutputFormat::Jsonl if self.include_down => crate::output::to_jsonl(&self.result),
+      OutputFormat::Jsonl => crate::output::to_jsonl(&self.result.iter().filter(|result| crate::output::is_reported(result)).cloned().collect::<Vec<IpScanResult>>()),
+    }
+  }
+
+  /// Serialize the scanner as an entry appended to a report file, stamped with the time of the scan
+  ///
+  /// JSON entries are a single line with a `timestamp` field, so the file is
+  /// valid newline-delimited JSON. YAML entries are separate documents and text
+  /// and table entries start with the timestamp. CSV rows have no timestamp, the header
+  /// only being written when `header` is set, for the first entry of a file.
+  /// Nmap XML entries are whole documents, stamped with their start time.
+  pub fn serialize_entry(&self, format: OutputFormat, timestamp: &str, header: bool) -> Result<String, ScannerError> {
+    match format {
+      OutputFormat::Json => {
+        let mut entry = serde_json::to_value(SerializedScanner::new(self)).map_err(|err| ScannerError::Serialization(err.to_string()))?;
+        if let Some(entry) = entry.as_object_mut() {
+          entry.insert("timestamp".to_string(), timestamp.into());
+        }
+        Ok(format!("{}\n", entry))
+      }
+      OutputFormat::Yaml => Ok(format!("--- # {}\n{}", timestamp, self.serialize(format)?)),
+      OutputFormat::Text => Ok(format!("# {}\n{}\n", timestamp, self.report())),
+      OutputFormat::Table => Ok(format!("# {}\n{}\n", timestamp, self.serialize(format)?)),
+      OutputFormat::Markdown => Ok(format!("<!-- {} -->\n{}\n", timestamp, self.serialize(format)?)),
+      OutputFormat::Csv if header => self.serialize(format),
+      OutputFormat::Csv => Ok(self.serialize(format)?.split_once('\n').map(|(_, rows)| rows.to_string()).unwrap_or_default()),
+      OutputFormat::Jsonl | OutputFormat::NmapXml => self.serialize(format),
+    }
+  }
+}
+
+impl Report for Scanner {
+  fn report(&self) -> String {
+    self.text_report(false)
+  }
+}
+
+impl Scanner {
+  /// Text report of the scan, the port states being colored when `color`, for a terminal
+  /// Grouped by port, the report lists open ports only and is never colored
+  pub fn text_report(&self, color: bool) -> String {
+    if self.group_by == GroupBy::Port {
+      return PortReport(self).report();
+    }
+
+    let mut report = self.meta.as_ref().map(ScanMeta::to_string).unwrap_or_default();
+
+    report.push_str(&format!("Scanner for {}\n", self.target()));
+    report.push_str(&format!("Ports: {:?}\n", self.ports));
+    report.push_str("=========================\n");
+
+    let results = self.result.iter().filter(|result| self.show_all || !result.open_ports.is_empty()).collect::<Vec<&IpScanResult>>();
+    let width = results.iter().map(|result| result.label().len()).max().unwrap_or(0);
+
+    for result in results {
+      // Filtered ports are only listed with the hosts without open ports
+      let result = if self.show_all {
+        Cow::Borrowed(result)
+      } else {
+        Cow::Owned(IpScanResult { filtered_ports: Vec::new(), ..result.clone() })
+      };
+
+      let colored = ColoredResult(&result);
+      let line: &dyn Display = if color { &colored } else { &*result };
+      if self.show_latency {
+        report.push_str(&format!("{:#width$}", line, width = width));
+      } else {
+        report.push_str(&format!("{:width$}", line, width = width));
+      }
+    }
+
+    if let Some(summary) = &self.summary {
+      report.push_str("=========================\n");
+      report.push_str(&format!("{}\n", summary));
+    }
+    if !self.connection_errors.is_empty() {
+      report.push_str(&format!("Connection errors: {}\n", self.connection_errors));
+    }
+
+    report
+  }
+}
+
+/// Sort results by IP, IPv4 first, merging the results of a same IP
+/// A merged host is up if any of its results is, with the open ports of every result,
+/// and timed out if any of them did
+fn merge_results(mut results: Vec<IpScanResult>) -> Vec<IpScanResult> {
+  results.sort_by_key(|result| result.ip);
+
+  let mut merged: Vec<IpScanResult> = Vec::with_capacity(results.len());
+  for result in results {
+    match merged.last_mut() {
+      Some(last) if last.ip == result.ip => {
+        last.up |= result.up;
+        last.hostname = last.hostname.take().or(result.hostname);
+
+        last.open_ports = union_ports(std::mem::take(&mut last.open_ports), result.open_ports);
+
+        // A port open in any of the results is open
+        let filtered_ports = union_ports(std::mem::take(&mut last.filtered_ports), result.filtered_ports);
+        last.filtered_ports = filtered_ports.into_iter().filter(|port| !has_port(&last.open_ports, port)).collect();
+
+        // A port open or filtered in any of the results was scanned
+        last.timed_out |= result.timed_out;
+        let unscanned_ports = union_ports(std::mem::take(&mut last.unscanned_ports), result.unscanned_ports);
+        last.unscanned_ports = unscanned_ports.into_iter().filter(|port| !has_port(&last.open_ports, port) && !has_port(&last.filtered_ports, port)).collect();
+
+        for error in result.errors {
+          if !last.errors.contains(&error) {
+            last.errors.push(error);
+          }
+        }
+      }
+      _ => merged.push(result),
+    }
+  }
+
+  merged
+}
+
+/// Ports of both lists, sorted by protocol and port, a port of both being kept once
+fn union_ports(mut ports: Vec<PortResult>, other: Vec<PortResult>) -> Vec<PortResult> {
+  for port in other {
+    if !has_port(&ports, &port) {
+      ports.push(port);
+    }
+  }
+  ports.sort_by_key(|port| (port.protocol, port.port));
+
+  ports
+}
+
+/// Whether the same port and protocol as `port` is in the list
+fn has_port(ports: &[PortResult], port: &PortResult) -> bool {
+  ports.iter().any(|other| other.protocol == port.protocol && other.port == port.port)
+}
+
+/// Run `task` on each item with a fixed pool of `workers` tasks, all pulling from a single queue
+/// Items are only taken from the iterator as the queue has room for them
+/// The values returned by the tasks are sent on the returned channel in completion order, `None` ones being skipped
+fn work_queue<L, I, T, F, Fut>(items: L, workers: usize, task: F) -> Receiver<T>
+where
+  L: IntoIterator<Item = I> + Send + 'static,
+  L::IntoIter: Send,
+  I: Send + 'static,
+  T: Send + 'static,
+  F: Fn(I) -> Fut + Clone + Send + 'static,
+  Fut: Future<Output = Option<T>> + Send + 'static,
+{
+  let workers = workers.max(1);
+  let (queue, pending) = mpsc::channel::<I>(workers);
+  let (sender, receiver) = mpsc::channel(workers);
+
+  tokio::spawn(async move {
+    for item in items {
+      // Once every worker stopped nobody takes the items anymore
+      if queue.send(item).await.is_err() {
+        break;
+      }
+    }
+  });
+
+  let pending = Arc::new(tokio::sync::Mutex::new(pending));
+  for _ in 0..workers {
+    let pending = pending.clone();
+    let sender = sender.clone();
+    let task = task.clone();
+
+    tokio::spawn(async move {
+      loop {
+        // The lock is only held while waiting for the next item, not while running it
+        let item = pending.lock().await.recv().await;
+        let Some(item) = item else { break };
+
+        // A task panicking doesn't take its worker down with it
+        let result = match tokio::spawn(task(item)).await {
+          Ok(result) => result,
+          Err(err) => {
+            error!("task failed: {}", err);
+            None
+          }
+        };
+        // The receiver was dropped, there is nobody left to report to
+        if let Some(result) = result {
+          if sender.send(result).await.is_err() {
+            break;
+          }
+        }
+      }
+    });
+  }
+
+  receiver
+}
+
+/// Hosts of a target, yielded one at a time when scanning
+#[derive(Debug, Clone)]
+enum TargetHosts {
+  Single(IpAddr),
+  Network(Cidr),
+  Range(IpRange),
+  Wildcard(IpWildcard),
+
+  /// Every address a host name resolved to
+  Addresses(Vec<IpAddr>),
+}
+
+impl TargetHosts {
+  fn iter(&self) -> Box<dyn Iterator<Item = IpAddr> + Send> {
+    match self {
+      TargetHosts::Single(ip) => Box::new(std::iter::once(*ip)),
+      TargetHosts::Network(cidr) => Box::new(cidr.iter_hosts()),
+      TargetHosts::Range(range) => Box::new(range.iter()),
+      TargetHosts::Wildcard(wildcard) => Box::new(wildcard.iter()),
+      TargetHosts::Addresses(addresses) => Box::new(addresses.clone().into_iter()),
+    }
+  }
+
+  /// First and last IPs of each range of addresses
+  fn bounds(&self) -> Vec<(IpAddr, IpAddr)> {
+    match self {
+      TargetHosts::Single(ip) => vec![(*ip, *ip)],
+      TargetHosts::Network(cidr) => vec![(cidr.network(), cidr.broadcast())],
+      TargetHosts::Range(range) => vec![(IpAddr::V4(range.start), IpAddr::V4(range.end))],
+      TargetHosts::Wildcard(wildcard) => {
+        let (first, last) = wildcard.bounds();
+        vec![(IpAddr::V4(first), IpAddr::V4(last))]
+      }
+      TargetHosts::Addresses(addresses) => addresses.iter().map(|ip| (*ip, *ip)).collect(),
+    }
+  }
+
+  /// Whether an IP is one of the hosts yielded
+  fn contains(&self, ip: &IpAddr) -> bool {
+    match self {
+      TargetHosts::Single(single) => single == ip,
+      TargetHosts::Network(cidr) => cidr.contains_host(ip),
+      TargetHosts::Range(range) => range.contains(ip),
+      TargetHosts::Wildcard(wildcard) => wildcard.contains(ip),
+      TargetHosts::Addresses(addresses) => addresses.contains(ip),
+    }
+  }
+
+  /// Whether some addresses between the bounds of this target are between the ones of `other`
+  fn overlaps(&self, other: &TargetHosts) -> bool {
+    let bounds = other.bounds();
+    self.bounds().iter().any(|(first, last)| bounds.iter().any(|(start, end)| first <= end && start <= last))
+  }
+}
+
+/// Hosts of several targets, one target after the other, a host of overlapping targets
+/// being yielded once, by the first target having it, without listing them up front
+/// Only the earlier targets overlapping a target are checked, the single addresses in a set
+fn combined_ips(targets: Vec<TargetHosts>) -> Box<dyn Iterator<Item = IpAddr> + Send> {
+  Box::new((0..targets.len()).flat_map(move |index| {
+    let (singles, others): (Vec<&TargetHosts>, Vec<&TargetHosts>) =
+      targets[..index].iter().filter(|earlier| earlier.overlaps(&targets[index])).partition(|earlier| matches!(earlier, TargetHosts::Single(_) | TargetHosts::Addresses(_)));
+    let seen = singles.into_iter().flat_map(TargetHosts::iter).collect::<HashSet<IpAddr>>();
+    let others = others.into_iter().cloned().collect::<Vec<TargetHosts>>();
+
+    targets[index].iter().filter(move |ip| !seen.contains(ip) && !others.iter().any(|earlier| earlier.contains(ip)))
+  }))
+}
+
+/// Settings shared by the scans of every host
+#[derive(Debug, Clone)]
+struct HostOptions {
+  ports: Vec<u16>,
+  protocols: Vec<Protocol>,
+  port_protocols: BTreeMap<u16, Vec<Protocol>>,
+  timeout: Duration,
+
+  /// Time to wait for the TCP connection to a port, adapted to the round trip
+  /// times of every host when adaptive
+  connect_timeout: Arc<ConnectTimeout>,
+
+  /// Time to wait for a banner or an HTTP response, apart from the connection
+  read_timeout: Duration,
+
+  retries: u32,
+  confirm: bool,
+  rate_limiter: Option<Arc<RateLimiter>>,
+
+  /// Limiter of the ports probed per second, shared by every host, the retries not counting
+  port_limiter: Option<Arc<RateLimiter>>,
+
+  host_timeout: Option<Duration>,
+
+  /// Open ports the scan stops at, shared by every host
+  open_limit: Option<Arc<OpenLimit>>,
+
+  /// Moment the scan stops probing, set from its maximum duration
+  scan_deadline: Option<tokio::time::Instant>,
+
+  /// Raw socket of a SYN scan, the ports being connected to without it
+  syn: Option<Arc<SynScanner>>,
+
+  /// ARP table of an ARP discovery, the hosts being discovered with TCP without it
+  arp: Option<Arc<ArpTable>>,
+
+  discovery: bool,
+  banner: bool,
+  tls: bool,
+
+  /// Ports probed with an HTTP request, none when HTTP probing is disabled
+  http_ports: Vec<u16>,
+
+  /// SOCKS5 proxy of the TCP connections
+  proxy: Option<Proxy>,
+
+  /// Local end of the connections
+  source: Source,
+
+  /// Name sent in the TLS handshake and the HTTP requests, the one of `--sni` or the host name scanned
+  server_name: Option<String>,
+
+  /// Resolver of the hosts with open ports, when resolving
+  resolver: Option<Arc<Resolver>>,
+
+  /// Failed connections to the ports of every host
+  errors: Arc<ErrorCounter>,
+
+  /// Connections in flight across every host, as many as the concurrency, each
+  /// discovery connection and each port probe holding one
+  connections: Arc<tokio::sync::Semaphore>,
+
+  /// Token of `scan_with_cancel`, the probes not started or in flight once cancelled being dropped
+  cancel: CancelToken,
+}
+
+impl HostOptions {
+  /// Wait for the rate limiter, if any, before a connection attempt
+  async fn throttle(&self) {
+    if let Some(rate_limiter) = &self.rate_limiter {
+      rate_limiter.acquire().await;
+    }
+  }
+
+  /// Wait for the limiter of the ports per second, if any, before a port probe
+  async fn throttle_port(&self) {
+    if let Some(port_limiter) = &self.port_limiter {
+      port_limiter.acquire().await;
+    }
+  }
+
+  /// Wait for a connection in flight to end when there are as many as the concurrency
+  async fn connection(&self) -> tokio::sync::SemaphorePermit<'_> {
+    self.connections.acquire().await.expect("the semaphore of the connections is never closed")
+  }
+
+  /// Every port to probe on a host, with its protocol
+  fn probes(&self) -> Vec<(Protocol, u16)> {
+    port::probes(&self.ports, &self.protocols, &self.port_protocols)
+  }
+
+  /// Whether enough open ports were found for the scan to stop
+  fn limit_reached(&self) -> bool {
+    self.open_limit.as_ref().is_some_and(|limit| limit.reached())
+  }
+
+  /// Whether the scan ran for its maximum duration
+  fn deadline_reached(&self) -> bool {
+    self.scan_deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline)
+  }
+}
+
+/// Number of open ports a scan stops at, counted across every host
+#[derive(Debug)]
+struct OpenLimit {
+  max: usize,
+  found: AtomicUsize,
+}
+
+impl OpenLimit {
+  fn new(max: usize) -> Self {
+    Self { max, found: AtomicUsize::new(0) }
+  }
+
+  /// Count an open port
+  fn add(&self) {
+    if self.found.fetch_add(1, Ordering::SeqCst) + 1 == self.max {
+      info!("Found {} open ports, stopping the scan", self.max);
+    }
+  }
+
+  fn reached(&self) -> bool {
+    self.found.load(Ordering::SeqCst) >= self.max
+  }
+}
+
+/// Scan the hosts with a pool of `workers` tasks, every (IP, port) pair being a single item of their queue
+///
+/// The probes of a host are queued one after the other, so a few hosts are in
+/// progress at a time, their ports being probed by whichever worker is free.
+/// Each host is sent as soon as its last probe is done.
+fn scan_hosts<I>(ips: I, options: Arc<HostOptions>, workers: usize) -> Receiver<IpScanResult>
+where
+  I: Iterator<Item = IpAddr> + Send + 'static,
+{
+  let probes = options.probes();
+  let queue = ips.flat_map(move |ip| HostScan::start(ip, &probes));
+
+  work_queue(queue, workers, move |probe: Probe| {
+    let options = options.clone();
+    let ip = probe.host.ip;
+
+    logging::with_host(ip, probe.run(options))
+  })
+}
+
+/// Item of the queue of a scan, a port of a host to probe
+struct Probe {
+  host: Arc<HostScan>,
+
+  /// Protocol and port, `None` for the single item of a host without ports
+  port: Option<(Protocol, u16)>,
+}
+
+/// Host whose ports are probed by several workers, its result being assembled as the probes are done
+#[derive(Debug)]
+struct HostScan {
+  ip: IpAddr,
+
+  /// Moment the host is abandoned, set once its first probe starts
+  deadline: std::sync::OnceLock<Option<tokio::time::Instant>>,
+
+  /// Answer to the discovery probe, sent by the first worker to reach the host
+  /// `None` when the host timed out first
+  up: tokio::sync::OnceCell<Option<bool>>,
+
+  /// Probes not done yet
+  remaining: AtomicUsize,
+
+  /// Whether any probe was started, a host whose probes were all dropped once
+  /// the open ports limit was reached not being reported
+  probed: AtomicBool,
+
+  /// Ports known so far
+  ports: std::sync::Mutex<HostPorts>,
+}
+
+#[derive(Debug, Default)]
+struct HostPorts {
+  open: Vec<PortResult>,
+  filtered: Vec<PortResult>,
+  unscanned: Vec<PortResult>,
+
+  /// Whether ports are unscanned because of the host timeout, and not of the open ports limit or the deadline
+  timed_out: bool,
+
+  errors: Vec<String>,
+}
+
+impl HostScan {
+  /// Items of the queue probing each port of a host
+  fn start(ip: IpAddr, probes: &[(Protocol, u16)]) -> Vec<Probe> {
+    let host = Arc::new(HostScan {
+      ip,
+      deadline: std::sync::OnceLock::new(),
+      up: tokio::sync::OnceCell::new(),
+      remaining: AtomicUsize::new(probes.len().max(1)),
+      probed: AtomicBool::new(false),
+      ports: std::sync::Mutex::new(HostPorts::default()),
+    });
+
+    // Without ports the host is still checked, and reported
+    if probes.is_empty() {
+      return vec![Probe { host, port: None }];
+    }
+    probes.iter().map(|port| Probe { host: host.clone(), port: Some(*port) }).collect()
+  }
+
+  /// Keep the result of a probe, counting the open ports towards the limit
+  /// Every state is logged at the debug level, which `--verbose` enables
+  fn record(&self, mut result: PortResult, options: &HostOptions) {
+    debug!("{}/{} {}", result.port, result.protocol, result.state);
+    let mut ports = self.ports.lock().unwrap();
+
+    if let Some(error) = result.error.take() {
+      debug!("{}", error);
+      if !ports.errors.contains(&error) {
+        ports.errors.push(error);
+      }
+    }
+
+    match result.state {
+      PortState::Filtered => ports.filtered.push(result),
+      PortState::Closed => {}
+      // Not probed for a lack of resources
+      PortState::Unknown => ports.unscanned.push(result),
+      _ => {
+        ports.open.push(result);
+        if let Some(limit) = &options.open_limit {
+          limit.add();
+        }
+      }
+    }
+  }
+
+  /// Keep a port that wasn't probed, because of the host timeout, the open ports limit or the deadline of the scan
+  fn skip(&self, protocol: Protocol, port: u16, timed_out: bool) {
+    let mut ports = self.ports.lock().unwrap();
+
+    ports.unscanned.push(PortResult::new(port, protocol, PortState::Unknown));
+    ports.timed_out |= timed_out;
+  }
+
+  /// Keep the port of a probe that failed unscanned, with the error it failed with
+  fn fail(&self, port: Option<(Protocol, u16)>, error: String) {
+    let mut ports = self.ports.lock().unwrap();
+
+    if let Some((protocol, port)) = port {
+      ports.unscanned.push(PortResult::new(port, protocol, PortState::Unknown));
+    }
+    if !ports.errors.contains(&error) {
+      ports.errors.push(error);
+    }
+  }
+
+  /// Assemble the result of the host once every probe is done
+  /// Return `None` when none of its probes was started
+  async fn finish(&self, options: &HostOptions) -> Option<IpScanResult> {
+    if !self.probed.load(Ordering::SeqCst) {
+      return None;
+    }
+
+    // Without discovery nobody checked, the host is taken as up
+    let up = self.up.get().copied().unwrap_or(Some(true));
+    if up != Some(true) {
+      return Some(IpScanResult { up: false, timed_out: up.is_none(), ..IpScanResult::new(self.ip) });
+    }
+
+    let HostPorts { mut open, mut filtered, mut unscanned, timed_out, errors } = std::mem::take(&mut *self.ports.lock().unwrap());
+    open.sort_by_key(|result| (result.protocol, result.port));
+    filtered.sort_by_key(|result| (result.protocol, result.port));
+    unscanned.sort_by_key(|result| (result.protocol, result.port));
+
+    if timed_out {
+      debug!("host timed out, {} ports unscanned", unscanned.len());
+    } else if !unscanned.is_empty() {
+      debug!("scan stopped, {} ports unscanned", unscanned.len());
+    }
+
+    // Only the hosts worth reporting are looked up, there is no time left for the ones that timed out
+    let hostname = match &options.resolver {
+      Some(resolver) if !open.is_empty() && !timed_out => resolver.lookup(self.ip).await,
+      _ => None,
+    };
+    if let Some(hostname) = &hostname {
+      debug!("resolved to {}", hostname);
+    }
+    debug!("{} open ports, {} filtered", open.len(), filtered.len());
+
+    Some(IpScanResult {
+      ip: self.ip,
+      hostname,
+      up: true,
+      open_ports: open,
+      filtered_ports: filtered,
+      timed_out,
+      unscanned_ports: unscanned,
+      errors,
+    })
+  }
+}
+
+impl Probe {
+  /// Probe the port, checking first the host is up when discovering
+  /// Return the result of the host when this was its last probe
+  ///
+  /// Past the host timeout the probe is abandoned, or not even started, its
+  /// port being unscanned. Once the open ports limit or the deadline of the
+  /// scan is reached the probes not started are dropped, their port being unscanned too.
+  /// A cancelled scan drops the probes in flight as well.
+  async fn run(self, options: Arc<HostOptions>) -> Option<IpScanResult> {
+    let host = &self.host;
+    if options.limit_reached() || options.deadline_reached() || options.cancel.is_cancelled() {
+      if let Some((protocol, port)) = self.port {
+        host.skip(protocol, port, false);
+      }
+      return match host.remaining.fetch_sub(1, Ordering::SeqCst) {
+        1 => host.finish(&options).await,
+        _ => None,
+      };
+    }
+    host.probed.store(true, Ordering::SeqCst);
+
+    // A probe panicking, on what a host sent, leaves its port unscanned rather than losing the whole host
+    let probe = tokio::spawn(logging::with_host(host.ip, Self::probe(host.clone(), self.port, options.clone())));
+    if let Err(err) = probe.await {
+      error!("probe failed: {}", err);
+      host.fail(self.port, format!("probe failed: {}", err));
+    }
+
+    match host.remaining.fetch_sub(1, Ordering::SeqCst) {
+      1 => host.finish(&options).await,
+      _ => None,
+    }
+  }
+
+  /// Check the host is up when discovering, then probe the port, keeping its result
+  async fn probe(host: Arc<HostScan>, port: Option<(Protocol, u16)>, options: Arc<HostOptions>) {
+    let deadline = *host.deadline.get_or_init(|| options.host_timeout.map(|host_timeout| tokio::time::Instant::now() + host_timeout));
+
+    let up = match options.discovery {
+      true => {
+        *host
+          .up
+          .get_or_init(|| async {
+            let up = before(deadline, is_host_up(host.ip, &options)).await;
+            if up != Some(true) {
+              debug!("host down");
+            }
+            up
+          })
+          .await
+      }
+      false => Some(true),
+    };
+
+    if let (Some(true), Some((protocol, port))) = (up, port) {
+      let expired = deadline.is_some_and(|deadline| tokio::time::Instant::now() >= deadline);
+      let result = match expired {
+        true => Err(true),
+        false => tokio::select! {
+          result = before(deadline, probe_port(host.ip, protocol, port, &options)) => result.ok_or(true),
+          _ = options.cancel.cancelled() => Err(false),
+        },
+      };
+
+      match result {
+        Ok(result) => host.record(result, &options),
+        Err(timed_out) => host.skip(protocol, port, timed_out),
+      }
+    }
+  }
+}
+
+  /// Probe a port of a host with the given protocol, a second time when open and confirming
+  async fn probe_port(ip: IpAddr, protocol: Protocol, port: u16, options: &HostOptions) -> PortResult {
+    debug_assert_ne!(port, 0, "port 0 is refused when resolving the ports");
+    options.throttle_port().await;
+    let _connection = options.connection().await;
+    let mut result = match protocol {
+      Protocol::Tcp => scan_tcp_port(ip, port, options).await,
+      Protocol::Udp => {
+        options.throttle().await;
+        PortResult::new(port, Protocol::Udp, scan_udp_port(ip, port, options.timeout, &options.source).await)
+      }
+    };
+
+    if options.confirm && result.state == PortState::Open {
+      options.throttle_port().await;
+      result.unstable = !is_still_open(ip, protocol, port, options).await;
+      if result.unstable {
+        debug!("{}/{} no longer open", port, protocol);
+      }
+    }
+
+    result
+  }
+
+  /// Probe an open port again for its state only, with the retries of a first probe
+  /// A load balancer sending it to another backend or a flapping service may no longer have it open
+  async fn is_still_open(ip: IpAddr, protocol: Protocol, port: u16, options: &HostOptions) -> bool {
+    match (protocol, &options.syn, ip) {
+      (Protocol::Udp, _, _) => {
+        options.throttle().await;
+        scan_udp_port(ip, port, options.timeout, &options.source).await == PortState::Open
+      }
+      (Protocol::Tcp, Some(syn), IpAddr::V4(ip)) => scan_syn_port(syn, ip, port, options).await.state == PortState::Open,
+      (Protocol::Tcp, _, _) => scan_port(ip, port, &options.connect_timeout, options.retries, options.proxy.as_ref(), &options.source, || options.throttle())
+        .await
+        .inspect_err(|err| options.errors.add(err.kind()))
+        .is_ok(),
+    }
+  }
+
+  /// Run a future until the deadline, if any
+  /// Return `None` when the deadline passed first
+  async fn before<F: Future>(deadline: Option<tokio::time::Instant>, future: F) -> Option<F::Output> {
+    match deadline {
+      Some(deadline) => tokio::time::timeout_at(deadline, future).await.ok(),
+      None => Some(future.await),
+    }
+  }
+
+  /// Check if a host is up with a TCP connect on the discovery ports, or with ARP when it is on the link
+  /// A refused connection means the host answered with a reset, so it is up too, as is a host that
+  /// couldn't be checked for a lack of file descriptors
+  async fn is_host_up(ip: IpAddr, options: &Arc<HostOptions>) -> bool {
+    if let (Some(arp), IpAddr::V4(ip)) = (&options.arp, ip) {
+      if arp.on_link(ip) {
+        return arp.is_up(ip, options.timeout).await;
+      }
+    }
+
+    // The discovery ports are tried at the same time by the worker that reached the host first,
+    // a host down costing a single timeout, and the first answer ends the others
+    // Each connection waits for its turn among the ones in flight, as the port probes do
+    let mut discovery = tokio::task::JoinSet::new();
+    for port in DISCOVERY_PORTS {
+      let options = options.clone();
+      // Only waiting for file descriptors is retried, the ports of a host up answer the first time
+      discovery.spawn(async move {
+        let _connection = options.connection().await;
+        with_retries(0, RETRY_BACKOFF, |_| async {
+          options.throttle().await;
+          connect(SocketAddr::new(ip, port), options.timeout, options.proxy.as_ref(), &options.source).await
+        })
+        .await
+        .map(|_| ())
+      });
+    }
+
+    let mut exhausted = false;
+    while let Some(answer) = discovery.join_next().await {
+      match answer {
+        Ok(Ok(())) => return true,
+        Ok(Err(err)) if err.kind() == ErrorKind::ConnectionRefused => return true,
+        Ok(Err(err)) => exhausted |= is_exhausted(&err),
+        Err(_) => {}
+      }
+    }
+
+    // Nothing was learnt about the host, its ports are scanned rather than skipped as down
+    if exhausted {
+      warn!("Out of file descriptors while checking {} is up, scanning it anyway", ip);
+    }
+    exhausted
+  }
+
+  /// Scan a TCP port, grabbing its banner when enabled
+  /// A port not answering before the timeout is filtered, most likely by a firewall dropping the packets
+  async fn scan_tcp_port(ip: IpAddr, port: u16, options: &HostOptions) -> PortResult {
+    // The services are probed on a connection, a SYN only telling the state
+    if let (Some(syn), IpAddr::V4(ip)) = (&options.syn, ip) {
+      if !options.banner && !options.tls && !options.http_ports.contains(&port) {
+        return scan_syn_port(syn, ip, port, options).await;
+      }
+    }
+
+    let (mut stream, latency) = match scan_port(ip, port, &options.connect_timeout, options.retries, options.proxy.as_ref(), &options.source, || options.throttle()).await {
+      Ok(connection) => connection,
+      Err(err) => {
+        options.errors.add(err.kind());
+        return failed_result(port, &err);
+      }
+    };
+
+    options.connect_timeout.observe(latency);
+    let mut result = PortResult::new(port, Protocol::Tcp, PortState::Open);
+    result.latency = Some(latency);
+
+    // A web server waits for the request, there is no banner or handshake to wait for
+    if options.http_ports.contains(&port) {
+      let address = SocketAddr::new(ip, port);
+      result.server_name = server_name(ip, options).await;
+      // A redirect not followed on this connection opens a new one, throttled and retried as this one was
+      let reconnect = |address: SocketAddr| async move {
+        scan_port(address.ip(), address.port(), &options.connect_timeout, options.retries, options.proxy.as_ref(), &options.source, || options.throttle()).await.map(|(stream, _)| stream)
+      };
+      result.http = http::probe_http(&mut stream, address, result.server_name.as_deref(), options.read_timeout, reconnect).await;
+      if result.http.is_none() {
+        result.error = Some(format!("{}/tcp: no HTTP response", port));
+      }
+      return result;
+    }
+
+    if options.banner {
+      result.banner = banner::grab_banner(&mut stream, options.read_timeout).await;
+      result.service = result.banner.as_deref().and_then(fingerprint::identify);
+    }
+    // A service sending a banner first doesn't wait for a TLS handshake
+    if options.tls && result.banner.is_none() {
+      result.server_name = server_name(ip, options).await;
+      result.tls = tls::probe_tls(&mut stream, result.server_name.as_deref(), TLS_TIMEOUT).await;
+    }
+
+    result
+  }
+
+  /// Name of the virtual host of an IP for its HTTP and TLS probes, looked up when resolving without one given,
+  /// the lookup being cached for the host name of its result
+  async fn server_name(ip: IpAddr, options: &HostOptions) -> Option<String> {
+    match (&options.server_name, &options.resolver) {
+      (Some(name), _) => Some(name.clone()),
+      (None, Some(resolver)) => resolver.lookup(ip).await,
+      (None, None) => None,
+    }
+  }
+
+  /// Scan a TCP port with a SYN, without completing the handshake
+  async fn scan_syn_port(syn: &SynScanner, ip: Ipv4Addr, port: u16, options: &HostOptions) -> PortResult {
+    let probe = with_retries(options.retries, RETRY_BACKOFF, |attempt| async move {
+      options.throttle().await;
+      syn.probe(ip, port, options.connect_timeout.get(attempt)).await
+    })
+    .await;
+
+    match probe {
+      Ok(latency) => {
+        options.connect_timeout.observe(latency);
+        PortResult { latency: Some(latency), ..PortResult::new(port, Protocol::Tcp, PortState::Open) }
+      }
+      Err(err) => {
+        options.errors.add(err.kind());
+        failed_result(port, &err)
+      }
+    }
+  }
+
+  /// Connect to a TCP port from `source`, through the proxy if any, calling `throttle` before each attempt
+  /// Return the stream and the time the successful attempt took to connect
+  async fn scan_port<F, Fut>(ip: IpAddr, port: u16, timeout: &ConnectTimeout, retries: u32, proxy: Option<&Proxy>, source: &Source, throttle: F) -> io::Result<(TcpStream, Duration)>
+  where
+    F: Fn() -> Fut,
+    Fut: Future<Output = ()>,
+  {
+    let address = SocketAddr::new(ip, port);
+    let throttle = &throttle;
+
+    with_retries(retries, RETRY_BACKOFF, move |attempt| async move {
+      throttle().await;
+      connect(address, timeout.get(attempt), proxy, source).await
+    })
+    .await
+  }
+
+  /// Open a TCP connection, failing with `TimedOut` when it takes longer than `timeout`
+  /// Through a proxy, the timeout covers the whole handshake with the proxy
+  /// Return the stream and the time it took to connect
+  async fn connect(address: SocketAddr, timeout: Duration, proxy: Option<&Proxy>, source: &Source) -> io::Result<(TcpStream, Duration)> {
+    let start = Instant::now();
+    let stream = tokio::time::timeout(timeout, proxy::connect(proxy, source, address)).await??;
+
+    Ok((stream, start.elapsed()))
+  }
+
+  /// Whether a connection error may not happen again on a new attempt
+  /// A refused connection is a definitive answer from the host so it is not retried
+  fn is_transient(kind: ErrorKind) -> bool {
+    matches!(
+      kind,
+      ErrorKind::TimedOut | ErrorKind::WouldBlock | ErrorKind::ConnectionReset | ErrorKind::ConnectionAborted | ErrorKind::Interrupted
+    )
+  }
+
+  /// Whether a connection failed for a lack of file descriptors or buffers on this machine, telling nothing of the port
+  fn is_exhausted(err: &io::Error) -> bool {
+    #[cfg(unix)]
+    if matches!(err.raw_os_error(), Some(libc::EMFILE | libc::ENFILE | libc::ENOBUFS)) {
+      return true;
+    }
+
+    err.kind() == ErrorKind::OutOfMemory
+  }
+
+  /// Warn once for the whole process that the scan runs out of file descriptors
+  fn warn_exhausted(err: &io::Error) {
+    static WARNED: AtomicBool = AtomicBool::new(false);
+
+    if !WARNED.swap(true, Ordering::SeqCst) {
+      warn!("{}, backing off and retrying: lower --concurrency or raise the limit of open files (ulimit -n)", err);
+    }
+  }
+
+  /// State of a TCP port whose connection failed
+  /// The port is left unknown when this machine ran out of resources, instead of being reported closed
+  fn failed_state(err: &io::Error) -> PortState {
+    if is_exhausted(err) {
+      PortState::Unknown
+    } else if err.kind() == ErrorKind::TimedOut {
+      PortState::Filtered
+    } else {
+      PortState::Closed
+    }
+  }
+
+  /// Result of a TCP port whose connection or SYN failed
+  /// Refused or not answering is the state of the port, anything else a problem on the way to it
+  fn failed_result(port: u16, err: &io::Error) -> PortResult {
+    let mut result = PortResult::new(port, Protocol::Tcp, failed_state(err));
+    if !matches!(err.kind(), ErrorKind::ConnectionRefused | ErrorKind::TimedOut) {
+      result.error = Some(format!("connection failed: {}", err));
+    }
+
+    result
+  }
+
+  /// Run `connect` until it succeeds, fails for a non transient reason or `retries` retries are done
+  /// `connect` is given the number of the attempt, the first one being 0
+  /// Running out of file descriptors doesn't count as an attempt, up to `EXHAUSTED_RETRIES` times with a longer backoff
+  /// giving the connections in flight the time to end
+  async fn with_retries<T, F, Fut>(retries: u32, backoff: Duration, mut connect: F) -> io::Result<T>
+  where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = io::Result<T>>,
+  {
+    let mut attempt = 0;
+    let mut exhausted = 0;
+
+    loop {
+      match connect(attempt).await {
+        Err(err) if exhausted < EXHAUSTED_RETRIES && is_exhausted(&err) => {
+          warn_exhausted(&err);
+          let delay = backoff * 2_u32.pow(exhausted + 1);
+          trace!("connection failed ({}), retrying in {}ms", err, delay.as_millis());
+          tokio::time::sleep(delay).await;
+          exhausted += 1;
+        }
+        Err(err) if attempt < retries && is_transient(err.kind()) => {
+          let delay = backoff * 2_u32.pow(attempt);
+          trace!("connection failed ({}), retrying in {}ms", err, delay.as_millis());
+          tokio::time::sleep(delay).await;
+          attempt += 1;
+        }
+        result => return result,
+      }
+    }
+  }
+
+  /// Probe an UDP port with a single datagram
+  ///
+  /// - A response means the port is open
+  /// - An ICMP port unreachable (surfaced as `ConnectionRefused` on a
+  ///   connected socket) means the port is closed
+  /// - No answer before the timeout means the port is open|filtered
+  async fn scan_udp_port(ip: IpAddr, port: u16, timeout: Duration, source: &Source) -> PortState {
+    let address = SocketAddr::new(ip, port);
+
+    let socket = match source.bind_udp(ip) {
+      Ok(socket) => socket,
+      Err(err) if is_exhausted(&err) => {
+        warn_exhausted(&err);
+        return PortState::Unknown;
+      }
+      Err(_) => return PortState::Closed,
+    };
+
+    if socket.connect(address).await.is_err() {
+      return PortState::Closed;
+    }
+
+    if let Err(err) = socket.send(udp_probe(port)).await {
+      return match err.kind() {
+        ErrorKind::ConnectionRefused => PortState::Closed,
+        _ => PortState::OpenFiltered,
+      };
+    }
+
+    match tokio::time::timeout(timeout, recv_udp(&socket)).await {
+      Ok(Ok(_)) => PortState::Open,
+      Ok(Err(err)) => match err.kind() {
+        ErrorKind::WouldBlock | ErrorKind::TimedOut => PortState::OpenFiltered,
+        _ => PortState::Closed,
+      },
+      // Nothing came back before the timeout
+      Err(_) => PortState::OpenFiltered,
+    }
+  }
+
+  /// Wait for a datagram on a connected socket, or for the error reported by the host
+  /// An ICMP error only raises an error on the socket, a plain `recv` would not wake up for it
+  async fn recv_udp(socket: &UdpSocket) -> io::Result<usize> {
+    let mut buffer = [0_u8; 512];
+
+    loop {
+      let ready = socket.ready(Interest::READABLE | Interest::ERROR).await?;
+      if ready.is_error() {
+        return Err(socket.take_error()?.unwrap_or_else(|| ErrorKind::ConnectionRefused.into()));
+      }
+
+      match socket.try_recv(&mut buffer) {
+        Err(err) if err.kind() == ErrorKind::WouldBlock => continue,
+        result => return result,
+      }
+    }
+  }
+
+  /// Payload sent to an UDP port
+  /// Most services ignore an empty datagram so well known ones get a valid request
+  fn udp_probe(port: u16) -> &'static [u8] {
+    match port {
+      // DNS query for the root NS records
+      53 => &[0x13, 0x37, 0x01, 0x00, 0x00, 0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x02, 0x00, 0x01],
+      // NTP v3 client request
+      123 => &[
+        0x1b, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+      ],
+      _ => &[],
+    }
+  }
+
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::connection::ConnectionError;
+  use std::net::{Ipv4Addr, Ipv6Addr};
+
+  #[test]
+  fn test_get_one_ip() {
+    let scanner = Scanner::new("192.168.1.1".to_string(), None).unwrap();
+    let ips = scanner.get_ips();
+
+    assert_eq!(ips.len(), 1);
+    assert_eq!(ips[0], "192.168.1.1".parse::<IpAddr>().unwrap());
+  }
+
+  #[test]
+  fn test_default_ports() {
+    let scanner = Scanner::new("192.168.1.1".to_string(), None).unwrap();
+
+    assert_eq!(scanner.ports, DEFAULT_PORTS);
+  }
+
+  #[test]
+  fn test_explicit_ports() {
+    let scanner = Scanner::new("192.168.1.1".to_string(), Some(vec![21, 25])).unwrap();
+
+    assert_eq!(scanner.ports, vec![21, 25]);
+  }
+
+  #[test]
+  fn test_get_ips_bare_network_address() {
+    let scanner = Scanner::new("192.168.1.0".to_string(), None).unwrap();
+    assert_eq!(scanner.get_ips(), vec!["192.168.1.0".parse::<IpAddr>().unwrap()]);
+
+    let scanner = Scanner::new("10.0.0.0".to_string(), None).unwrap();
+    assert_eq!(scanner.get_ips(), vec!["10.0.0.0".parse::<IpAddr>().unwrap()]);
+    assert_eq!(scanner.reserved_ranges(), vec![]);
+
+    let scanner = Scanner::new("192.168.1.0/24".to_string(), None).unwrap();
+    assert_eq!(scanner.get_ips().len(), 254);
+    assert!(!scanner.get_ips().contains(&"192.168.1.0".parse::<IpAddr>().unwrap()));
+  }
+
+  #[test]
+  fn test_get_ips_24() {
+    let scanner = Scanner::new("192.168.1.0/24".to_string(), None).unwrap();
+    let ips = scanner.get_ips();
+
+    assert_eq!(ips.len(), 254);
+
+    for (i, ip) in ips.iter().enumerate() {
+      assert_eq!(*ip, format!("192.168.1.{}", i + 1).parse::<IpAddr>().unwrap());
+    }
+  }
+
+  #[test]
+  fn test_get_ips_8() {
+    let scanner = Scanner::new("192.0.0.0/8".to_string(), None).unwrap();
+    let ips = scanner.get_ips();
+    let ips_to_compare = vec![
+      "192.1.0.0",
+      "192.1.1.1",
+      "192.1.1.254",
+      "192.254.254.254",
+      "192.255.255.254",
+      "192.254.128.254",
+    ];
+
+
+    for ip in ips_to_compare {
+      assert!(ips.contains(&ip.parse::<IpAddr>().unwrap()), "Should contain [{}]", ip);
+    }
+  }
+
+  #[test]
+  fn test_get_ips_16() {
+    let scanner = Scanner::new("192.168.0.0/16".to_string(), None).unwrap();
+    let ips = scanner.get_ips();
+
+    assert_eq!(ips.len(), 65534);
+
+    let unique = ips.iter().collect::<std::collections::HashSet<_>>();
+    assert_eq!(unique.len(), 65534);
+
+    for (i, ip) in ips.iter().enumerate() {
+      let expected = u32::from(Ipv4Addr::new(192, 168, 0, 0)) + i as u32 + 1;
+      assert_eq!(*ip, IpAddr::V4(Ipv4Addr::from(expected)));
+    }
+
+    assert!(ips.contains(&"192.168.0.255".parse::<IpAddr>().unwrap()));
+    assert!(!ips.contains(&"192.168.255.255".parse::<IpAddr>().unwrap()));
+  }
+
+  #[tokio::test(flavor = "multi_thread", worker_threads = 8)]
+  async fn test_work_queue_limit() {
+    let running = Arc::new(AtomicUsize::new(0));
+    let max_running = Arc::new(AtomicUsize::new(0));
+
+    let task_running = running.clone();
+    let task_max_running = max_running.clone();
+    let mut receiver = work_queue(0..50, 3, move |i: u32| {
+      let running = task_running.clone();
+      let max_running = task_max_running.clone();
+
+      async move {
+        let current = running.fetch_add(1, Ordering::SeqCst) + 1;
+        max_running.fetch_max(current, Ordering::SeqCst);
+
+        tokio::time::sleep(Duration::from_millis(5)).await;
+
+        running.fetch_sub(1, Ordering::SeqCst);
+        // Odd items have nothing to report
+        i.is_multiple_of(2).then_some(i)
+      }
+    });
+
+    let mut results = Vec::new();
+    while let Some(result) = receiver.recv().await {
+      results.push(result);
+    }
+    results.sort();
+
+    assert_eq!(max_running.load(Ordering::SeqCst), 3);
+    assert_eq!(results, (0..50).step_by(2).collect::<Vec<u32>>());
+  }
+
+  #[tokio::test]
+  async fn test_work_queue_panic() {
+    let mut receiver = work_queue(0..10, 2, |i: u32| async move {
+      assert_ne!(i, 3);
+      Some(i)
+    });
+
+    let mut results = Vec::new();
+    while let Some(result) = receiver.recv().await {
+      results.push(result);
+    }
+    results.sort();
+
+    assert_eq!(results, vec![0, 1, 2, 4, 5, 6, 7, 8, 9]);
+  }
+
+  #[tokio::test]
+  async fn test_scan_probe_panic() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1").ports(vec![port]).discovery(false).quiet(true).build().unwrap();
+    // Refused when resolving the ports, probing it panics in debug builds
+    scanner.ports.push(0);
+    scanner.scan().await;
+
+    let results = scanner.results();
+    assert_eq!(results.len(), 1);
+    assert_eq!(results[0].open_ports.iter().map(|result| result.port).collect::<Vec<u16>>(), vec![port]);
+    assert_eq!(results[0].unscanned_ports.iter().map(|result| result.port).collect::<Vec<u16>>(), vec![0]);
+    assert_eq!(results[0].errors.len(), 1);
+    assert!(results[0].errors[0].starts_with("probe failed:"), "{:?}", results[0].errors);
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_scan_every_pair_once() {
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+
+    // Bound on every loopback address, each accepted connection tells the IP and port it was for
+    let accepted: Arc<Mutex<HashMap<SocketAddr, usize>>> = Arc::default();
+    let mut ports = Vec::new();
+    for _ in 0..5 {
+      let listener = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+      ports.push(listener.local_addr().unwrap().port());
+
+      let accepted = accepted.clone();
+      std::thread::spawn(move || {
+        for stream in listener.incoming().flatten() {
+          *accepted.lock().unwrap().entry(stream.local_addr().unwrap()).or_default() += 1;
+        }
+      });
+    }
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1-127.0.0.4").ports(ports.clone()).discovery(false).concurrency(3).quiet(true).build().unwrap();
+    scanner.scan().await;
+    assert!(scanner.results().iter().all(|result| result.open_ports.len() == 5), "{:?}", scanner.results());
+
+    // The listeners may still be accepting the last connections
+    let start = Instant::now();
+    while accepted.lock().unwrap().values().sum::<usize>() < 20 && start.elapsed() < Duration::from_secs(2) {
+      std::thread::sleep(Duration::from_millis(10));
+    }
+    std::thread::sleep(Duration::from_millis(50));
+
+    let accepted = accepted.lock().unwrap();
+    for ip in scanner.get_ips() {
+      for port in &ports {
+        assert_eq!(accepted.get(&SocketAddr::new(ip, *port)), Some(&1), "{}:{}", ip, port);
+      }
+    }
+    assert_eq!(accepted.len(), 20);
+  }
+
+  #[tokio::test]
+  async fn test_serialize_only_open() {
+    // Nothing listens on the discard port
+    let mut scanner = Scanner::builder().ip("127.0.0.1-127.0.0.2").ports(vec![9]).discovery(false).quiet(true).build().unwrap();
+    scanner.scan().await;
+    assert_eq!(scanner.results().len(), 2);
+
+    let json: serde_json::Value = serde_json::from_str(&scanner.serialize(OutputFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["result"], serde_json::json!([]));
+    assert_eq!(json["summary"]["hostsScanned"], 2);
+    assert!(serde_yaml::from_str::<serde_yaml::Value>(&scanner.serialize(OutputFormat::Yaml).unwrap()).unwrap()["result"].as_sequence().unwrap().is_empty());
+    assert_eq!(scanner.serialize(OutputFormat::Jsonl).unwrap(), "");
+
+    scanner.include_down = true;
+    let json: serde_json::Value = serde_json::from_str(&scanner.serialize(OutputFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["result"].as_array().unwrap().len(), 2);
+    assert_eq!(scanner.serialize(OutputFormat::Jsonl).unwrap().lines().count(), 2);
+  }
+
+  #[tokio::test]
+  async fn test_scan_with() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1-127.0.0.3").ports(vec![port]).discovery(false).quiet(true).build().unwrap();
+    let mut done = Vec::new();
+    scanner.scan_with(|result| done.push((result.ip, result.open_ports.len()))).await;
+
+    // Called for the hosts without open ports too
+    done.sort();
+    let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+    assert_eq!(done, vec![(ip("127.0.0.1"), 1), (ip("127.0.0.2"), 0), (ip("127.0.0.3"), 0)]);
+    assert_eq!(scanner.results().len(), 3);
+  }
+
+  #[tokio::test]
+  async fn test_scan_resume() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1-127.0.0.3").ports(vec![port]).discovery(false).quiet(true).build().unwrap();
+    let previous = IpScanResult { open_ports: vec![PortResult::new(1, Protocol::Tcp, PortState::Open)], ..IpScanResult::new(ip("127.0.0.2")) };
+    scanner.resume(vec![previous.clone()]);
+    assert_eq!(scanner.get_ips(), vec![ip("127.0.0.1"), ip("127.0.0.3")]);
+
+    let mut done = Vec::new();
+    scanner.scan_with(|result| done.push(result.ip)).await;
+
+    // The resumed host is not scanned again, its result being kept
+    done.sort();
+    assert_eq!(done, vec![ip("127.0.0.1"), ip("127.0.0.3")]);
+    assert_eq!(scanner.results().len(), 3);
+    assert_eq!(scanner.results()[1], previous);
+  }
+
+  #[tokio::test]
+  async fn test_scan_max_open() {
+    let mut ports = Vec::new();
+    let mut listeners = Vec::new();
+    for _ in 0..5 {
+      let listener = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+      ports.push(listener.local_addr().unwrap().port());
+      listeners.push(listener);
+    }
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1-127.0.0.50").ports(ports).discovery(false).concurrency(1).max_open(3).quiet(true).build().unwrap();
+    scanner.scan().await;
+
+    // A single worker probes the ports one after the other, no probe is in progress when the limit is reached
+    let summary = scanner.summary().unwrap();
+    assert_eq!(summary.open_ports, 3);
+    assert!(summary.max_open_reached && !summary.interrupted);
+    assert!(summary.to_string().ends_with(", stopped at the maximum of open ports"), "{}", summary);
+
+    let results = scanner.results();
+    assert_eq!(results.len(), 1, "{:?}", results);
+    assert_eq!(results[0].unscanned_ports.len(), 2);
+    assert!(!results[0].timed_out);
+  }
+
+  #[tokio::test]
+  async fn test_scan_adaptive_timeout() {
+    let listeners = (0..crate::timeout::SAMPLES).map(|_| std::net::TcpListener::bind("127.0.0.1:0").unwrap()).collect::<Vec<_>>();
+    let ports = listeners.iter().map(|listener| listener.local_addr().unwrap().port()).collect::<Vec<u16>>();
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1/32").ports(ports.clone()).discovery(false).quiet(true).build().unwrap();
+    scanner.scan().await;
+    assert_eq!(scanner.adapted_timeout(), None);
+
+    // Loopback round trips are far below the lowest timeout
+    let mut scanner = Scanner::builder()
+      .ip("127.0.0.1/32")
+      .ports(ports)
+      .discovery(false)
+      .adaptive_timeout(Duration::from_millis(40))
+      .quiet(true)
+      .build()
+      .unwrap();
+    scanner.scan().await;
+
+    assert_eq!(scanner.results()[0].open_ports.len(), crate::timeout::SAMPLES);
+    assert_eq!(scanner.adapted_timeout(), Some(Duration::from_millis(40)));
+    assert!(scanner.serialize(OutputFormat::Json).unwrap().contains(r#""adaptedTimeoutMs": 40"#));
+  }
+
+  #[tokio::test]
+  async fn test_scan_until_interrupted() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let mut scanner = Scanner::builder().ip("127.0.0.0/24").ports(vec![port]).discovery(false).concurrency(1).quiet(true).build().unwrap();
+    let interrupted = scanner.scan_until(std::future::ready(())).await;
+
+    let summary = scanner.summary().unwrap().clone();
+    assert!(interrupted);
+    assert!(summary.interrupted);
+    assert!(summary.hosts_scanned < 254, "{}", summary);
+    assert!(scanner.report().ends_with(", interrupted\n"), "{}", scanner.report());
+
+    let json: serde_json::Value = serde_json::from_str(&scanner.serialize(OutputFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["summary"]["interrupted"], true);
+  }
+
+  #[tokio::test]
+  async fn test_scan_until_completed() {
+    let mut scanner = Scanner::builder().ip("127.0.0.1/32").ports(vec![9]).discovery(false).quiet(true).build().unwrap();
+
+    assert!(!scanner.scan_until(tokio::time::sleep(Duration::from_secs(60))).await);
+    assert!(!scanner.summary().unwrap().interrupted);
+  }
+
+  #[tokio::test]
+  async fn test_scan_connection_errors() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1-127.0.0.2").ports(vec![port, closed]).discovery(false).quiet(true).build().unwrap();
+    assert!(scanner.connection_errors().is_empty());
+
+    // The counts of a scan replace the ones of the previous scan
+    for _ in 0..2 {
+      scanner.scan().await;
+      assert_eq!(scanner.connection_errors().get(ConnectionError::Refused), 3);
+    }
+    assert!(scanner.report().ends_with("\nConnection errors: refused: 3\n"), "{}", scanner.report());
+
+    let json: serde_json::Value = serde_json::from_str(&scanner.serialize(OutputFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["connectionErrors"]["refused"], 3);
+  }
+
+  #[tokio::test]
+  async fn test_scan_from_source() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let source = Source { ip: Some("127.0.0.2".parse().unwrap()), interface: None };
+    let mut scanner = Scanner::builder().ip("127.0.0.1/32").ports(vec![port]).source(source.clone()).quiet(true).build().unwrap();
+    scanner.scan().await;
+    assert_eq!(scanner.results()[0].open_ports.len(), 1);
+
+    let err = Scanner::builder().ip("10.0.0.1/32").ip("fd00::1").source(source).build().unwrap_err();
+    assert_eq!(err, ScannerError::SourceFamily { source: "127.0.0.2".parse().unwrap(), target: "fd00::1".to_string() });
+  }
+
+  #[tokio::test]
+  async fn test_scan_stream() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let scanner = Scanner::new("127.0.0.1/32".to_string(), Some(vec![port])).unwrap();
+    let mut receiver = scanner.scan_stream();
+
+    let result = receiver.recv().await.unwrap();
+    assert_eq!(result.ip, "127.0.0.1".parse::<IpAddr>().unwrap());
+    assert_eq!(result.open_ports.len(), 1);
+    assert_eq!(result.open_ports[0].port, port);
+    assert_eq!(result.open_ports[0].state, PortState::Open);
+    assert!(receiver.recv().await.is_none());
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_scan_until_each_json_lines() {
+    let listener = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+      for mut stream in listener.incoming().flatten() {
+        let _ = std::io::Write::write_all(&mut stream, b"SSH-2.0-OpenSSH_9.6\r\n");
+      }
+    });
+
+    let mut scanner = Scanner::new("127.0.0.1-127.0.0.2".to_string(), Some(vec![port])).unwrap();
+    scanner.options.banner = true;
+    scanner.quiet = true;
+
+    let mut output = String::new();
+    scanner.scan_until_each(std::future::pending::<()>(), |result| output.push_str(&(crate::output::to_json_line(result).unwrap() + "\n"))).await;
+
+    let mut ips = Vec::new();
+    for line in output.lines() {
+      let result: IpScanResult = serde_json::from_str(line).unwrap();
+      assert_eq!(result.open_ports.len(), 1);
+      assert_eq!(result.open_ports[0].port, port);
+      assert_eq!(result.open_ports[0].banner.as_deref(), Some("SSH-2.0-OpenSSH_9.6"));
+      assert_eq!(result.open_ports[0].service.as_ref().map(|service| service.name.as_str()), Some("ssh"));
+      ips.push(result.ip);
+    }
+    ips.sort();
+    assert_eq!(ips, vec!["127.0.0.1".parse::<IpAddr>().unwrap(), "127.0.0.2".parse().unwrap()]);
+    assert_eq!(crate::output::to_jsonl(&scanner.result).unwrap().lines().count(), 2);
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_scan_ports_concurrently() {
+    let listeners = (0..4).map(|_| std::net::TcpListener::bind("127.0.0.1:0").unwrap()).collect::<Vec<_>>();
+    let mut ports = listeners.iter().map(|listener| listener.local_addr().unwrap().port()).collect::<Vec<u16>>();
+    ports.sort_by(|a, b| b.cmp(a));
+
+    let mut scanner = Scanner::new("127.0.0.1/32".to_string(), None).unwrap();
+    scanner.options.discovery = false;
+    scanner.options.banner = true;
+
+    // Each silent listener makes its probe wait for the whole banner timeout
+    let start = std::time::Instant::now();
+    let result = scan_hosts(std::iter::once(Ipv4Addr::LOCALHOST.into()), Arc::new(scanner.host_options(ports.clone())), 4).recv().await.unwrap();
+    let elapsed = start.elapsed();
+
+    ports.sort();
+    assert_eq!(result.open_ports.iter().map(|port| port.port).collect::<Vec<u16>>(), ports);
+    assert!(elapsed < BANNER_TIMEOUT * 2, "{:?}", elapsed);
+  }
+
+  #[tokio::test]
+  async fn test_scan_resolve() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let mut scanner = Scanner::new("127.0.0.1/32".to_string(), Some(vec![port])).unwrap();
+    scanner.quiet = true;
+    scanner.options.resolve = true;
+    scanner.scan().await;
+
+    let hostname = scanner.results()[0].hostname.clone().unwrap();
+    assert!(hostname.starts_with("localhost"), "{}", hostname);
+    assert_eq!(scanner.resolver.len(), 1);
+  }
+
+  #[tokio::test]
+  async fn test_scan_http() {
+    use std::io::{Read, Write};
+
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut request = [0_u8; 1024];
+      let _ = stream.read(&mut request).unwrap();
+      stream.write_all(b"HTTP/1.1 200 OK\r\n\r\n<title>Dashboard</title>").unwrap();
+    });
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1/32").ports(vec![port]).discovery(false).http(true).http_ports(vec![port]).quiet(true).build().unwrap();
+    scanner.scan().await;
+
+    let http = scanner.results()[0].open_ports[0].http.clone().unwrap();
+    assert_eq!((http.status, http.title.as_deref()), (200, Some("Dashboard")));
+    assert!(scanner.report().contains(&format!("{}/tcp [http 200 \"Dashboard\"]", port)), "{}", scanner.report());
+
+    let json: serde_json::Value = serde_json::from_str(&scanner.serialize(OutputFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["result"][0]["openPorts"][0]["http"]["title"], "Dashboard");
+  }
+
+  #[tokio::test]
+  async fn test_scan_host_errors() {
+    use std::io::{Read, Write};
+
+    // Listed as an HTTP port, but another service answers the request
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut request = [0_u8; 1024];
+      let _ = stream.read(&mut request).unwrap();
+      stream.write_all(b"SSH-2.0-OpenSSH_9.6\r\n").unwrap();
+    });
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1/32").ports(vec![port]).discovery(false).http(true).http_ports(vec![port]).quiet(true).build().unwrap();
+    scanner.scan().await;
+
+    // The host is still reported, its port open
+    let result = &scanner.results()[0];
+    assert_eq!(result.open_ports.len(), 1);
+    assert_eq!(result.open_ports[0].http, None);
+    assert_eq!(result.errors, vec![format!("{}/tcp: no HTTP response", port)]);
+
+    let json: serde_json::Value = serde_json::from_str(&scanner.serialize(OutputFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["result"][0]["errors"][0], format!("{}/tcp: no HTTP response", port));
+    assert!(json["result"][0]["openPorts"][0].get("error").is_none());
+  }
+
+  #[tokio::test]
+  async fn test_scan_http_virtual_host() {
+    use std::io::{Read, Write};
+
+    // A server answering by name, with a default page for the requests by IP
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    std::thread::spawn(move || {
+      for stream in listener.incoming() {
+        let mut stream = stream.unwrap();
+        let mut request = [0_u8; 1024];
+        let size = stream.read(&mut request).unwrap();
+        let request = String::from_utf8_lossy(&request[..size]).to_ascii_lowercase();
+        let title = if request.contains("\r\nhost: intranet.lan:") { "Intranet" } else { "Default" };
+        stream.write_all(format!("HTTP/1.1 200 OK\r\nConnection: close\r\n\r\n<title>{}</title>", title).as_bytes()).unwrap();
+      }
+    });
+
+    let scan = |sni: Option<&str>| {
+      let builder = Scanner::builder().ip("127.0.0.1/32").ports(vec![port]).discovery(false).http(true).http_ports(vec![port]).quiet(true);
+      let mut scanner = match sni {
+        Some(sni) => builder.sni(sni),
+        None => builder,
+      }
+      .build()
+      .unwrap();
+      async move {
+        scanner.scan().await;
+        scanner.results()[0].open_ports[0].clone()
+      }
+    };
+
+    let by_name = scan(Some("intranet.lan")).await;
+    assert_eq!(by_name.http.and_then(|http| http.title).as_deref(), Some("Intranet"));
+    assert_eq!(by_name.server_name.as_deref(), Some("intranet.lan"));
+
+    let by_ip = scan(None).await;
+    assert_eq!(by_ip.http.and_then(|http| http.title).as_deref(), Some("Default"));
+    assert_eq!(by_ip.server_name, None);
+  }
+
+  #[tokio::test]
+  async fn test_scan_tcp_port_filtered() {
+    // Connections beyond a full accept queue get their SYN dropped, like behind a firewall
+    let socket = tokio::net::TcpSocket::new_v4().unwrap();
+    socket.bind("127.0.0.1:0".parse().unwrap()).unwrap();
+    let listener = socket.listen(0).unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let mut queued = Vec::new();
+    for _ in 0..4 {
+      if let Ok(Ok(stream)) = tokio::time::timeout(Duration::from_millis(100), TcpStream::connect(("127.0.0.1", port))).await {
+        queued.push(stream);
+      }
+    }
+
+    let mut scanner = Scanner::new("127.0.0.1/32".to_string(), None).unwrap();
+    scanner.options.timeout = Duration::from_millis(100);
+    scanner.options.retries = 0;
+
+    let result = scan_tcp_port(Ipv4Addr::LOCALHOST.into(), port, &scanner.host_options(vec![port])).await;
+    assert_eq!(result.state, PortState::Filtered);
+
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let result = scan_tcp_port(Ipv4Addr::LOCALHOST.into(), closed, &scanner.host_options(vec![closed])).await;
+    assert_eq!(result.state, PortState::Closed);
+  }
+
+  #[tokio::test]
+  async fn test_scan_host_timeout() {
+    // The connection is accepted by the kernel but no banner ever comes
+    let silent = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = silent.local_addr().unwrap().port();
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let mut scanner = Scanner::builder()
+      .ip("127.0.0.1/32")
+      .ports(vec![port, closed])
+      .discovery(false)
+      .banner(true)
+      .concurrency(1)
+      .host_timeout(Duration::from_millis(100))
+      .quiet(true)
+      .build()
+      .unwrap();
+
+    let start = Instant::now();
+    scanner.scan().await;
+
+    assert!(start.elapsed() < BANNER_TIMEOUT, "{:?}", start.elapsed());
+    let result = &scanner.results()[0];
+    assert!(result.timed_out && result.open_ports.is_empty());
+    let mut unscanned = vec![port, closed];
+    unscanned.sort();
+    assert_eq!(result.unscanned_ports.iter().map(|result| result.port).collect::<Vec<u16>>(), unscanned);
+    assert!(result.unscanned_ports.iter().all(|result| result.state == PortState::Unknown));
+    assert_eq!(result.to_string(), "127.0.0.1: no open ports (timed out, 2 ports unscanned)\n");
+  }
+
+  #[tokio::test]
+  async fn test_scan_max_duration() {
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let mut scanner = Scanner::builder()
+      .ip("127.0.0.1-127.0.0.100")
+      .ports(vec![closed])
+      .discovery(false)
+      .rate(20)
+      .concurrency(2)
+      .max_duration(Duration::from_millis(200))
+      .quiet(true)
+      .build()
+      .unwrap();
+
+    let start = Instant::now();
+    assert!(!scanner.scan_until(std::future::pending::<()>()).await);
+
+    // The scan stops with the probes in progress, well before the grace period
+    assert!(start.elapsed() < INTERRUPT_GRACE, "{:?}", start.elapsed());
+    let summary = scanner.summary().unwrap();
+    assert!(summary.deadline_reached && !summary.interrupted);
+    assert!(summary.hosts_scanned > 0 && summary.hosts_scanned < 100, "{:?}", summary);
+    assert!(summary.to_string().ends_with(", stopped at the maximum duration"));
+  }
+
+  #[tokio::test]
+  async fn test_scan_through_proxy() {
+    let server = crate::proxy::spawn_test_proxy(23);
+    let proxy: Proxy = format!("socks5://user:secret@{}", server).parse().unwrap();
+    let mut scanner = Scanner::builder().ip("192.168.1.1/32").ports(vec![22, 23]).discovery(false).banner(true).proxy(proxy).quiet(true).build().unwrap();
+
+    scanner.scan().await;
+
+    let ports = &scanner.results()[0].open_ports;
+    assert_eq!(ports.iter().map(|result| result.port).collect::<Vec<u16>>(), vec![22]);
+    assert_eq!(ports[0].banner.as_deref(), Some("hello"));
+  }
+
+  #[tokio::test]
+  async fn test_scan_port_short_timeout() {
+    let port = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let start = std::time::Instant::now();
+    let is_open = scan_port(Ipv4Addr::LOCALHOST.into(), port, &ConnectTimeout::fixed(Duration::from_millis(10)), 0, None, &Source::default(), || async {}).await.is_ok();
+
+    assert!(!is_open);
+    assert!(start.elapsed() < Duration::from_millis(500));
+  }
+
+  #[tokio::test]
+  async fn test_with_retries_transient_failure() {
+    let mut attempts = 0;
+    let result = with_retries(1, Duration::ZERO, |_| {
+      attempts += 1;
+      let attempt = attempts;
+      async move {
+        if attempt == 1 {
+          Err(io::Error::from(ErrorKind::TimedOut))
+        } else {
+          Ok(attempt)
+        }
+      }
+    })
+    .await;
+
+    assert_eq!(result.unwrap(), 2);
+  }
+
+  #[tokio::test]
+  async fn test_with_retries_refused_not_retried() {
+    let mut attempts = 0;
+    let result: io::Result<()> = with_retries(3, Duration::ZERO, |_| {
+      attempts += 1;
+      async { Err(io::Error::from(ErrorKind::ConnectionRefused)) }
+    })
+    .await;
+
+    assert_eq!(result.unwrap_err().kind(), ErrorKind::ConnectionRefused);
+    assert_eq!(attempts, 1);
+  }
+
+  #[tokio::test]
+  async fn test_with_retries_gives_up() {
+    let mut attempts = 0;
+    let result: io::Result<()> = with_retries(2, Duration::ZERO, |_| {
+      attempts += 1;
+      async { Err(io::Error::from(ErrorKind::TimedOut)) }
+    })
+    .await;
+
+    assert!(result.is_err());
+    assert_eq!(attempts, 3);
+  }
+
+  #[tokio::test]
+  async fn test_with_retries_exhausted() {
+    let emfile = || io::Error::from_raw_os_error(libc::EMFILE);
+
+    // Waiting for file descriptors doesn't use the retries of the port
+    let mut attempts = Vec::new();
+    let result = with_retries(0, Duration::ZERO, |attempt| {
+      attempts.push(attempt);
+      let result = if attempts.len() < 3 { Err(emfile()) } else { Ok(attempt) };
+      async move { result }
+    })
+    .await;
+    assert_eq!(result.unwrap(), 0);
+    assert_eq!(attempts, vec![0, 0, 0]);
+
+    let mut count = 0;
+    let result: io::Result<()> = with_retries(1, Duration::ZERO, |_| {
+      count += 1;
+      async { Err(io::Error::from_raw_os_error(libc::EMFILE)) }
+    })
+    .await;
+    assert_eq!(count, EXHAUSTED_RETRIES + 1);
+
+    // Given up, the port is neither closed nor filtered
+    let err = result.unwrap_err();
+    assert!(is_exhausted(&err));
+    assert_eq!(failed_state(&err), PortState::Unknown);
+    assert_eq!(failed_state(&io::Error::from(ErrorKind::TimedOut)), PortState::Filtered);
+    assert_eq!(failed_state(&io::Error::from(ErrorKind::ConnectionRefused)), PortState::Closed);
+
+    // As much for a SYN as for a connection
+    let result = failed_result(22, &err);
+    assert_eq!(result.state, PortState::Unknown);
+    assert_eq!(result.error.as_deref(), Some(format!("connection failed: {}", err).as_str()));
+    assert_eq!(failed_result(22, &io::Error::from(ErrorKind::ConnectionRefused)).error, None);
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_scan_rate() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // With a single port and discovery disabled each result is a single connection
+    let mut scanner = Scanner::new("127.0.0.0/28".to_string(), Some(vec![port])).unwrap();
+    scanner.options.discovery = false;
+    scanner.options.rate = Some(10);
+
+    let start = std::time::Instant::now();
+    let mut receiver = scanner.scan_stream();
+    for _ in 0..10 {
+      receiver.recv().await.unwrap();
+    }
+
+    let elapsed = start.elapsed();
+    assert!(elapsed >= Duration::from_millis(850), "{:?}", elapsed);
+    assert!(elapsed < Duration::from_millis(1500), "{:?}", elapsed);
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_scan_pps() {
+    // Bound to every address so each host of 127.0.0.0/8 has the port open
+    let listener = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let accepts = std::thread::spawn(move || (0..30).map(|_| listener.accept().map(|_| Instant::now()).unwrap()).collect::<Vec<Instant>>());
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1-127.0.0.30").ports(vec![port]).discovery(false).concurrency(64).pps(40).quiet(true).build().unwrap();
+    let start = Instant::now();
+    scanner.scan().await;
+    let elapsed = start.elapsed();
+    let accepts = accepts.join().unwrap();
+
+    // 30 probes at 40 per second, the first one being immediate
+    assert!(elapsed >= Duration::from_millis(700), "{:?}", elapsed);
+    // The probes of every host share the budget, at most 10 in any quarter of a second
+    for (index, accepted) in accepts.iter().enumerate() {
+      let window = accepts[index..].iter().take_while(|other| other.duration_since(*accepted) < Duration::from_millis(250)).count();
+      assert!(window <= 11, "{} probes in 250ms", window);
+    }
+  }
+
+  #[tokio::test]
+  async fn test_is_port_open() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let open = listener.local_addr().unwrap().port();
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let localhost = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    assert_eq!(Scanner::is_port_open(localhost, open, Duration::from_secs(1)).await, Ok(true));
+    assert_eq!(Scanner::is_port_open(localhost, closed, Duration::from_secs(1)).await, Ok(false));
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_scan_with_cancel() {
+    // The connections complete in the backlog, nothing needs to accept them
+    let listener = std::net::TcpListener::bind("0.0.0.0:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    // 40 probes at 20 per second would take 2s
+    let mut scanner = Scanner::builder().ip("127.0.0.1-127.0.0.40").ports(vec![port]).discovery(false).pps(20).quiet(true).build().unwrap();
+    let cancel = CancelToken::new();
+    let canceller = cancel.clone();
+    tokio::spawn(async move {
+      tokio::time::sleep(Duration::from_millis(300)).await;
+      canceller.cancel();
+    });
+
+    let start = Instant::now();
+    assert!(scanner.scan_with_cancel(cancel).await);
+    assert!(start.elapsed() < Duration::from_secs(1), "{:?}", start.elapsed());
+
+    // The hosts probed before the cancellation are kept, the probes waiting for their turn being unscanned
+    let summary = scanner.summary().unwrap();
+    assert!(summary.interrupted);
+    assert!((1..40).contains(&summary.open_ports), "{:?}", summary);
+    assert!(scanner.results().iter().any(|result| !result.unscanned_ports.is_empty() && !result.timed_out));
+    drop(listener);
+  }
+
+  #[tokio::test]
+  async fn test_probe_port_confirm() {
+    let stable = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let flapping = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let ports = vec![stable.local_addr().unwrap().port(), flapping.local_addr().unwrap().port()];
+    // Closed once its first connection is accepted, the ports per second leaving it the time to close before the second probe
+    std::thread::spawn(move || drop(flapping.accept()));
+
+    let scanner = Scanner::builder().ip("127.0.0.1").ports(ports.clone()).confirm(true).pps(5).quiet(true).build().unwrap();
+    let options = scanner.host_options(ports.clone());
+    let ip = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    let result = probe_port(ip, Protocol::Tcp, ports[0], &options).await;
+    assert_eq!((result.state, result.unstable), (PortState::Open, false));
+    let result = probe_port(ip, Protocol::Tcp, ports[1], &options).await;
+    assert_eq!((result.state, result.unstable), (PortState::Open, true));
+  }
+
+  #[tokio::test]
+  async fn test_scan_tcp_port_latency() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let scanner = Scanner::new("127.0.0.1/32".to_string(), None).unwrap();
+
+    let result = scan_tcp_port(Ipv4Addr::LOCALHOST.into(), port, &scanner.host_options(vec![port])).await;
+
+    assert_eq!(result.state, PortState::Open);
+    assert!(result.latency.unwrap() < DEFAULT_TIMEOUT);
+  }
+
+  #[test]
+  fn test_report_show_latency() {
+    let mut scanner = Scanner::new("192.168.1.1".to_string(), None).unwrap();
+    scanner.show_latency = true;
+    scanner.result = vec![IpScanResult {
+      open_ports: vec![PortResult { latency: Some(Duration::from_micros(1500)), ..PortResult::new(22, Protocol::Tcp, PortState::Open) }],
+      ..IpScanResult::new(scanner.ip)
+    }];
+
+    assert!(scanner.report().contains("22/tcp 1.50ms\n"), "{}", scanner.report());
+  }
+
+  #[tokio::test]
+  async fn test_scan_meta() {
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+    let mut scanner = Scanner::builder().ip("127.0.0.1").ports(vec![closed]).timeout(Duration::from_millis(300)).concurrency(8).discovery(false).quiet(true).build().unwrap();
+    assert!(scanner.meta().is_none());
+    scanner.scan().await;
+
+    let json: serde_json::Value = serde_json::from_str(&scanner.serialize(OutputFormat::Json).unwrap()).unwrap();
+    let meta = &json["meta"];
+    assert_eq!(meta["version"], env!("CARGO_PKG_VERSION"));
+    assert!(meta["startedAt"].as_str().unwrap().ends_with('Z'), "{}", meta);
+    assert_eq!(meta["target"], "127.0.0.1");
+    assert_eq!(meta["ports"], closed.to_string());
+    assert_eq!(meta["timeoutMs"], 300);
+    assert_eq!(meta["concurrency"], 8);
+    // First in the report
+    assert!(scanner.serialize(OutputFormat::Json).unwrap().starts_with("{\n  \"meta\": {"));
+    assert!(scanner.serialize(OutputFormat::Yaml).unwrap().starts_with("meta:\n  version: "));
+
+    let report = scanner.report();
+    assert!(report.starts_with(&format!("# skanner {}\n# Started: ", env!("CARGO_PKG_VERSION"))), "{}", report);
+    assert!(report.contains(&format!("# Target: 127.0.0.1\n# Ports: {}\n# Timeout: 300ms\n# Concurrency: 8\nScanner for 127.0.0.1\n", closed)), "{}", report);
+
+    // Read back with the results
+    let results = ScanResults::parse(&scanner.serialize(OutputFormat::Json).unwrap(), OutputFormat::Json).unwrap();
+    assert_eq!(results.meta.as_ref(), scanner.meta());
+  }
+
+  #[test]
+  fn test_text_report_color() {
+    let mut scanner = scanner_with_results();
+    scanner.show_all = true;
+    scanner.result[1].filtered_ports = vec![PortResult::new(443, Protocol::Tcp, PortState::Filtered)];
+
+    // With --no-color, as when not printed to a terminal, the report is plain
+    let plain = scanner.text_report(crate::color::enabled(true));
+    assert!(!plain.contains('\x1b'), "{}", plain);
+    assert_eq!(plain, scanner.report());
+
+    let colored = scanner.text_report(true);
+    assert!(colored.contains("\x1b[32m80/tcp [http 200 \"Router\"]\x1b[0m"), "{}", colored);
+    assert!(colored.contains("\x1b[33m53/udp (open|filtered)\x1b[0m"), "{}", colored);
+    assert!(colored.contains("\x1b[33m443/tcp (filtered)\x1b[0m"), "{}", colored);
+    // The columns stay aligned, the colors being around the ports only
+    let strip = |line: &str| line.replace("\x1b[32m", "").replace("\x1b[33m", "").replace("\x1b[0m", "");
+    assert_eq!(strip(&colored), plain);
+  }
+
+  #[tokio::test]
+  async fn test_scan_udp_port_open() {
+    let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let port = server.local_addr().unwrap().port();
+
+    let handle = std::thread::spawn(move || {
+      let mut buffer = [0_u8; 512];
+      let (_, from) = server.recv_from(&mut buffer).unwrap();
+      server.send_to(b"pong", from).unwrap();
+    });
+
+    assert_eq!(scan_udp_port(Ipv4Addr::LOCALHOST.into(), port, DEFAULT_TIMEOUT, &Source::default()).await, PortState::Open);
+    handle.join().unwrap();
+  }
+
+  #[tokio::test]
+  async fn test_scan_udp_port_closed() {
+    // Bind then drop a socket to get a port nothing listens on
+    let port = std::net::UdpSocket::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    assert_eq!(scan_udp_port(Ipv4Addr::LOCALHOST.into(), port, DEFAULT_TIMEOUT, &Source::default()).await, PortState::Closed);
+  }
+
+  #[tokio::test]
+  async fn test_scan_port_protocols() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let tcp = listener.local_addr().unwrap().port();
+    let server = std::net::UdpSocket::bind("127.0.0.1:0").unwrap();
+    let udp = server.local_addr().unwrap().port();
+
+    let handle = std::thread::spawn(move || {
+      let mut buffer = [0_u8; 512];
+      let (_, from) = server.recv_from(&mut buffer).unwrap();
+      server.send_to(b"pong", from).unwrap();
+    });
+
+    let port_protocols = BTreeMap::from([(tcp, vec![Protocol::Tcp]), (udp, vec![Protocol::Udp])]);
+    let mut scanner = Scanner::builder().ip("127.0.0.1/32").ports(vec![tcp, udp]).port_protocols(port_protocols).discovery(false).quiet(true).build().unwrap();
+    assert_eq!(scanner.probes(), vec![(Protocol::Tcp, tcp), (Protocol::Udp, udp)]);
+    scanner.scan().await;
+    handle.join().unwrap();
+
+    let open = scanner.results()[0].open_ports.iter().map(|port| (port.protocol, port.port)).collect::<Vec<(Protocol, u16)>>();
+    assert_eq!(open, vec![(Protocol::Tcp, tcp), (Protocol::Udp, udp)]);
+    // Neither port was probed with the other protocol, that would have been refused
+    assert!(scanner.connection_errors().is_empty(), "{}", scanner.connection_errors());
+    assert!(scanner.report().contains(&format!("{}/tcp, {}/udp", tcp, udp)), "{}", scanner.report());
+  }
+
+  #[test]
+  fn test_new_invalid_ip() {
+    let error = Scanner::new("192.168.1.300".to_string(), None).unwrap_err();
+
+    assert_eq!(error, ScannerError::InvalidIp("192.168.1.300".to_string()));
+    assert_eq!(error.to_string(), "Invalid IP address: 192.168.1.300");
+  }
+
+  #[tokio::test]
+  async fn test_lookup_hostname() {
+    let scanner = Scanner::lookup(Target::Host("localhost".to_string()), None).await.unwrap();
+
+    assert_eq!(scanner.target(), "localhost");
+    assert!(scanner.get_ips().contains(&Ipv4Addr::LOCALHOST.into()), "{:?}", scanner.get_ips());
+  }
+
+  #[tokio::test]
+  async fn test_lookup_keeps_ips_and_networks() {
+    let scanner = Scanner::lookup("192.168.1.0/30".parse().unwrap(), None).await.unwrap();
+    assert_eq!(scanner.hostname, None);
+    assert_eq!(scanner.get_ips().len(), 2);
+  }
+
+  #[tokio::test]
+  async fn test_lookup_all() {
+    let targets = ["192.168.1.0/30", "10.1.1.5", "localhost"].map(|target| target.parse().unwrap()).to_vec();
+    let scanner = Scanner::lookup_all(targets, Some(vec![22])).await.unwrap();
+
+    assert_eq!(scanner.target(), "192.168.1.0/30, 10.1.1.5, localhost");
+    assert_eq!(scanner.ports, vec![22]);
+
+    let ips = scanner.get_ips();
+    assert_eq!(&ips[..3], &["192.168.1.1".parse::<IpAddr>().unwrap(), "192.168.1.2".parse().unwrap(), "10.1.1.5".parse().unwrap()]);
+    assert!(ips.contains(&Ipv4Addr::LOCALHOST.into()));
+  }
+
+  #[test]
+  fn test_ip_scan_result_order() {
+    let result = |ip: &str| IpScanResult::new(ip.parse().unwrap());
+
+    let mut results = ["10.0.0.10", "::1", "10.0.0.9", "9.255.255.255", "10.0.0.100", "10.0.1.0"].map(result).to_vec();
+    results.sort();
+
+    // 10.0.0.9 before 10.0.0.10, unlike the strings
+    assert_eq!(results.iter().map(|result| result.ip.to_string()).collect::<Vec<String>>(), vec!["9.255.255.255", "10.0.0.9", "10.0.0.10", "10.0.0.100", "10.0.1.0", "::1"]);
+
+    let mut down = result("10.0.0.9");
+    down.up = false;
+    assert!(down < result("10.0.0.9") && result("10.0.0.9") < result("10.0.0.10"));
+  }
+
+  #[test]
+  fn test_merge_results() {
+    let result = |ip: &str, up: bool, ports: &[u16]| IpScanResult {
+      up,
+      open_ports: ports.iter().map(|port| PortResult::new(*port, Protocol::Tcp, PortState::Open)).collect(),
+      ..IpScanResult::new(ip.parse().unwrap())
+    };
+
+    let mut filtered = result("192.168.1.10", true, &[]);
+    filtered.filtered_ports = vec![PortResult::new(80, Protocol::Tcp, PortState::Filtered), PortResult::new(8080, Protocol::Tcp, PortState::Filtered)];
+
+    let merged = merge_results(vec![
+      filtered,
+      result("192.168.1.10", true, &[443, 80]),
+      result("192.168.1.9", true, &[22]),
+      result("192.168.1.10", true, &[22, 80]),
+      result("192.168.1.2", false, &[]),
+      result("192.168.1.2", true, &[]),
+    ]);
+
+    assert_eq!(merged.iter().map(|result| result.ip.to_string()).collect::<Vec<String>>(), vec!["192.168.1.2", "192.168.1.9", "192.168.1.10"]);
+    assert!(merged[0].up);
+    assert_eq!(merged[2].open_ports.iter().map(|port| port.port).collect::<Vec<u16>>(), vec![22, 80, 443]);
+    assert_eq!(merged[2].filtered_ports.iter().map(|port| port.port).collect::<Vec<u16>>(), vec![8080]);
+
+    // The later result timed out, its unscanned ports kept unless another result scanned them
+    let mut timed_out = result("192.168.1.10", true, &[]);
+    timed_out.timed_out = true;
+    timed_out.unscanned_ports = [22, 3306, 8080, 9000].map(|port| PortResult::new(port, Protocol::Tcp, PortState::Unknown)).to_vec();
+    let merged = merge_results(vec![result("192.168.1.10", true, &[22]), timed_out]);
+
+    assert_eq!(merged.len(), 1);
+    assert!(merged[0].timed_out);
+    assert_eq!(merged[0].unscanned_ports.iter().map(|port| port.port).collect::<Vec<u16>>(), vec![3306, 8080, 9000]);
+  }
+
+  #[tokio::test]
+  async fn test_scan_overlapping_targets() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let mut scanner = Scanner::builder().ip("127.0.0.0/30").ip("127.0.0.1/32").ports(vec![port]).quiet(true).build().unwrap();
+    assert_eq!(scanner.get_ips().len(), 2);
+
+    scanner.scan().await;
+
+    let ips = scanner.results().iter().map(|result| result.ip.to_string()).collect::<Vec<String>>();
+    assert_eq!(ips, vec!["127.0.0.1", "127.0.0.2"]);
+    assert_eq!(scanner.results()[0].open_ports.len(), 1);
+  }
+
+  #[test]
+  fn test_combined_targets() {
+    let scanner = Scanner::builder().ip("10.0.0.0/30").ip("10.0.0.2-10.0.0.5").ip("10.0.0.4").ip("10.0.0.*").ip("10.0.0.0").quiet(true).build().unwrap();
+
+    // Every host once, in the order of the first target having it, the network address of 10.0.0.0/30 not being one of its hosts
+    let ips = scanner.get_ips();
+    assert_eq!(ips.len(), 255);
+    assert_eq!(&ips[..6], &["10.0.0.1", "10.0.0.2", "10.0.0.3", "10.0.0.4", "10.0.0.5", "10.0.0.6"].map(|ip| ip.parse::<IpAddr>().unwrap()));
+    assert_eq!(ips[254], "10.0.0.0".parse::<IpAddr>().unwrap());
+
+    // The hosts of a large network are enumerated as scanned, not listed once the targets are combined
+    let scanner = Scanner::builder().ip("10.0.0.0/8").ip("10.0.0.1").ip("192.168.1.1").quiet(true).build().unwrap();
+    assert!(scanner.combined.iter().all(|target| !matches!(target, TargetHosts::Addresses(_))));
+    assert_eq!(scanner.ips().take(2).collect::<Vec<IpAddr>>(), vec!["10.0.0.1".parse::<IpAddr>().unwrap(), "10.0.0.2".parse().unwrap()]);
+    assert_eq!(scanner.bounds().len(), 3);
+  }
+
+  #[tokio::test]
+  async fn test_scan_summary() {
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    let mut scanner = Scanner::builder().ip("127.0.0.0/30").ports(vec![port]).discovery(false).quiet(true).build().unwrap();
+    assert_eq!(scanner.summary(), None);
+    scanner.scan().await;
+
+    let summary = scanner.summary().unwrap().clone();
+    assert_eq!((summary.hosts_scanned, summary.hosts_up, summary.hosts_with_open_ports, summary.open_ports), (2, 2, 1, 1));
+
+    let footer = format!("=========================\n2 hosts scanned, 2 up, 1 with open ports, 1 open ports in {:.2}s\nConnection errors: refused: 1\n", summary.elapsed.as_secs_f64());
+    assert!(scanner.report().ends_with(&footer), "{}", scanner.report());
+
+    let json: serde_json::Value = serde_json::from_str(&scanner.serialize(OutputFormat::Json).unwrap()).unwrap();
+    assert_eq!(json["summary"]["hostsWithOpenPorts"], 1);
+    assert_eq!(json["summary"]["elapsedMs"], summary.elapsed.as_millis() as u64);
+    assert_eq!(json["summary"].get("interrupted"), None);
+  }
+
+  #[tokio::test]
+  async fn test_lookup_all_unresolved_hosts() {
+    let targets = ["unknown.invalid", "localhost", "other.invalid", "127.0.0.2"].map(|target| target.parse().unwrap()).to_vec();
+    let mut scanner = Scanner::lookup_all(targets, Some(vec![1])).await.unwrap();
+
+    // The scan goes on with the targets left, in their order
+    assert_eq!(scanner.target(), "localhost, 127.0.0.2");
+    assert!(scanner.get_ips().contains(&Ipv4Addr::LOCALHOST.into()), "{:?}", scanner.get_ips());
+    scanner.options.discovery = false;
+    scanner.quiet = true;
+    scanner.scan().await;
+    assert!(scanner.results().iter().any(|result| result.ip == IpAddr::V4(Ipv4Addr::new(127, 0, 0, 2))));
+
+    let targets = ["unknown.invalid", "other.invalid"].map(|target| target.parse().unwrap()).to_vec();
+    let error = Scanner::lookup_all(targets, None).await.unwrap_err();
+    assert!(matches!(error, ScannerError::Resolution { ref host, .. } if host == "unknown.invalid"), "{:?}", error);
+  }
+
+  #[tokio::test]
+  async fn test_lookup_unknown_host() {
+    let error = Scanner::lookup(Target::Host("unknown.invalid".to_string()), None).await.unwrap_err();
+
+    assert!(matches!(error, ScannerError::Resolution { ref host, .. } if host == "unknown.invalid"), "{:?}", error);
+  }
+
+  #[test]
+  fn test_builder() {
+    let scanner = Scanner::builder()
+      .ip("10.0.0.0/30")
+      .ports(vec![22])
+      .timeout(Duration::from_millis(200))
+      .concurrency(8)
+      .retries(0)
+      .discovery(false)
+      .quiet(true)
+      .build()
+      .unwrap();
+
+    assert_eq!(scanner.get_ips().len(), 2);
+    assert_eq!(scanner.ports, vec![22]);
+    assert_eq!(scanner.options.timeout, Duration::from_millis(200));
+    assert_eq!(scanner.options.concurrency, 8);
+    assert_eq!(scanner.options.retries, 0);
+    assert!(!scanner.options.discovery);
+    assert!(scanner.quiet);
+  }
+
+  #[test]
+  fn test_builder_defaults_and_errors() {
+    let scanner = Scanner::builder().ip("192.168.1.1").build().unwrap();
+    assert_eq!(scanner.ports, DEFAULT_PORTS);
+    assert_eq!(scanner.options.timeout, DEFAULT_TIMEOUT);
+    assert_eq!(scanner.options.concurrency, DEFAULT_CONCURRENCY);
+
+    assert_eq!(Scanner::builder().build().unwrap_err(), ScannerError::MissingTarget);
+    assert_eq!(Scanner::builder().ip("localhost").build().unwrap_err(), ScannerError::InvalidIp("localhost".to_string()));
+  }
+
+  #[test]
+  fn test_builder_denied_ports() {
+    // Dropped with a warning, the other ports still being scanned
+    let mut scanner = Scanner::builder().ip("192.168.1.1").ports(vec![22, 502, 80]).port_protocols(BTreeMap::from([(502, vec![Protocol::Udp])])).quiet(true).build().unwrap();
+    assert_eq!(scanner.ports, vec![22, 80]);
+    assert!(scanner.options.port_protocols.is_empty());
+    assert_eq!(scanner.remove_ports(Some(&[80]), DEFAULT_DENIED_PORTS), vec![22]);
+    assert_eq!(scanner.ports, vec![80]);
+
+    let scanner = Scanner::builder().ip("192.168.1.1").ports(vec![22, 502]).denied_ports(vec![22]).build().unwrap();
+    assert_eq!(scanner.ports, vec![502]);
+    let scanner = Scanner::builder().ip("192.168.1.1").ports(vec![22, 80, 443]).allowed_ports(vec![443, 80]).build().unwrap();
+    assert_eq!(scanner.ports, vec![80, 443]);
+
+    let error = Scanner::builder().ip("192.168.1.1").ports(vec![502]).build().unwrap_err();
+    assert_eq!(error, ScannerError::InvalidPorts("every port given is denied or not allowed".to_string()));
+  }
+
+  #[test]
+  fn test_builder_refuses_reserved_targets() {
+    let refused = |target: &str| Scanner::builder().ip(target).quiet(true).build().unwrap_err();
+
+    assert_eq!(refused("0.0.0.0"), ScannerError::ReservedTarget { network: "0.0.0.0/8".to_string(), kind: Reserved::ThisNetwork });
+    assert_eq!(refused("224.0.0.251"), ScannerError::ReservedTarget { network: "224.0.0.0/4".to_string(), kind: Reserved::Multicast });
+    assert_eq!(refused("255.255.255.255"), ScannerError::ReservedTarget { network: "255.255.255.255/32".to_string(), kind: Reserved::Broadcast });
+    assert_eq!(refused("ff02::1"), ScannerError::ReservedTarget { network: "ff00::/8".to_string(), kind: Reserved::Multicast });
+    // 223.x.x.x is public, but a network reaching past it has multicast addresses
+    assert!(Scanner::builder().ip("223.0.0.0").build().is_ok());
+    assert_eq!(refused("192.0.0.0/2"), ScannerError::ReservedTarget { network: "224.0.0.0/4".to_string(), kind: Reserved::Multicast });
+  }
+
+  #[test]
+  fn test_builder_allow_reserved() {
+    assert!(Scanner::builder().ip("239.255.255.250/32").allow_reserved(true).build().is_ok());
+    assert!(Scanner::builder().ip("127.0.0.1/32").quiet(true).build().is_ok());
+
+    let scanner = Scanner::builder().ip("0.0.0.0/0").exclude(vec!["0.0.0.0/8".parse().unwrap(), "224.0.0.0/3".parse().unwrap()]).quiet(true).build().unwrap();
+    assert_eq!(scanner.reserved_ranges(), vec![("127.0.0.0/8".parse().unwrap(), Reserved::Loopback)]);
+  }
+
+  #[test]
+  fn test_is_loopback() {
+    let loopback = |target: &str| Scanner::builder().ip(target).quiet(true).build().unwrap().is_loopback();
+
+    assert!(loopback("127.0.0.1"));
+    assert!(loopback("127.0.0.0/8"));
+    assert!(loopback("127.0.0.1-127.0.0.20"));
+    assert!(loopback("::1"));
+    assert!(Scanner::builder().ip("127.0.0.1").ip("::1").quiet(true).build().unwrap().is_loopback());
+
+    assert!(!loopback("192.168.1.1"));
+    assert!(!loopback("126.255.255.255-127.0.0.1"));
+    assert!(!Scanner::builder().ip("127.0.0.1").ip("10.0.0.1").quiet(true).build().unwrap().is_loopback());
+  }
+
+  #[test]
+  fn test_builder_loopback_timeout() {
+    let timeout = |builder: ScannerBuilder| builder.quiet(true).build().unwrap().options.timeout;
+
+    assert_eq!(timeout(Scanner::builder().ip("127.0.0.1")), LOOPBACK_TIMEOUT);
+    assert_eq!(timeout(Scanner::builder().ip("::1").options(ScanOptions::default())), LOOPBACK_TIMEOUT);
+    // A timeout set is kept, even the default one
+    assert_eq!(timeout(Scanner::builder().ip("127.0.0.1").timeout(DEFAULT_TIMEOUT)), DEFAULT_TIMEOUT);
+    assert_eq!(timeout(Scanner::builder().ip("127.0.0.1").options(ScanOptions { timeout: Duration::from_secs(5), ..ScanOptions::default() })), Duration::from_secs(5));
+    assert_eq!(timeout(Scanner::builder().ip("10.0.0.1")), DEFAULT_TIMEOUT);
+  }
+
+  #[test]
+  fn test_new_range() {
+    let scanner = Scanner::new("192.168.1.50-192.168.1.54".to_string(), None).unwrap();
+
+    assert_eq!(scanner.target(), "192.168.1.50-192.168.1.54");
+    assert_eq!(scanner.get_ips().len(), 5);
+    assert_eq!(scanner.get_ips()[0], "192.168.1.50".parse::<IpAddr>().unwrap());
+    assert_eq!(scanner.get_ips()[4], "192.168.1.54".parse::<IpAddr>().unwrap());
+  }
+
+  #[test]
+  fn test_new_wildcard() {
+    let scanner = Scanner::new("192.168.1.*".to_string(), None).unwrap();
+
+    assert_eq!(scanner.target(), "192.168.1.*");
+    assert_eq!(scanner.get_ips().len(), 254);
+    assert_eq!(scanner.get_ips()[0], "192.168.1.1".parse::<IpAddr>().unwrap());
+    assert_eq!(scanner.get_ips()[253], "192.168.1.254".parse::<IpAddr>().unwrap());
+
+    let error = Scanner::new("192.168.1.x*".to_string(), None).unwrap_err();
+    assert_eq!(error.to_string(), "Invalid wildcard target: 192.168.1.x*, x* is neither an octet nor *");
+  }
+
+  #[test]
+  fn test_new_inverted_range() {
+    let error = Scanner::new("192.168.1.90-192.168.1.50".to_string(), None).unwrap_err();
+
+    assert_eq!(error, ScannerError::InvalidRange("192.168.1.90-192.168.1.50, the start comes after the end".to_string()));
+    assert_eq!(error.to_string(), "Invalid IP range: 192.168.1.90-192.168.1.50, the start comes after the end");
+  }
+
+  #[test]
+  fn test_range_reserved_and_excluded() {
+    let refused = Scanner::builder().ip("223.255.255.250-224.0.0.5").quiet(true).build().unwrap_err();
+    assert!(matches!(refused, ScannerError::ReservedTarget { kind: Reserved::Multicast, .. }), "{:?}", refused);
+
+    let scanner = Scanner::builder().ip("10.0.0.1-10.0.0.4").exclude(vec!["10.0.0.2/32".parse().unwrap()]).build().unwrap();
+    assert_eq!(scanner.get_ips().iter().map(|ip| ip.to_string()).collect::<Vec<String>>(), vec!["10.0.0.1", "10.0.0.3", "10.0.0.4"]);
+  }
+
+  #[test]
+  fn test_new_invalid_cidr() {
+    let error = Scanner::new("192.168.1.0/40".to_string(), None).unwrap_err();
+
+    assert_eq!(error, ScannerError::InvalidCidr("192.168.1.0/40".to_string()));
+  }
+
+  /// Scanner with results using every field of a report
+  fn scanner_with_results() -> Scanner {
+    let mut scanner = Scanner::new("192.168.1.0/30".to_string(), Some(vec![22, 53, 80, 443])).unwrap();
+    scanner.result = vec![
+      IpScanResult {
+        hostname: Some("router.lan".to_string()),
+        open_ports: vec![
+          PortResult {
+            latency: Some(Duration::from_millis(2)),
+            banner: Some("SSH-2.0-OpenSSH_8.9p1".to_string()),
+            service: fingerprint::identify("SSH-2.0-OpenSSH_8.9p1"),
+            ..PortResult::new(22, Protocol::Tcp, PortState::Open)
+          },
+          PortResult { http: Some(http::HttpInfo { status: 200, title: Some("Router".to_string()), location: None }), ..PortResult::new(80, Protocol::Tcp, PortState::Open) },
+          PortResult { tls: Some(tls::TlsInfo { common_name: Some("router.lan".to_string()), not_after: None }), ..PortResult::new(443, Protocol::Tcp, PortState::Open) },
+          PortResult::new(53, Protocol::Udp, PortState::OpenFiltered),
+        ],
+        ..IpScanResult::new("192.168.1.1".parse().unwrap())
+      },
+      IpScanResult {
+        filtered_ports: vec![PortResult::new(22, Protocol::Tcp, PortState::Filtered)],
+        timed_out: true,
+        unscanned_ports: vec![PortResult::new(80, Protocol::Tcp, PortState::Unknown)],
+        ..IpScanResult::new("192.168.1.2".parse().unwrap())
+      },
+    ];
+    scanner.summary = Some(ScanSummary { interrupted: true, ..ScanSummary::new(&scanner.result, Duration::from_millis(1234)) });
+
+    scanner
+  }
+
+  #[test]
+  fn test_results_round_trip() {
+    let scanner = scanner_with_results();
+
+    for format in [OutputFormat::Json, OutputFormat::Yaml] {
+      let results = ScanResults::parse(&scanner.serialize(format).unwrap(), format).unwrap();
+
+      assert_eq!(results.ip, scanner.ip);
+      assert_eq!(results.prefix, Some(30));
+      assert_eq!(results.ports, vec![22, 53, 80, 443]);
+      assert_eq!(results.results, scanner.result);
+      assert_eq!(results.summary.as_ref(), scanner.summary());
+
+      // Read back, the results are reported the same
+      let reimported = Scanner::from(results);
+      assert_eq!(reimported.report(), scanner.report());
+      assert_eq!(reimported.serialize(format).unwrap(), scanner.serialize(format).unwrap());
+    }
+  }
+
+  #[test]
+  fn test_results_appended_reports() {
+    let scanner = scanner_with_results();
+    let mut appended = Scanner::new("10.0.0.1/32".to_string(), None).unwrap().serialize_entry(OutputFormat::Json, "2026-10-13T10:00:00Z", true).unwrap();
+    appended.push_str(&scanner.serialize_entry(OutputFormat::Json, "2026-10-14T10:00:00Z", false).unwrap());
+
+    let results = ScanResults::parse(&appended, OutputFormat::Json).unwrap();
+
+    assert_eq!(results.results, scanner.result);
+  }
+
+  #[test]
+  fn test_results_invalid_reports() {
+    assert_eq!(ScanResults::parse("", OutputFormat::Json).unwrap_err(), ScannerError::InvalidReport { path: None, message: "empty report".to_string() });
+    assert!(ScanResults::parse("{\"ip\": \"192.168.1.1\"}", OutputFormat::Json).is_err());
+    assert!(ScanResults::parse("IP,Port", OutputFormat::Csv).is_err());
+
+    let error = ScanResults::read("/nonexistent/report.json").unwrap_err();
+    assert!(matches!(error, ScannerError::InvalidReport { path: Some(_), .. }), "{:?}", error);
+  }
+
+  #[test]
+  fn test_serialize_json() {
+    #[derive(serde::Deserialize)]
+    struct JsonResult {
+      ip: IpAddr,
+      #[serde(rename = "openPorts")]
+      open_ports: Vec<JsonPort>,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct JsonPort {
+      port: u16,
+      protocol: String,
+      state: String,
+    }
+
+    #[derive(serde::Deserialize)]
+    struct JsonScanner {
+      ip: IpAddr,
+      ports: Vec<u16>,
+      result: Vec<JsonResult>,
     }
+
+    let mut scanner = Scanner::new("192.168.1.1".to_string(), Some(vec![22, 53])).unwrap();
+    scanner.result = vec![IpScanResult {
+      open_ports: vec![PortResult::new(53, Protocol::Udp, PortState::OpenFiltered)],
+      ..IpScanResult::new(scanner.ip)
+    }];
+
+    let json = scanner.serialize(OutputFormat::Json).unwrap();
+    let parsed = serde_json::from_str::<JsonScanner>(&json).unwrap();
+
+    assert_eq!(parsed.ip, scanner.ip);
+    assert_eq!(parsed.ports, vec![22, 53]);
+    assert_eq!(parsed.result.len(), 1);
+    assert_eq!(parsed.result[0].ip, scanner.ip);
+    assert_eq!(parsed.result[0].open_ports[0].port, 53);
+    assert_eq!(parsed.result[0].open_ports[0].protocol, "udp");
+    assert_eq!(parsed.result[0].open_ports[0].state, "openFiltered");
+  }
+
+  #[test]
+  fn test_get_ips_ipv6_single_host() {
+    let scanner = Scanner::new("fd00::".to_string(), None).unwrap();
+
+    assert_eq!(scanner.get_ips(), vec!["fd00::".parse::<IpAddr>().unwrap()]);
+  }
+
+  #[test]
+  fn test_get_ips_ipv6_cidr() {
+    let scanner = Scanner::new("fd00::/120".to_string(), None).unwrap();
+    let ips = scanner.get_ips();
+
+    assert_eq!(ips.len(), 255);
+    assert_eq!(ips[0], "fd00::1".parse::<IpAddr>().unwrap());
+    assert_eq!(ips[254], "fd00::ff".parse::<IpAddr>().unwrap());
+  }
+
+  #[test]
+  fn test_new_ipv6_network_too_large() {
+    let error = Scanner::new("fd00::/64".to_string(), None).unwrap_err();
+
+    assert_eq!(error, ScannerError::NetworkTooLarge("fd00::/64".to_string()));
+  }
+
+  #[tokio::test]
+  async fn test_scan_port_ipv6() {
+    let listener = std::net::TcpListener::bind("[::1]:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+
+    assert!(scan_port(Ipv6Addr::LOCALHOST.into(), port, &ConnectTimeout::fixed(DEFAULT_TIMEOUT), 0, None, &Source::default(), || async {}).await.is_ok());
+  }
+
+  #[test]
+  fn test_report_skips_closed_hosts() {
+    let mut scanner = Scanner::new("192.168.1.0/30".to_string(), None).unwrap();
+    scanner.result = scanner.get_ips().into_iter().map(IpScanResult::new).collect();
+
+    assert_eq!(scanner.report(), "Scanner for 192.168.1.0/30\nPorts: [22, 80, 443, 8080]\n=========================\n");
+  }
+
+  #[test]
+  fn test_report_show_all() {
+    let mut scanner = Scanner::new("192.168.1.0/30".to_string(), None).unwrap();
+    scanner.show_all = true;
+    scanner.result = scanner.get_ips().into_iter().map(IpScanResult::new).collect();
+
+    let report = scanner.report();
+
+    assert!(report.contains("192.168.1.1: no open ports\n"));
+    assert!(report.contains("192.168.1.2: no open ports\n"));
+  }
+
+  #[tokio::test]
+  async fn test_is_host_up_refused() {
+    // Nothing listens on the discovery ports of the loopback but it answers with resets
+    let scanner = Scanner::new("127.0.0.1/32".to_string(), None).unwrap();
+
+    assert!(is_host_up(Ipv4Addr::LOCALHOST.into(), &Arc::new(scanner.host_options(vec![]))).await);
+  }
+
+  #[tokio::test(flavor = "multi_thread")]
+  async fn test_is_host_up_concurrent() {
+    // A proxy never answering, so every discovery port waits for the whole timeout
+    let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = proxy.local_addr().unwrap();
+    std::thread::spawn(move || {
+      let streams = proxy.incoming().collect::<Vec<_>>();
+      drop(streams);
+    });
+
+    let timeout = Duration::from_millis(300);
+    let scanner = Scanner::builder().ip("10.0.0.1").timeout(timeout).proxy(format!("socks5://{}", address).parse().unwrap()).build().unwrap();
+    let start = std::time::Instant::now();
+    assert!(!is_host_up("10.0.0.1".parse().unwrap(), &Arc::new(scanner.host_options(vec![]))).await);
+
+    // A host down costs one timeout, not one per discovery port
+    assert!(start.elapsed() < timeout * 2, "{:?}", start.elapsed());
+  }
+
+  #[tokio::test]
+  async fn test_is_host_up_concurrency() {
+    // A proxy never answering, counting the connections made to it
+    let proxy = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = proxy.local_addr().unwrap();
+    let accepted = Arc::new(AtomicUsize::new(0));
+    let counted = accepted.clone();
+    std::thread::spawn(move || {
+      let streams = proxy.incoming().inspect(|_| _ = counted.fetch_add(1, Ordering::SeqCst)).collect::<Vec<_>>();
+      drop(streams);
+    });
+
+    let timeout = Duration::from_millis(300);
+    let scanner = Scanner::builder().ip("10.0.0.1").timeout(timeout).concurrency(2).proxy(format!("socks5://{}", address).parse().unwrap()).build().unwrap();
+    let options = Arc::new(scanner.host_options(vec![]));
+    let discovery = tokio::spawn(async move { is_host_up("10.0.0.1".parse().unwrap(), &options).await });
+
+    // The discovery ports wait for their turn among the connections in flight
+    tokio::time::sleep(timeout / 2).await;
+    assert_eq!(accepted.load(Ordering::SeqCst), 2);
+    assert!(!discovery.await.unwrap());
+    assert_eq!(accepted.load(Ordering::SeqCst), DISCOVERY_PORTS.len());
+  }
+
+  #[test]
+  fn test_display_host_down() {
+    let result = IpScanResult { up: false, ..IpScanResult::new("192.168.1.1".parse().unwrap()) };
+
+    assert_eq!(result.to_string(), "192.168.1.1: host down\n");
+  }
+
+  #[test]
+  fn test_report_aligned() {
+    let mut scanner = Scanner::new("192.168.1.0/24".to_string(), Some(vec![22, 80, 443])).unwrap();
+    scanner.show_all = true;
+    scanner.result = vec![
+      IpScanResult {
+        hostname: Some("router.lan".to_string()),
+        open_ports: vec![PortResult::new(22, Protocol::Tcp, PortState::Open), PortResult::new(80, Protocol::Tcp, PortState::Open)],
+        ..IpScanResult::new("192.168.1.1".parse().unwrap())
+      },
+      IpScanResult {
+        open_ports: vec![PortResult::new(443, Protocol::Tcp, PortState::Open)],
+        ..IpScanResult::new("192.168.1.20".parse().unwrap())
+      },
+      IpScanResult::new("192.168.1.254".parse().unwrap()),
+      IpScanResult { up: false, ..IpScanResult::new("192.168.1.3".parse().unwrap()) },
+    ];
+
+    assert_eq!(
+      scanner.report(),
+      "Scanner for 192.168.1.0/24\n\
+       Ports: [22, 80, 443]\n\
+       =========================\n\
+       192.168.1.1 (router.lan): 22/tcp, 80/tcp\n\
+       192.168.1.20:             443/tcp\n\
+       192.168.1.254:            no open ports\n\
+       192.168.1.3:              host down\n"
+    );
+  }
+
+  #[test]
+  fn test_report_filtered_ports() {
+    let mut scanner = Scanner::new("192.168.1.0/30".to_string(), None).unwrap();
+    scanner.result = vec![
+      IpScanResult {
+        open_ports: vec![PortResult::new(22, Protocol::Tcp, PortState::Open)],
+        filtered_ports: vec![PortResult::new(8080, Protocol::Tcp, PortState::Filtered)],
+        ..IpScanResult::new("192.168.1.1".parse().unwrap())
+      },
+      IpScanResult {
+        filtered_ports: vec![PortResult::new(443, Protocol::Tcp, PortState::Filtered)],
+        ..IpScanResult::new("192.168.1.2".parse().unwrap())
+      },
+    ];
+
+    assert!(scanner.report().ends_with("=========================\n192.168.1.1: 22/tcp\n"), "{}", scanner.report());
+
+    scanner.show_all = true;
+    assert!(scanner.report().contains("192.168.1.1: 22/tcp, 8080/tcp (filtered)\n192.168.1.2: 443/tcp (filtered)\n"), "{}", scanner.report());
+  }
+
+  #[test]
+  fn test_display_hostname() {
+    let result = IpScanResult {
+      hostname: Some("router.lan".to_string()),
+      ..IpScanResult::new("192.168.1.1".parse().unwrap())
+    };
+
+    assert_eq!(result.to_string(), "192.168.1.1 (router.lan): no open ports\n");
+  }
+
+  #[test]
+  fn test_get_ips_exclude() {
+    let mut scanner = Scanner::new("192.168.1.0/24".to_string(), None).unwrap();
+    scanner.options.exclude = vec![crate::cidr::parse_network("192.168.1.1").unwrap()];
+
+    let ips = scanner.get_ips();
+
+    assert_eq!(ips.len(), 253);
+    assert!(!ips.contains(&"192.168.1.1".parse::<IpAddr>().unwrap()));
+    assert_eq!(scanner.count_ips(), (253, 1));
+  }
+
+  #[test]
+  fn test_get_ips_exclude_network() {
+    let mut scanner = Scanner::new("192.168.1.0/24".to_string(), None).unwrap();
+    scanner.options.exclude = vec![
+      crate::cidr::parse_network("192.168.1.0/25").unwrap(),
+      crate::cidr::parse_network("192.168.1.254").unwrap(),
+    ];
+
+    let ips = scanner.get_ips();
+
+    assert_eq!(ips.len(), 126);
+    assert_eq!(ips[0], "192.168.1.128".parse::<IpAddr>().unwrap());
+    assert_eq!(ips[125], "192.168.1.253".parse::<IpAddr>().unwrap());
+  }
+
+  #[test]
+  fn test_ordered_randomize_seed() {
+    let mut scanner = Scanner::new("192.168.1.0/24".to_string(), Some((1..=100).collect())).unwrap();
+    scanner.options.randomize = true;
+    scanner.options.randomize_ports = true;
+    scanner.options.seed = Some(42);
+
+    let (ips, ports) = scanner.ordered();
+    let (same_ips, same_ports) = scanner.ordered();
+    let (ips, same_ips) = (ips.collect::<Vec<IpAddr>>(), same_ips.collect::<Vec<IpAddr>>());
+
+    assert_eq!(ips, same_ips);
+    assert_eq!(ports, same_ports);
+    assert_ne!(ips, scanner.get_ips());
+    assert_ne!(ports, (1..=100).collect::<Vec<u16>>());
+
+    let mut sorted_ips = ips.clone();
+    sorted_ips.sort();
+    assert_eq!(sorted_ips, scanner.get_ips());
+  }
+
+  #[tokio::test]
+  async fn test_port_order() {
+    let listeners = (0..3).map(|_| std::net::TcpListener::bind("127.0.0.1:0").unwrap()).collect::<Vec<_>>();
+    let mut ports = listeners.iter().map(|listener| listener.local_addr().unwrap().port()).collect::<Vec<u16>>();
+    ports.sort_by(|a, b| b.cmp(a));
+    ports.extend([443, 22]);
+
+    let mut scanner = Scanner::builder().ip("127.0.0.1/32").ports(ports.clone()).port_order(PortOrder::Common).discovery(false).quiet(true).build().unwrap();
+    assert_eq!(scanner.ordered().1[..2], [443, 22]);
+
+    // The report is sorted whatever the order the ports were probed in
+    scanner.scan().await;
+    let mut open = ports[..3].to_vec();
+    open.sort();
+    assert_eq!(scanner.results()[0].open_ports.iter().map(|port| port.port).collect::<Vec<u16>>(), open);
+
+    scanner.options.port_order = Some(PortOrder::Numeric);
+    ports.sort();
+    assert_eq!(scanner.ordered().1, ports);
+  }
+
+  #[test]
+  fn test_ordered_without_randomize() {
+    let scanner = Scanner::new("192.168.1.0/24".to_string(), Some(vec![443, 22, 80])).unwrap();
+
+    let (ips, ports) = scanner.ordered();
+
+    assert_eq!(ips.collect::<Vec<IpAddr>>(), scanner.get_ips());
+    // Sorted when resolved
+    assert_eq!(ports, vec![22, 80, 443]);
+  }
+
+  #[test]
+  fn test_write_plan() {
+    let mut scanner = Scanner::builder().ip("192.168.1.0/30").ports(vec![443, 22]).exclude(vec!["192.168.1.2/32".parse().unwrap()]).build().unwrap();
+
+    let mut plan = Vec::new();
+    assert_eq!(scanner.write_plan(&mut plan).unwrap(), 1);
+    assert_eq!(
+      String::from_utf8(plan).unwrap(),
+      "# Target: 192.168.1.0/30\n# IPs: 1\n# Excluded: 1\n# Protocols: tcp\n# Ports: 22,443\n192.168.1.1\n"
+    );
+  }
+
+  #[test]
+  fn test_write_plan_randomized_stable() {
+    let plan = |seed: u64| {
+      let mut scanner = Scanner::builder().ip("10.0.0.0/24").ports((1..=20).collect()).randomize(true).randomize_ports(true).seed(seed).build().unwrap();
+      let mut plan = Vec::new();
+      scanner.write_plan(&mut plan).unwrap();
+      String::from_utf8(plan).unwrap()
+    };
+
+    assert_eq!(plan(7), plan(7));
+    assert_ne!(plan(7), plan(8));
+    assert!(plan(7).contains("# Seed: 7\n"));
+  }
+
+  #[test]
+  fn test_write_plan_picks_seed() {
+    let mut scanner = Scanner::builder().ip("10.0.0.0/29").randomize(true).build().unwrap();
+
+    scanner.write_plan(&mut Vec::new()).unwrap();
+
+    assert!(scanner.options.seed.is_some());
+  }
+
+  #[tokio::test]
+  async fn test_scan_randomized_results_sorted() {
+    let mut scanner = Scanner::new("127.0.0.0/28".to_string(), Some(vec![1])).unwrap();
+    scanner.options.discovery = false;
+    scanner.quiet = true;
+    scanner.options.randomize = true;
+    scanner.options.timeout = Duration::from_millis(50);
+
+    scanner.scan().await;
+
+    let ips = scanner.results().iter().map(|result| result.ip).collect::<Vec<IpAddr>>();
+    assert_eq!(ips, scanner.get_ips());
+    assert!(scanner.options.seed.is_some());
+  }
+
+  #[test]
+  fn test_ips_lazy() {
+    let scanner = Scanner::new("10.0.0.0/8".to_string(), None).unwrap();
+    let mut ips = scanner.ips();
+
+    assert_eq!(ips.next(), Some("10.0.0.1".parse::<IpAddr>().unwrap()));
+    assert_eq!(ips.next(), Some("10.0.0.2".parse::<IpAddr>().unwrap()));
+    assert_eq!(scanner.ips().nth(65_535), Some("10.1.0.0".parse::<IpAddr>().unwrap()));
+  }
+
+  #[test]
+  fn test_get_ips_cidr() {
+    let scanner = Scanner::new("192.168.1.0/26".to_string(), None).unwrap();
+    let ips = scanner.get_ips();
+
+    assert_eq!(ips.len(), 62);
+    assert_eq!(ips[0], "192.168.1.1".parse::<IpAddr>().unwrap());
+    assert_eq!(ips[61], "192.168.1.62".parse::<IpAddr>().unwrap());
+  }
+
+  #[test]
+  fn test_get_ips_cidr_not_aligned() {
+    let scanner = Scanner::new("10.0.3.7/20".to_string(), None).unwrap();
+    let ips = scanner.get_ips();
+
+    assert_eq!(scanner.ip, "10.0.0.0".parse::<IpAddr>().unwrap());
+    assert_eq!(ips.len(), 4094);
+    assert_eq!(ips[0], "10.0.0.1".parse::<IpAddr>().unwrap());
+    assert_eq!(ips[4093], "10.0.15.254".parse::<IpAddr>().unwrap());
   }
 }
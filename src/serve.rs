@@ -0,0 +1,288 @@
+//! Web dashboard of a scan, to watch its results in a browser as the hosts are done
+//!
+//! Two pages are served over plain HTTP/1.1, one request per connection:
+//!
+//! - `/`, an HTML page polling `/results` every second and listing the hosts with open ports
+//! - `/results`, the results so far as JSON, `{target, ports, complete, results}`, each
+//!   result in the format of the JSON report
+//!
+//! The server listens from the start of the scan and is shut down once the scan stops.
+
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use serde::Serialize;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::oneshot;
+use tokio::task::{JoinHandle, JoinSet};
+
+use crate::error::ScannerError;
+use crate::scanner::{IpScanResult, Scanner};
+
+/// Maximum size of a request, the headers of a browser fitting in it
+const MAX_REQUEST_SIZE: usize = 8 * 1024;
+
+/// Time a client has to send its request
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Page of the dashboard, rendering `/results` with the DOM so banners can't inject markup
+const DASHBOARD_PAGE: &str = r#"<!DOCTYPE html>
+<html>
+<head>
+<meta charset="utf-8">
+<title>skanner</title>
+<style>
+  body { font-family: sans-serif; margin: 2em; }
+  table { border-collapse: collapse; }
+  th, td { text-align: left; padding: 0.2em 1em 0.2em 0; vertical-align: top; }
+  th { border-bottom: 1px solid #888; }
+</style>
+</head>
+<body>
+<h1 id="target">skanner</h1>
+<p id="status">Waiting for the results</p>
+<table>
+  <thead><tr><th>IP</th><th>Hostname</th><th>Open ports</th></tr></thead>
+  <tbody id="hosts"></tbody>
+</table>
+<script>
+  function cell(row, text) {
+    row.insertCell().textContent = text;
+  }
+
+  function port(port) {
+    let text = port.port + "/" + port.protocol;
+    if (port.state !== "open") text += " (" + port.state + ")";
+    if (port.service) text += " " + port.service.name;
+    if (port.banner) text += " [" + port.banner + "]";
+    return text;
+  }
+
+  async function poll() {
+    let live;
+    try {
+      live = await (await fetch("/results?t=" + Date.now())).json();
+    } catch (error) {
+      document.getElementById("status").textContent = "The scan is over, the dashboard is no longer served";
+      return;
+    }
+
+    document.getElementById("target").textContent = "Scan of " + live.target;
+    const hosts = live.results.filter(result => result.openPorts.length > 0);
+    const open = hosts.reduce((count, result) => count + result.openPorts.length, 0);
+    document.getElementById("status").textContent =
+      live.results.length + " hosts scanned, " + open + " open ports on " + hosts.length + " hosts" + (live.complete ? ", done" : ", in progress");
+
+    const body = document.getElementById("hosts");
+    body.replaceChildren();
+    for (const result of hosts) {
+      const row = body.insertRow();
+      cell(row, result.ip);
+      cell(row, result.hostname || "");
+      cell(row, result.openPorts.map(port).join(", "));
+    }
+
+    if (!live.complete) setTimeout(poll, 1000);
+  }
+
+  poll();
+</script>
+</body>
+</html>
+"#;
+
+/// Results of the scan in progress, as served at `/results`
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct LiveResults {
+  /// Target of the scan, as in its report
+  pub target: String,
+
+  /// Ports scanned on each host
+  pub ports: Vec<u16>,
+
+  /// Whether the scan stopped, the results being final
+  pub complete: bool,
+
+  /// Hosts done, in completion order
+  pub results: Vec<IpScanResult>,
+}
+
+/// Dashboard server running along a scan
+#[derive(Debug)]
+pub struct Dashboard {
+  address: SocketAddr,
+  results: Arc<Mutex<LiveResults>>,
+  shutdown: Option<oneshot::Sender<()>>,
+  server: JoinHandle<()>,
+}
+
+impl Dashboard {
+  /// Listen on `address` and serve the results of the scan of `scanner`
+  pub async fn start(address: SocketAddr, scanner: &Scanner) -> Result<Self, ScannerError> {
+    let listener = TcpListener::bind(address).await.map_err(|err| ScannerError::Serve { address, message: err.to_string() })?;
+    let address = listener.local_addr().map_err(|err| ScannerError::Serve { address, message: err.to_string() })?;
+
+    let results = Arc::new(Mutex::new(LiveResults { target: scanner.target(), ports: scanner.ports().to_vec(), complete: false, results: Vec::new() }));
+    let (shutdown, stopped) = oneshot::channel();
+    let server = tokio::spawn(serve(listener, results.clone(), stopped));
+    info!("Serving the live results on http://{}/", address);
+
+    Ok(Self { address, results, shutdown: Some(shutdown), server })
+  }
+
+  /// Address the dashboard listens on, with the port picked by the system when 0 was given
+  pub fn address(&self) -> SocketAddr {
+    self.address
+  }
+
+  /// Add a host done to the results served
+  pub fn add(&self, result: &IpScanResult) {
+    self.results.lock().unwrap().results.push(result.clone());
+  }
+
+  /// Mark the results complete and shut the server down, the requests in progress being dropped
+  pub async fn finish(mut self) {
+    self.results.lock().unwrap().complete = true;
+    if let Some(shutdown) = self.shutdown.take() {
+      let _ = shutdown.send(());
+    }
+    let _ = (&mut self.server).await;
+  }
+}
+
+/// Accept connections until shut down, each being handled on its own task
+async fn serve(listener: TcpListener, results: Arc<Mutex<LiveResults>>, mut stopped: oneshot::Receiver<()>) {
+  let mut connections = JoinSet::new();
+
+  loop {
+    tokio::select! {
+      _ = &mut stopped => break,
+      accepted = listener.accept() => match accepted {
+        Ok((stream, _)) => {
+          connections.spawn(handle(stream, results.clone()));
+        }
+        Err(err) => debug!("Unable to accept a dashboard connection: {}", err),
+      },
+      // Reap the connections done so the set doesn't grow for the whole scan
+      Some(_) = connections.join_next(), if !connections.is_empty() => {}
+    }
+  }
+
+  connections.shutdown().await;
+}
+
+/// Answer the request of a connection, then close it
+async fn handle(mut stream: TcpStream, results: Arc<Mutex<LiveResults>>) {
+  let Ok(Some(request)) = tokio::time::timeout(REQUEST_TIMEOUT, read_request(&mut stream)).await else { return };
+
+  let response = match route(&request, &results) {
+    Ok((content_type, body)) => response("200 OK", content_type, &body),
+    Err(status) => response(status, "text/plain; charset=utf-8", status),
+  };
+  let _ = stream.write_all(response.as_bytes()).await;
+  let _ = stream.shutdown().await;
+}
+
+/// Read the head of a request, `None` when the client closed the connection or sent too much
+async fn read_request(stream: &mut TcpStream) -> Option<String> {
+  let mut request = Vec::new();
+  let mut buffer = [0; 1024];
+
+  while !request.windows(4).any(|window| window == b"\r\n\r\n") {
+    let length = stream.read(&mut buffer).await.ok()?;
+    if length == 0 || request.len() + length > MAX_REQUEST_SIZE {
+      return None;
+    }
+    request.extend_from_slice(&buffer[..length]);
+  }
+
+  Some(String::from_utf8_lossy(&request).into_owned())
+}
+
+/// Content type and body of the page requested, or the status of the error
+fn route(request: &str, results: &Mutex<LiveResults>) -> Result<(&'static str, String), &'static str> {
+  let mut line = request.lines().next().unwrap_or_default().split_whitespace();
+  let (method, target) = (line.next().unwrap_or_default(), line.next().unwrap_or_default());
+  // The query string of a poll defeating a cache is ignored
+  let path = target.split('?').next().unwrap_or_default();
+
+  match (method, path) {
+    ("GET", "/") => Ok(("text/html; charset=utf-8", DASHBOARD_PAGE.to_string())),
+    ("GET", "/results") => serde_json::to_string(&*results.lock().unwrap()).map(|body| ("application/json", body)).map_err(|_| "500 Internal Server Error"),
+    (_, "/" | "/results") => Err("405 Method Not Allowed"),
+    _ => Err("404 Not Found"),
+  }
+}
+
+fn response(status: &str, content_type: &str, body: &str) -> String {
+  format!(
+    "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nCache-Control: no-store\r\nConnection: close\r\n\r\n{}",
+    status,
+    content_type,
+    body.len(),
+    body
+  )
+}
+
+/// Clap value parser for the address of the dashboard, a port alone listening on localhost only
+pub fn parse_serve_address(s: &str) -> Result<SocketAddr, String> {
+  if let Ok(port) = s.parse::<u16>() {
+    return Ok(SocketAddr::new(IpAddr::V4(Ipv4Addr::LOCALHOST), port));
+  }
+
+  s.parse::<SocketAddr>().map_err(|_| format!("{} is neither a port nor an address such as 0.0.0.0:8080", s))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  use crate::port::{PortResult, PortState, Protocol};
+
+  async fn get(address: SocketAddr, request: &str) -> String {
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+    response
+  }
+
+  #[test]
+  fn test_parse_serve_address() {
+    assert_eq!(parse_serve_address("8080"), Ok("127.0.0.1:8080".parse().unwrap()));
+    assert_eq!(parse_serve_address("0.0.0.0:8080"), Ok("0.0.0.0:8080".parse().unwrap()));
+    assert_eq!(parse_serve_address("[::1]:8080"), Ok("[::1]:8080".parse().unwrap()));
+    assert!(parse_serve_address("localhost").is_err());
+  }
+
+  #[tokio::test]
+  async fn test_dashboard() {
+    let scanner = Scanner::new("10.0.0.0/30".to_string(), Some(vec![22])).unwrap();
+    let dashboard = Dashboard::start("127.0.0.1:0".parse().unwrap(), &scanner).await.unwrap();
+    let address = dashboard.address();
+
+    let page = get(address, "GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").await;
+    assert!(page.starts_with("HTTP/1.1 200 OK\r\n"), "{}", page);
+    assert!(page.contains("text/html"));
+
+    let mut result = IpScanResult::new("10.0.0.1".parse().unwrap());
+    result.open_ports.push(PortResult::new(22, Protocol::Tcp, PortState::Open));
+    dashboard.add(&result);
+
+    let response = get(address, "GET /results?t=1 HTTP/1.1\r\n\r\n").await;
+    let body = response.split("\r\n\r\n").nth(1).unwrap();
+    let live: serde_json::Value = serde_json::from_str(body).unwrap();
+    assert_eq!(live["target"], "10.0.0.0/30");
+    assert_eq!(live["complete"], false);
+    assert_eq!(live["results"][0]["openPorts"][0]["port"], 22);
+
+    assert!(get(address, "POST /results HTTP/1.1\r\n\r\n").await.starts_with("HTTP/1.1 405"));
+    assert!(get(address, "GET /other HTTP/1.1\r\n\r\n").await.starts_with("HTTP/1.1 404"));
+
+    dashboard.finish().await;
+    assert!(TcpStream::connect(address).await.is_err());
+  }
+}
@@ -0,0 +1,270 @@
+//! Half-open TCP scan, sending a SYN on a raw socket and reading the answer
+//! without completing the handshake
+//!
+//! A SYN/ACK means the port is open, the kernel then resetting the connection
+//! it knows nothing about, and a RST that it is closed. Raw sockets need root
+//! or the `CAP_NET_RAW` capability, and only IPv4 hosts are scanned this way.
+
+use std::collections::HashMap;
+use std::io::{self, ErrorKind, Read};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::ops::RangeInclusive;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use clap::ValueEnum;
+use rand::Rng;
+use socket2::{Domain, Socket, Type};
+use tokio::sync::oneshot;
+
+use crate::connection::{self, Source};
+
+/// Ports the SYNs are sent from, one per scan
+pub const SOURCE_PORTS: RangeInclusive<u16> = 40000..=59999;
+
+/// Window advertised in the SYNs
+const WINDOW: u16 = 1024;
+
+/// Time the receiving thread waits for a packet before checking the scan is still running
+const READ_TIMEOUT: Duration = Duration::from_millis(100);
+
+const FIN: u8 = 0x01;
+const SYN: u8 = 0x02;
+const RST: u8 = 0x04;
+const ACK: u8 = 0x10;
+
+/// How the TCP ports are probed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, ValueEnum)]
+pub enum ScanType {
+  /// Full TCP connection, which needs no privileges
+  #[default]
+  Connect,
+
+  /// Half-open scan on a raw socket, needing root or `CAP_NET_RAW`
+  Syn,
+}
+
+/// Answer of a port to a SYN
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Answer {
+  SynAck,
+  Reset,
+}
+
+/// Probes waiting for an answer, by host and port
+type Pending = Mutex<HashMap<(Ipv4Addr, u16), oneshot::Sender<Answer>>>;
+
+/// Raw socket the SYNs of a scan are sent on, with a thread reading the answers
+#[derive(Debug)]
+pub struct SynScanner {
+  socket: Arc<Socket>,
+  source: Source,
+  source_port: u16,
+  sequence: u32,
+  pending: Arc<Pending>,
+  stopped: Arc<AtomicBool>,
+}
+
+impl SynScanner {
+  /// Open the raw socket, failing with `PermissionDenied` without the privileges for it
+  pub fn open(source: &Source) -> io::Result<Self> {
+    let socket = Socket::new(Domain::IPV4, Type::RAW, Some(socket2::Protocol::TCP))?;
+    if let Some(interface) = &source.interface {
+      connection::bind_device(&socket, interface)?;
+    }
+    socket.set_read_timeout(Some(READ_TIMEOUT))?;
+
+    let scanner = Self {
+      socket: Arc::new(socket),
+      source: source.clone(),
+      source_port: rand::thread_rng().gen_range(SOURCE_PORTS),
+      sequence: rand::random(),
+      pending: Arc::default(),
+      stopped: Arc::default(),
+    };
+
+    let (socket, pending, stopped) = (scanner.socket.clone(), scanner.pending.clone(), scanner.stopped.clone());
+    let (port, sequence) = (scanner.source_port, scanner.sequence);
+    std::thread::Builder::new().name("syn-receiver".to_string()).spawn(move || receive(&socket, &pending, &stopped, port, sequence))?;
+
+    Ok(scanner)
+  }
+
+  /// Send a SYN to a port and wait up to `timeout` for its answer
+  /// Return the round trip time when open, a `ConnectionRefused` error when closed and `TimedOut` without an answer
+  pub async fn probe(&self, ip: Ipv4Addr, port: u16, timeout: Duration) -> io::Result<Duration> {
+    let segment = syn_segment(self.local_ip(ip, port).await?, ip, self.source_port, port, self.sequence);
+    let (sender, receiver) = oneshot::channel();
+    self.pending.lock().unwrap().insert((ip, port), sender);
+
+    let start = Instant::now();
+    let answer = match self.socket.send_to(&segment, &SocketAddr::new(ip.into(), 0).into()) {
+      Ok(_) => tokio::time::timeout(timeout, receiver).await,
+      Err(err) => {
+        self.pending.lock().unwrap().remove(&(ip, port));
+        return Err(err);
+      }
+    };
+    self.pending.lock().unwrap().remove(&(ip, port));
+
+    match answer {
+      Ok(Ok(Answer::SynAck)) => Ok(start.elapsed()),
+      Ok(Ok(Answer::Reset)) => Err(ErrorKind::ConnectionRefused.into()),
+      _ => Err(ErrorKind::TimedOut.into()),
+    }
+  }
+
+  /// Address the SYNs to `ip` are sent from, needed for the checksum
+  async fn local_ip(&self, ip: Ipv4Addr, port: u16) -> io::Result<Ipv4Addr> {
+    if let Some(IpAddr::V4(local)) = self.source.ip {
+      return Ok(local);
+    }
+
+    // Connecting an UDP socket sends nothing, it only picks the route
+    let socket = self.source.bind_udp(ip.into())?;
+    socket.connect(SocketAddr::new(ip.into(), port)).await?;
+    match socket.local_addr()?.ip() {
+      IpAddr::V4(local) => Ok(local),
+      IpAddr::V6(_) => Err(ErrorKind::AddrNotAvailable.into()),
+    }
+  }
+}
+
+impl Drop for SynScanner {
+  fn drop(&mut self) {
+    self.stopped.store(true, Ordering::SeqCst);
+  }
+}
+
+/// Read the packets coming to the raw socket until the scan is dropped, handing each answer to its probe
+fn receive(socket: &Socket, pending: &Pending, stopped: &AtomicBool, port: u16, sequence: u32) {
+  let mut socket = socket;
+  let mut buffer = [0; 1500];
+
+  while !stopped.load(Ordering::SeqCst) {
+    let length = match socket.read(&mut buffer) {
+      Ok(length) => length,
+      Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::Interrupted) => continue,
+      Err(_) => return,
+    };
+
+    if let Some((ip, from, answer)) = parse_answer(&buffer[..length], port, sequence) {
+      if let Some(sender) = pending.lock().unwrap().remove(&(ip, from)) {
+        let _ = sender.send(answer);
+      }
+    }
+  }
+}
+
+/// TCP header of a SYN, with its checksum
+fn syn_segment(source: Ipv4Addr, destination: Ipv4Addr, source_port: u16, port: u16, sequence: u32) -> [u8; 20] {
+  let mut segment = [0; 20];
+  segment[0..2].copy_from_slice(&source_port.to_be_bytes());
+  segment[2..4].copy_from_slice(&port.to_be_bytes());
+  segment[4..8].copy_from_slice(&sequence.to_be_bytes());
+  // Header of 5 words, without options
+  segment[12] = 5 << 4;
+  segment[13] = SYN;
+  segment[14..16].copy_from_slice(&WINDOW.to_be_bytes());
+
+  let checksum = checksum(source, destination, &segment);
+  segment[16..18].copy_from_slice(&checksum.to_be_bytes());
+  segment
+}
+
+/// Checksum of a TCP segment, over its pseudo header and itself
+fn checksum(source: Ipv4Addr, destination: Ipv4Addr, segment: &[u8]) -> u16 {
+  let mut pseudo = Vec::with_capacity(12 + segment.len());
+  pseudo.extend_from_slice(&source.octets());
+  pseudo.extend_from_slice(&destination.octets());
+  pseudo.extend_from_slice(&[0, 6]);
+  pseudo.extend_from_slice(&(segment.len() as u16).to_be_bytes());
+  pseudo.extend_from_slice(segment);
+
+  let mut sum = pseudo.chunks(2).map(|word| u32::from(word[0]) << 8 | u32::from(*word.get(1).unwrap_or(&0))).sum::<u32>();
+  while sum > 0xffff {
+    sum = (sum & 0xffff) + (sum >> 16);
+  }
+  !(sum as u16)
+}
+
+/// Host, port and answer of an IPv4 packet answering a SYN sent from `port` with `sequence`
+fn parse_answer(packet: &[u8], port: u16, sequence: u32) -> Option<(Ipv4Addr, u16, Answer)> {
+  let header = usize::from(packet.first()? & 0x0f) * 4;
+  if packet.len() < header + 20 || packet[9] != 6 {
+    return None;
+  }
+
+  let ip = Ipv4Addr::new(packet[12], packet[13], packet[14], packet[15]);
+  let tcp = &packet[header..];
+  let from = u16::from_be_bytes([tcp[0], tcp[1]]);
+  let to = u16::from_be_bytes([tcp[2], tcp[3]]);
+  let ack = u32::from_be_bytes([tcp[8], tcp[9], tcp[10], tcp[11]]);
+  if to != port || ack != sequence.wrapping_add(1) {
+    return None;
+  }
+
+  match tcp[13] & (FIN | SYN | RST | ACK) {
+    flags if flags & RST != 0 => Some((ip, from, Answer::Reset)),
+    flags if flags == SYN | ACK => Some((ip, from, Answer::SynAck)),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// IPv4 packet from `from` to `to` with a TCP header
+  fn packet(from: (Ipv4Addr, u16), to: u16, ack: u32, flags: u8) -> Vec<u8> {
+    let mut packet = vec![0; 40];
+    packet[0] = 0x45;
+    packet[9] = 6;
+    packet[12..16].copy_from_slice(&from.0.octets());
+    packet[20..22].copy_from_slice(&from.1.to_be_bytes());
+    packet[22..24].copy_from_slice(&to.to_be_bytes());
+    packet[28..32].copy_from_slice(&ack.to_be_bytes());
+    packet[33] = flags;
+    packet
+  }
+
+  #[test]
+  fn test_syn_segment() {
+    let source = Ipv4Addr::new(192, 168, 1, 2);
+    let destination = Ipv4Addr::new(192, 168, 1, 10);
+    let segment = syn_segment(source, destination, 40000, 22, 0x01020304);
+
+    assert_eq!(&segment[0..4], &[0x9c, 0x40, 0, 22]);
+    assert_eq!(segment[13], SYN);
+    // A segment with its checksum sums to zero
+    assert_eq!(checksum(source, destination, &segment), 0);
+  }
+
+  #[test]
+  fn test_parse_answer() {
+    let host = Ipv4Addr::new(10, 0, 0, 1);
+
+    assert_eq!(parse_answer(&packet((host, 22), 40000, 101, SYN | ACK), 40000, 100), Some((host, 22, Answer::SynAck)));
+    assert_eq!(parse_answer(&packet((host, 23), 40000, 101, RST | ACK), 40000, 100), Some((host, 23, Answer::Reset)));
+
+    // Not an answer to our SYNs
+    assert_eq!(parse_answer(&packet((host, 22), 40001, 101, SYN | ACK), 40000, 100), None);
+    assert_eq!(parse_answer(&packet((host, 22), 40000, 5, SYN | ACK), 40000, 100), None);
+    assert_eq!(parse_answer(&packet((host, 22), 40000, 101, ACK), 40000, 100), None);
+    assert_eq!(parse_answer(&[0x45; 10], 40000, 100), None);
+  }
+
+  #[tokio::test]
+  async fn test_syn_probe() {
+    // Raw sockets need privileges the tests may not have
+    let Ok(scanner) = SynScanner::open(&Source::default()) else { return };
+    let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let closed = std::net::TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+    let timeout = Duration::from_secs(1);
+    assert!(scanner.probe(Ipv4Addr::LOCALHOST, port, timeout).await.is_ok());
+    assert_eq!(scanner.probe(Ipv4Addr::LOCALHOST, closed, timeout).await.unwrap_err().kind(), ErrorKind::ConnectionRefused);
+  }
+}
@@ -0,0 +1,194 @@
+use std::fmt::{Display, Formatter};
+use std::net::IpAddr;
+use std::str::FromStr;
+
+use crate::cidr::{Cidr, IpRange, IpWildcard};
+use crate::error::ScannerError;
+use crate::scanner::MAX_NETWORK_SIZE;
+
+/// Target of a scan, as given on the command line or in a targets file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Target {
+  /// A single IPv4 or IPv6 address, whatever its octets
+  Single(IpAddr),
+
+  /// A network in CIDR notation (`192.168.1.0/24`, `fd00::/120`), its network and broadcast addresses skipped
+  Cidr(Cidr),
+
+  /// An inclusive range of IPv4 addresses (`192.168.1.50-192.168.1.90`)
+  Range(IpRange),
+
+  /// IPv4 addresses with wildcard octets (`192.168.*.*`)
+  Wildcard(IpWildcard),
+
+  /// A host name, resolved when the scanner is created
+  Host(String),
+}
+
+impl FromStr for Target {
+  type Err = ScannerError;
+
+  /// Host names are checked but not resolved
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    if is_hostname(s) {
+      return Ok(Target::Host(s.to_string()));
+    }
+
+    if s.contains('*') {
+      return s.parse::<IpWildcard>().map(Target::Wildcard).map_err(ScannerError::InvalidWildcard);
+    }
+
+    if s.contains('-') {
+      return s.parse::<IpRange>().map(Target::Range).map_err(ScannerError::InvalidRange);
+    }
+
+    if s.contains('/') {
+      let cidr = s.parse::<Cidr>()?;
+      if cidr.size() > MAX_NETWORK_SIZE {
+        return Err(ScannerError::NetworkTooLarge(s.to_string()));
+      }
+
+      return Ok(Target::Cidr(cidr));
+    }
+
+    s.parse::<IpAddr>().map(Target::Single).map_err(|_| ScannerError::InvalidIp(s.to_string()))
+  }
+}
+
+impl TryFrom<&str> for Target {
+  type Error = ScannerError;
+
+  fn try_from(s: &str) -> Result<Self, Self::Error> {
+    s.parse()
+  }
+}
+
+/// The target in the notation it was given in
+impl Display for Target {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    match self {
+      Target::Single(ip) => write!(f, "{}", ip),
+      Target::Cidr(cidr) => write!(f, "{}", cidr),
+      Target::Range(range) => write!(f, "{}", range),
+      Target::Wildcard(wildcard) => write!(f, "{}", wildcard),
+      Target::Host(host) => write!(f, "{}", host),
+    }
+  }
+}
+
+/// Whether a target looks like a host name rather than an IP or a network
+/// Anything else is left to the IP parser so its error is reported
+pub(crate) fn is_hostname(target: &str) -> bool {
+  target.parse::<IpAddr>().is_err()
+    && target.chars().any(|c| c.is_ascii_alphabetic())
+    && target.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '.')
+}
+
+/// Read the targets of a file, one IP, network in CIDR notation or host name per line
+/// Blank lines and lines starting with `#` are ignored
+pub fn read_targets(path: &str) -> Result<Vec<Target>, ScannerError> {
+  let content = std::fs::read_to_string(path).map_err(|err| ScannerError::TargetsFile {
+    path: path.to_string(),
+    line: None,
+    message: err.to_string(),
+  })?;
+
+  parse_targets(&content).map_err(|(line, err)| ScannerError::TargetsFile {
+    path: path.to_string(),
+    line: Some(line),
+    message: err.to_string(),
+  })
+}
+
+/// Parse the targets of a file, returning the number of the first invalid line and its error
+/// Host names are checked but not resolved
+pub fn parse_targets(content: &str) -> Result<Vec<Target>, (usize, ScannerError)> {
+  let mut targets = Vec::new();
+
+  for (index, line) in content.lines().enumerate() {
+    let target = line.trim();
+    if target.is_empty() || target.starts_with('#') {
+      continue;
+    }
+
+    targets.push(target.parse::<Target>().map_err(|err| (index + 1, err))?);
+  }
+
+  Ok(targets)
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_parse_targets() {
+    let content = "# office\n192.168.1.0/24\n\n  10.0.0.5  \nscanme.example.com\n# fd00::/120\nfd00::1\n";
+
+    let targets = parse_targets(content).unwrap();
+    assert_eq!(targets.iter().map(Target::to_string).collect::<Vec<String>>(), vec!["192.168.1.0/24", "10.0.0.5", "scanme.example.com", "fd00::1"]);
+    assert_eq!(targets[2], Target::Host("scanme.example.com".to_string()));
+  }
+
+  #[test]
+  fn test_parse_target() {
+    let ip = |ip: &str| ip.parse::<IpAddr>().unwrap();
+
+    let valid = [
+      ("192.168.1.1", Target::Single(ip("192.168.1.1"))),
+      ("10.0.0.0", Target::Single(ip("10.0.0.0"))),
+      ("fd00::1", Target::Single(ip("fd00::1"))),
+      ("::ffff:192.168.1.1", Target::Single(ip("::ffff:192.168.1.1"))),
+      ("192.168.1.0/24", Target::Cidr(Cidr { ip: ip("192.168.1.0"), prefix: 24 })),
+      ("10.0.0.5/30", Target::Cidr(Cidr { ip: ip("10.0.0.5"), prefix: 30 })),
+      ("fd00::/120", Target::Cidr(Cidr { ip: ip("fd00::"), prefix: 120 })),
+      ("0.0.0.0/0", Target::Cidr(Cidr { ip: ip("0.0.0.0"), prefix: 0 })),
+      ("192.168.1.50-192.168.1.90", Target::Range("192.168.1.50-192.168.1.90".parse().unwrap())),
+      ("192.168.*.*", Target::Wildcard("192.168.*.*".parse().unwrap())),
+      ("localhost", Target::Host("localhost".to_string())),
+      ("scanme.example.com", Target::Host("scanme.example.com".to_string())),
+      ("router-1.lan", Target::Host("router-1.lan".to_string())),
+    ];
+    for (input, target) in valid {
+      assert_eq!(input.parse::<Target>().as_ref(), Ok(&target), "{}", input);
+      assert_eq!(Target::try_from(input).as_ref(), Ok(&target), "{}", input);
+      assert_eq!(target.to_string(), input);
+    }
+
+    let invalid = [
+      ("", ScannerError::InvalidIp(String::new())),
+      ("192.168.1.300", ScannerError::InvalidIp("192.168.1.300".to_string())),
+      ("192.168.1", ScannerError::InvalidIp("192.168.1".to_string())),
+      ("10.0.0.0/33", ScannerError::InvalidCidr("10.0.0.0/33".to_string())),
+      ("fd00::/64", ScannerError::NetworkTooLarge("fd00::/64".to_string())),
+      ("192.168.1.90-192.168.1.50", ScannerError::InvalidRange("192.168.1.90-192.168.1.50, the start comes after the end".to_string())),
+      ("fd00::1-fd00::5", ScannerError::InvalidRange("fd00::1-fd00::5, fd00::1 is not an IPv4 address".to_string())),
+      ("192.168.*", ScannerError::InvalidWildcard("192.168.*, expected four octets such as 192.168.1.*".to_string())),
+      ("under_score.lan", ScannerError::InvalidIp("under_score.lan".to_string())),
+    ];
+    for (input, error) in invalid {
+      assert_eq!(input.parse::<Target>(), Err(error), "{}", input);
+    }
+  }
+
+  #[test]
+  fn test_parse_targets_invalid_line() {
+    let (line, err) = parse_targets("192.168.1.1\n\n192.168.1.300\n").unwrap_err();
+
+    assert_eq!(line, 3);
+    assert_eq!(err, ScannerError::InvalidIp("192.168.1.300".to_string()));
+  }
+
+  #[test]
+  fn test_read_targets_errors() {
+    let path = std::env::temp_dir().join(format!("skanner-targets-{}.txt", std::process::id()));
+    std::fs::write(&path, "10.0.0.1\n10.0.0.0/33\n").unwrap();
+    let path = path.to_str().unwrap().to_string();
+
+    let err = read_targets(&path).unwrap_err();
+    std::fs::remove_file(&path).unwrap();
+
+    assert_eq!(err.to_string(), format!("Invalid target in {} at line 2: Invalid CIDR notation: 10.0.0.0/33", path));
+    assert!(matches!(read_targets(&path), Err(ScannerError::TargetsFile { line: None, .. })));
+  }
+}
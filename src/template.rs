@@ -0,0 +1,176 @@
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
+use crate::port::PortResult;
+use crate::scanner::IpScanResult;
+
+/// Value of a finding substituted for a placeholder
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Field {
+  Ip,
+  Hostname,
+  Port,
+  Protocol,
+  State,
+  Service,
+  Banner,
+  Latency,
+}
+
+/// Placeholders of a template, with the field each one stands for
+const FIELDS: [(&str, Field); 8] = [
+  ("ip", Field::Ip),
+  ("hostname", Field::Hostname),
+  ("port", Field::Port),
+  ("protocol", Field::Protocol),
+  ("state", Field::State),
+  ("service", Field::Service),
+  ("banner", Field::Banner),
+  ("latency", Field::Latency),
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+  Text(String),
+  Field(Field),
+}
+
+/// Line written for each open port, such as `{ip}:{port} {service}`
+///
+/// The placeholders are `{ip}`, `{hostname}`, `{port}`, `{protocol}`,
+/// `{state}`, `{service}`, `{banner}` and `{latency}` (in milliseconds), the
+/// ones without a value being left empty. `{{` and `}}` are literal braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Template {
+  source: String,
+  segments: Vec<Segment>,
+}
+
+impl FromStr for Template {
+  type Err = String;
+
+  fn from_str(s: &str) -> Result<Self, Self::Err> {
+    let mut segments = Vec::new();
+    let mut text = String::new();
+    let mut chars = s.chars();
+
+    while let Some(c) = chars.next() {
+      match c {
+        '{' if chars.as_str().starts_with('{') => {
+          chars.next();
+          text.push('{');
+        }
+        '}' if chars.as_str().starts_with('}') => {
+          chars.next();
+          text.push('}');
+        }
+        '{' => {
+          let (name, rest) = chars.as_str().split_once('}').ok_or_else(|| format!("unclosed placeholder in {}", s))?;
+          let field = FIELDS.iter().find(|(field, _)| *field == name).map(|(_, field)| *field).ok_or_else(|| {
+            let names = FIELDS.iter().map(|(field, _)| format!("{{{}}}", field)).collect::<Vec<String>>();
+            format!("unknown placeholder {{{}}}, expected one of {}", name, names.join(", "))
+          })?;
+
+          if !text.is_empty() {
+            segments.push(Segment::Text(std::mem::take(&mut text)));
+          }
+          segments.push(Segment::Field(field));
+          chars = rest.chars();
+        }
+        '}' => return Err(format!("unmatched }} in {}, use }}}} for a literal brace", s)),
+        c => text.push(c),
+      }
+    }
+    if !text.is_empty() {
+      segments.push(Segment::Text(text));
+    }
+
+    Ok(Template { source: s.to_string(), segments })
+  }
+}
+
+impl Display for Template {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "{}", self.source)
+  }
+}
+
+/// Clap value parser for a template
+pub fn parse_template(s: &str) -> Result<Template, String> {
+  s.parse::<Template>()
+}
+
+impl Template {
+  /// Line of an open port of a host, without the line break
+  pub fn line(&self, result: &IpScanResult, port: &PortResult) -> String {
+    let mut line = String::new();
+
+    for segment in &self.segments {
+      match segment {
+        Segment::Text(text) => line.push_str(text),
+        Segment::Field(Field::Ip) => line.push_str(&result.ip.to_string()),
+        Segment::Field(Field::Hostname) => line.push_str(result.hostname.as_deref().unwrap_or_default()),
+        Segment::Field(Field::Port) => line.push_str(&port.port.to_string()),
+        Segment::Field(Field::Protocol) => line.push_str(&port.protocol.to_string()),
+        Segment::Field(Field::State) => line.push_str(&port.state.to_string()),
+        Segment::Field(Field::Service) => line.push_str(&port.service.as_ref().map(|service| service.to_string()).unwrap_or_default()),
+        Segment::Field(Field::Banner) => line.push_str(port.banner.as_deref().unwrap_or_default()),
+        Segment::Field(Field::Latency) => line.push_str(&port.latency.map(|latency| format!("{:.2}", latency.as_secs_f64() * 1000.0)).unwrap_or_default()),
+      }
+    }
+
+    line
+  }
+
+  /// One line per open port of every host
+  pub fn render(&self, results: &[IpScanResult]) -> String {
+    results.iter().flat_map(|result| result.open_ports.iter().map(move |port| self.line(result, port) + "\n")).collect()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::time::Duration;
+
+  use crate::port::{PortState, Protocol};
+
+  fn host() -> IpScanResult {
+    let mut ssh = PortResult::new(22, Protocol::Tcp, PortState::Open);
+    ssh.banner = Some("SSH-2.0-OpenSSH_9.6".to_string());
+    ssh.service = crate::fingerprint::identify("SSH-2.0-OpenSSH_9.6");
+    ssh.latency = Some(Duration::from_micros(1250));
+
+    IpScanResult {
+      open_ports: vec![ssh, PortResult::new(53, Protocol::Udp, PortState::OpenFiltered)],
+      ..IpScanResult::new("192.168.1.10".parse().unwrap())
+    }
+  }
+
+  #[test]
+  fn test_template_render() {
+    let template: Template = "{ip}:{port}/{protocol} {state} {latency} {banner}".parse().unwrap();
+
+    assert_eq!(template.render(&[host()]), "192.168.1.10:22/tcp open 1.25 SSH-2.0-OpenSSH_9.6\n192.168.1.10:53/udp open|filtered  \n");
+  }
+
+  #[test]
+  fn test_template_service_and_braces() {
+    let template: Template = "{{{ip}}} {service}{hostname}".parse().unwrap();
+    let result = host();
+
+    assert_eq!(template.line(&result, &result.open_ports[0]), format!("{{192.168.1.10}} {}", result.open_ports[0].service.as_ref().unwrap()));
+    assert_eq!(template.to_string(), "{{{ip}}} {service}{hostname}");
+  }
+
+  #[test]
+  fn test_template_errors() {
+    assert_eq!(
+      "{ip} {host}".parse::<Template>().unwrap_err(),
+      "unknown placeholder {host}, expected one of {ip}, {hostname}, {port}, {protocol}, {state}, {service}, {banner}, {latency}"
+    );
+    assert!("{ip".parse::<Template>().is_err());
+    assert!("{ip} }".parse::<Template>().is_err());
+    assert_eq!("no placeholder".parse::<Template>().unwrap().render(&[host()]), "no placeholder\nno placeholder\n");
+  }
+}
@@ -0,0 +1,132 @@
+use std::sync::Mutex;
+use std::time::Duration;
+
+/// Number of successful connections measured before adapting the timeout
+pub const SAMPLES: usize = 8;
+
+/// Multiple of the median round trip time the adapted timeout is set to
+pub const MULTIPLIER: u32 = 4;
+
+/// Lowest timeout the adaptive timeout goes down to by default
+pub const DEFAULT_MIN_TIMEOUT: Duration = Duration::from_millis(50);
+
+/// Time to wait for a TCP connection, shared between tasks
+///
+/// A fixed timeout is the same for every attempt. An adaptive one starts at
+/// its maximum, then is set to `MULTIPLIER` times the median round trip time
+/// of the first `SAMPLES` successful connections, within its bounds. The
+/// retries always wait the maximum, so a port slower than the others is not
+/// reported as filtered.
+#[derive(Debug)]
+pub struct ConnectTimeout {
+  min: Duration,
+  max: Duration,
+  adaptive: bool,
+  state: Mutex<State>,
+}
+
+#[derive(Debug, Default)]
+struct State {
+  samples: Vec<Duration>,
+  adapted: Option<Duration>,
+}
+
+impl ConnectTimeout {
+  /// Same timeout for every attempt
+  pub fn fixed(timeout: Duration) -> Self {
+    Self { min: timeout, max: timeout, adaptive: false, state: Mutex::default() }
+  }
+
+  /// Timeout adapted to the round trip times, between `min` and `max`
+  pub fn adaptive(min: Duration, max: Duration) -> Self {
+    Self { min: min.min(max), max, adaptive: true, state: Mutex::default() }
+  }
+
+  /// Timeout of an attempt, the first one being 0
+  pub fn get(&self, attempt: u32) -> Duration {
+    match attempt {
+      0 => self.adapted().unwrap_or(self.max),
+      _ => self.max,
+    }
+  }
+
+  /// Timeout chosen from the round trip times, `None` until enough connections succeeded
+  pub fn adapted(&self) -> Option<Duration> {
+    self.state.lock().unwrap().adapted
+  }
+
+  /// Measure the round trip time of a successful connection
+  /// Return the timeout when it was just adapted
+  pub fn observe(&self, rtt: Duration) -> Option<Duration> {
+    if !self.adaptive {
+      return None;
+    }
+
+    let mut state = self.state.lock().unwrap();
+    if state.adapted.is_some() {
+      return None;
+    }
+
+    state.samples.push(rtt);
+    if state.samples.len() < SAMPLES {
+      return None;
+    }
+
+    state.samples.sort();
+    let median = state.samples[state.samples.len() / 2];
+    let timeout = (median * MULTIPLIER).clamp(self.min, self.max);
+    state.adapted = Some(timeout);
+
+    debug!("timeout adapted to {}ms, the median round trip time being {:.2}ms", timeout.as_millis(), median.as_secs_f64() * 1000.0);
+    Some(timeout)
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn test_adaptive_timeout() {
+    let timeout = ConnectTimeout::adaptive(Duration::from_millis(50), Duration::from_secs(1));
+    assert_eq!(timeout.get(0), Duration::from_secs(1));
+
+    for rtt in [30, 10, 20, 40, 25, 35, 15, 900] {
+      assert_eq!(timeout.adapted(), None);
+      timeout.observe(Duration::from_millis(rtt));
+    }
+
+    // 4 times the median of 30ms, the retries waiting the maximum
+    assert_eq!(timeout.adapted(), Some(Duration::from_millis(120)));
+    assert_eq!(timeout.get(0), Duration::from_millis(120));
+    assert_eq!(timeout.get(1), Duration::from_secs(1));
+
+    // Chosen once
+    assert_eq!(timeout.observe(Duration::from_millis(500)), None);
+    assert_eq!(timeout.get(0), Duration::from_millis(120));
+  }
+
+  #[test]
+  fn test_adaptive_timeout_bounds() {
+    let fast = ConnectTimeout::adaptive(Duration::from_millis(50), Duration::from_secs(1));
+    let slow = ConnectTimeout::adaptive(Duration::from_millis(50), Duration::from_secs(1));
+    for _ in 0..SAMPLES {
+      fast.observe(Duration::from_micros(300));
+      slow.observe(Duration::from_millis(400));
+    }
+
+    assert_eq!(fast.adapted(), Some(Duration::from_millis(50)));
+    assert_eq!(slow.adapted(), Some(Duration::from_secs(1)));
+  }
+
+  #[test]
+  fn test_fixed_timeout() {
+    let timeout = ConnectTimeout::fixed(Duration::from_millis(200));
+    for _ in 0..SAMPLES {
+      assert_eq!(timeout.observe(Duration::from_millis(1)), None);
+    }
+
+    assert_eq!(timeout.get(0), Duration::from_millis(200));
+    assert_eq!(timeout.adapted(), None);
+  }
+}
@@ -0,0 +1,385 @@
+use std::fmt::{Display, Formatter};
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+
+/// Time to wait for a service to answer the TLS handshake
+pub const TLS_TIMEOUT: Duration = Duration::from_millis(1000);
+
+/// Maximum number of bytes read from the handshake, certificate chains included
+const MAX_HANDSHAKE_SIZE: usize = 64 * 1024;
+
+const RECORD_HANDSHAKE: u8 = 22;
+const RECORD_ALERT: u8 = 21;
+
+const HANDSHAKE_CLIENT_HELLO: u8 = 1;
+const HANDSHAKE_CERTIFICATE: u8 = 11;
+const HANDSHAKE_SERVER_HELLO_DONE: u8 = 14;
+
+/// Cipher suites offered, the common TLS 1.2 ones so most servers pick one
+const CIPHER_SUITES: [u16; 15] = [
+  0xc02f, 0xc030, 0xc02b, 0xc02c, 0xcca8, 0xcca9, 0xc013, 0xc014, 0xc009, 0xc00a, 0x009c, 0x009d, 0x002f, 0x0035, 0x000a,
+];
+
+/// Signature algorithms offered, ECDSA, RSA-PSS and RSA with SHA-2 and SHA-1
+const SIGNATURE_ALGORITHMS: [u16; 11] = [0x0403, 0x0503, 0x0603, 0x0804, 0x0805, 0x0806, 0x0401, 0x0501, 0x0601, 0x0201, 0x0203];
+
+/// Elliptic curves offered, x25519, secp256r1 and secp384r1
+const SUPPORTED_GROUPS: [u16; 3] = [0x001d, 0x0017, 0x0018];
+
+/// TLS service found on a port, with the certificate it presented
+///
+/// The handshake offers TLS 1.2 only: the certificate is encrypted from
+/// TLS 1.3 on, while a TLS 1.2 server sends it in the clear. A server
+/// refusing TLS 1.2 is still reported, without certificate.
+#[derive(Debug, Clone, Default, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TlsInfo {
+  /// Common name of the subject of the certificate
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub common_name: Option<String>,
+
+  /// End of validity of the certificate, as `YYYY-MM-DDTHH:MM:SSZ`
+  #[serde(skip_serializing_if = "Option::is_none")]
+  pub not_after: Option<String>,
+}
+
+impl Display for TlsInfo {
+  fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+    write!(f, "tls")?;
+    if let Some(common_name) = &self.common_name {
+      write!(f, " {}", common_name)?;
+    }
+    if let Some(not_after) = &self.not_after {
+      write!(f, " expires {}", not_after)?;
+    }
+    Ok(())
+  }
+}
+
+/// Start a TLS handshake on a connected stream and read the certificate of the server
+/// Return `None` when the service doesn't speak TLS or didn't answer before the timeout
+pub async fn probe_tls(stream: &mut TcpStream, server_name: Option<&str>, timeout: Duration) -> Option<TlsInfo> {
+  tokio::time::timeout(timeout, handshake(stream, server_name)).await.ok()?
+}
+
+async fn handshake(stream: &mut TcpStream, server_name: Option<&str>) -> Option<TlsInfo> {
+  stream.write_all(&client_hello(server_name)).await.ok()?;
+
+  let mut messages: Vec<u8> = Vec::new();
+  let mut header = [0_u8; 5];
+
+  loop {
+    stream.read_exact(&mut header).await.ok()?;
+
+    // Anything but a TLS record is another protocol
+    let (content_type, major) = (header[0], header[1]);
+    if major != 3 || (content_type != RECORD_HANDSHAKE && content_type != RECORD_ALERT) {
+      return None;
+    }
+
+    let length = u16::from_be_bytes([header[3], header[4]]) as usize;
+    let mut record = vec![0_u8; length];
+    stream.read_exact(&mut record).await.ok()?;
+
+    // An alert is a TLS server refusing the handshake, most likely TLS 1.2
+    if content_type == RECORD_ALERT {
+      return Some(TlsInfo::default());
+    }
+
+    messages.extend_from_slice(&record);
+    if messages.len() > MAX_HANDSHAKE_SIZE {
+      return Some(TlsInfo::default());
+    }
+
+    // Handshake messages may span several records, only complete ones are read
+    let mut offset = 0;
+    while let Some((kind, body)) = handshake_message(&messages[offset..]) {
+      match kind {
+        HANDSHAKE_CERTIFICATE => return Some(certificate_message(body).map(parse_certificate).unwrap_or_default()),
+        HANDSHAKE_SERVER_HELLO_DONE => return Some(TlsInfo::default()),
+        _ => offset += 4 + body.len(),
+      }
+    }
+  }
+}
+
+/// Clap value parser for the name sent with `--sni`, which has to be a host name rather than an IP
+pub fn parse_sni(s: &str) -> Result<String, String> {
+  match crate::targets::is_hostname(s) {
+    true => Ok(s.to_ascii_lowercase()),
+    false => Err(format!("{} is not a host name", s)),
+  }
+}
+
+/// Type and body of the first handshake message of `data`, if it is complete
+fn handshake_message(data: &[u8]) -> Option<(u8, &[u8])> {
+  let length = read_u24(data.get(1..4)?);
+  let body = data.get(4..4 + length)?;
+
+  Some((data[0], body))
+}
+
+/// First certificate, the one of the server, of a Certificate message
+fn certificate_message(body: &[u8]) -> Option<&[u8]> {
+  let length = read_u24(body.get(3..6)?);
+  body.get(6..6 + length)
+}
+
+fn read_u24(bytes: &[u8]) -> usize {
+  (bytes[0] as usize) << 16 | (bytes[1] as usize) << 8 | bytes[2] as usize
+}
+
+/// ClientHello record offering TLS 1.2, with the server name when scanning a host name
+fn client_hello(server_name: Option<&str>) -> Vec<u8> {
+  let mut extensions: Vec<u8> = Vec::new();
+
+  if let Some(name) = server_name {
+    let mut list = vec![0];
+    push_vec16(&mut list, name.as_bytes());
+    let mut server_names = Vec::new();
+    push_vec16(&mut server_names, &list);
+    push_extension(&mut extensions, 0x0000, &server_names);
+  }
+
+  let groups = SUPPORTED_GROUPS.iter().flat_map(|group| group.to_be_bytes()).collect::<Vec<u8>>();
+  let mut supported_groups = Vec::new();
+  push_vec16(&mut supported_groups, &groups);
+  push_extension(&mut extensions, 0x000a, &supported_groups);
+
+  // Uncompressed points only
+  push_extension(&mut extensions, 0x000b, &[1, 0]);
+
+  let algorithms = SIGNATURE_ALGORITHMS.iter().flat_map(|algorithm| algorithm.to_be_bytes()).collect::<Vec<u8>>();
+  let mut signature_algorithms = Vec::new();
+  push_vec16(&mut signature_algorithms, &algorithms);
+  push_extension(&mut extensions, 0x000d, &signature_algorithms);
+
+  // Extended master secret and empty renegotiation info, required by some servers
+  push_extension(&mut extensions, 0x0017, &[]);
+  push_extension(&mut extensions, 0xff01, &[0]);
+
+  let mut hello: Vec<u8> = vec![3, 3];
+  hello.extend_from_slice(&rand::random::<[u8; 32]>());
+  // No session to resume
+  hello.push(0);
+  push_vec16(&mut hello, &CIPHER_SUITES.iter().flat_map(|suite| suite.to_be_bytes()).collect::<Vec<u8>>());
+  // Null compression only
+  hello.extend_from_slice(&[1, 0]);
+  push_vec16(&mut hello, &extensions);
+
+  let mut handshake = vec![HANDSHAKE_CLIENT_HELLO];
+  handshake.extend_from_slice(&(hello.len() as u32).to_be_bytes()[1..]);
+  handshake.extend_from_slice(&hello);
+
+  // The record version stays TLS 1.0 for old servers, the hello asks for TLS 1.2
+  let mut record = vec![RECORD_HANDSHAKE, 3, 1];
+  push_vec16(&mut record, &handshake);
+  record
+}
+
+/// Append `data` prefixed with its length on 2 bytes
+fn push_vec16(buffer: &mut Vec<u8>, data: &[u8]) {
+  buffer.extend_from_slice(&(data.len() as u16).to_be_bytes());
+  buffer.extend_from_slice(data);
+}
+
+fn push_extension(buffer: &mut Vec<u8>, kind: u16, data: &[u8]) {
+  buffer.extend_from_slice(&kind.to_be_bytes());
+  push_vec16(buffer, data);
+}
+
+/// Read the common name and the end of validity of a DER encoded X.509 certificate
+/// Fields that can't be read are left empty
+pub fn parse_certificate(der: &[u8]) -> TlsInfo {
+  parse_tbs_certificate(der).unwrap_or_default()
+}
+
+fn parse_tbs_certificate(der: &[u8]) -> Option<TlsInfo> {
+  let (_, certificate, _) = der_item(der)?;
+  let (_, tbs, _) = der_item(certificate)?;
+
+  // The version is an optional explicit tag
+  let (tag, _, rest) = der_item(tbs)?;
+  let rest = if tag == 0xa0 { der_item(rest)?.2 } else { rest };
+
+  // Signature algorithm and issuer
+  let (_, _, rest) = der_item(rest)?;
+  let (_, _, rest) = der_item(rest)?;
+
+  let (_, validity, rest) = der_item(rest)?;
+  let (_, subject, _) = der_item(rest)?;
+
+  let (_, _, validity) = der_item(validity)?;
+  let not_after = der_item(validity).and_then(|(tag, time, _)| der_time(tag, time));
+
+  Some(TlsInfo {
+    common_name: common_name(subject),
+    not_after,
+  })
+}
+
+/// Tag, content and remaining bytes of the first DER item of `data`
+fn der_item(data: &[u8]) -> Option<(u8, &[u8], &[u8])> {
+  let tag = *data.first()?;
+  let first = *data.get(1)? as usize;
+
+  let (length, start) = if first < 0x80 {
+    (first, 2)
+  } else {
+    let size = first & 0x7f;
+    if size == 0 || size > 4 {
+      return None;
+    }
+    let length = data.get(2..2 + size)?.iter().fold(0_usize, |length, byte| length << 8 | *byte as usize);
+    (length, 2 + size)
+  };
+
+  let content = data.get(start..start + length)?;
+  Some((tag, content, &data[start + length..]))
+}
+
+/// Common name (OID 2.5.4.3) of a distinguished name
+fn common_name(mut name: &[u8]) -> Option<String> {
+  const COMMON_NAME: [u8; 3] = [0x55, 0x04, 0x03];
+
+  while let Some((_, set, rest)) = der_item(name) {
+    let mut attributes = set;
+    while let Some((_, attribute, next)) = der_item(attributes) {
+      let (_, oid, value) = der_item(attribute)?;
+      if oid == COMMON_NAME {
+        let (_, value, _) = der_item(value)?;
+        return Some(String::from_utf8_lossy(value).into_owned());
+      }
+      attributes = next;
+    }
+    name = rest;
+  }
+
+  None
+}
+
+/// Format an UTCTime (`YYMMDDHHMMSSZ`) or a GeneralizedTime (`YYYYMMDDHHMMSSZ`) as `YYYY-MM-DDTHH:MM:SSZ`
+fn der_time(tag: u8, time: &[u8]) -> Option<String> {
+  let time = std::str::from_utf8(time).ok()?;
+
+  let (year, rest) = match tag {
+    // Years 50 to 99 are in the 20th century
+    0x17 => {
+      let year = time.get(0..2)?.parse::<u32>().ok()?;
+      (if year < 50 { 2000 + year } else { 1900 + year }, time.get(2..)?)
+    }
+    0x18 => (time.get(0..4)?.parse::<u32>().ok()?, time.get(4..)?),
+    _ => return None,
+  };
+
+  // Checked on bytes, a multi-byte character sent by the server not being cut in the middle
+  let digits = rest.get(..10)?;
+  if !digits.bytes().all(|c| c.is_ascii_digit()) {
+    return None;
+  }
+
+  Some(format!("{:04}-{}-{}T{}:{}:{}Z", year, &digits[0..2], &digits[2..4], &digits[4..6], &digits[6..8], &digits[8..10]))
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use std::io::{Read, Write};
+  use std::net::TcpListener;
+
+  const CERTIFICATE: &[u8] = include_bytes!("../tests/fixtures/certificate.der");
+
+  /// Handshake record with a single message
+  fn handshake_record(kind: u8, body: &[u8]) -> Vec<u8> {
+    let mut message = vec![kind];
+    message.extend_from_slice(&(body.len() as u32).to_be_bytes()[1..]);
+    message.extend_from_slice(body);
+
+    let mut record = vec![RECORD_HANDSHAKE, 3, 3];
+    push_vec16(&mut record, &message);
+    record
+  }
+
+  #[test]
+  fn test_parse_certificate() {
+    let info = parse_certificate(CERTIFICATE);
+
+    assert_eq!(info.common_name.as_deref(), Some("skanner.test"));
+    assert_eq!(info.not_after.as_deref(), Some("2036-10-11T10:15:03Z"));
+    assert_eq!(parse_certificate(&CERTIFICATE[..100]), TlsInfo::default());
+  }
+
+  #[test]
+  fn test_der_time() {
+    assert_eq!(der_time(0x17, b"991231235959Z"), Some("1999-12-31T23:59:59Z".to_string()));
+    assert_eq!(der_time(0x18, b"20510101000000Z"), Some("2051-01-01T00:00:00Z".to_string()));
+    assert_eq!(der_time(0x17, b"99123"), None);
+    assert_eq!(der_time(0x17, "99123123595é".as_bytes()), None);
+    assert_eq!(der_time(0x18, "2051010100000é".as_bytes()), None);
+  }
+
+  #[test]
+  fn test_parse_sni() {
+    assert_eq!(parse_sni("Intranet.LAN"), Ok("intranet.lan".to_string()));
+    assert!(parse_sni("10.0.0.1").is_err());
+    assert!(parse_sni("intranet.lan/admin").is_err());
+  }
+
+  #[test]
+  fn test_client_hello_server_name() {
+    let hello = client_hello(Some("example.com"));
+
+    assert_eq!(&hello[..3], &[RECORD_HANDSHAKE, 3, 1]);
+    assert_eq!(u16::from_be_bytes([hello[3], hello[4]]) as usize, hello.len() - 5);
+    assert!(hello.windows(11).any(|window| window == b"example.com"));
+  }
+
+  #[tokio::test]
+  async fn test_probe_tls() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let handle = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      let mut hello = [0_u8; 512];
+      let _ = stream.read(&mut hello).unwrap();
+
+      // The certificate message is split between two records
+      let mut certificates = Vec::new();
+      certificates.extend_from_slice(&((CERTIFICATE.len() + 3) as u32).to_be_bytes()[1..]);
+      certificates.extend_from_slice(&(CERTIFICATE.len() as u32).to_be_bytes()[1..]);
+      certificates.extend_from_slice(CERTIFICATE);
+      let certificate = handshake_record(HANDSHAKE_CERTIFICATE, &certificates);
+      let (first, second) = certificate[5..].split_at(100);
+
+      stream.write_all(&handshake_record(2, &[3, 3])).unwrap();
+      stream.write_all(&[&[RECORD_HANDSHAKE, 3, 3], &(first.len() as u16).to_be_bytes()[..], first].concat()).unwrap();
+      stream.write_all(&[&[RECORD_HANDSHAKE, 3, 3], &(second.len() as u16).to_be_bytes()[..], second].concat()).unwrap();
+    });
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    let info = probe_tls(&mut stream, None, TLS_TIMEOUT).await.unwrap();
+    handle.join().unwrap();
+
+    assert_eq!(info.common_name.as_deref(), Some("skanner.test"));
+    assert_eq!(info.to_string(), "tls skanner.test expires 2036-10-11T10:15:03Z");
+  }
+
+  #[tokio::test]
+  async fn test_probe_tls_other_protocol() {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = listener.local_addr().unwrap();
+
+    let handle = std::thread::spawn(move || {
+      let (mut stream, _) = listener.accept().unwrap();
+      stream.write_all(b"HTTP/1.1 400 Bad Request\r\n\r\n").unwrap();
+    });
+
+    let mut stream = TcpStream::connect(address).await.unwrap();
+    assert_eq!(probe_tls(&mut stream, None, TLS_TIMEOUT).await, None);
+    handle.join().unwrap();
+  }
+}
@@ -0,0 +1,87 @@
+use std::net::TcpListener;
+
+use clap::Parser;
+
+use skanner::{Args, EXIT_NEW_FINDINGS, EXIT_NO_OPEN_PORTS, EXIT_OPEN_PORTS, ScanResults, exit_code, run};
+
+#[tokio::test]
+async fn test_run_counts_open_ports() {
+  let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+  let open = listener.local_addr().unwrap().port();
+  let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+  let args = Args::try_parse_from(["skanner", "--ip", "127.0.0.1", "--ports", &open.to_string(), &closed.to_string(), "--quiet"]).unwrap();
+  let outcome = run(args).await;
+  let summary = outcome.as_ref().unwrap();
+
+  assert_eq!(summary.hosts_scanned, 1);
+  assert_eq!(summary.hosts_up, 1);
+  assert_eq!(summary.hosts_with_open_ports, 1);
+  assert_eq!(summary.open_ports, 1);
+  assert!(!summary.interrupted);
+  assert_eq!(exit_code(&outcome), EXIT_OPEN_PORTS);
+}
+
+#[tokio::test]
+async fn test_run_without_open_ports() {
+  let closed = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+
+  let args = Args::try_parse_from(["skanner", "--ip", "127.0.0.1", "--ports", &closed.to_string(), "--no-discovery", "--quiet"]).unwrap();
+  let outcome = run(args).await;
+
+  assert_eq!(outcome.as_ref().unwrap().open_ports, 0);
+  assert_eq!(exit_code(&outcome), EXIT_NO_OPEN_PORTS);
+}
+
+#[tokio::test]
+async fn test_run_alert_on_new() {
+  let first = TcpListener::bind("127.0.0.1:0").unwrap();
+  let second = TcpListener::bind("127.0.0.1:0").unwrap();
+  let ports = [&first, &second].map(|listener| listener.local_addr().unwrap().port().to_string());
+  let baseline = std::env::temp_dir().join(format!("skanner-baseline-{}.json", std::process::id()));
+  let _ = std::fs::remove_file(&baseline);
+  let baseline = baseline.to_str().unwrap();
+
+  let scan = |ports: &[&str]| {
+    let args = Args::try_parse_from([&["skanner", "--ip", "127.0.0.1", "--no-discovery", "--quiet", "--alert-on-new", baseline, "--ports"], ports].concat()).unwrap();
+    run(args)
+  };
+
+  // The first run writes the baseline, nothing being new
+  let outcome = scan(&[&ports[0]]).await;
+  assert_eq!(outcome.as_ref().unwrap().new_since_baseline, Some(0));
+  assert_eq!(exit_code(&outcome), EXIT_OPEN_PORTS);
+  assert_eq!(ScanResults::read(baseline).unwrap().results[0].open_ports.len(), 1);
+
+  // The second listener is new, then part of the updated baseline
+  let outcome = scan(&[&ports[0], &ports[1]]).await;
+  assert_eq!(outcome.as_ref().unwrap().new_since_baseline, Some(1));
+  assert_eq!(exit_code(&outcome), EXIT_NEW_FINDINGS);
+
+  let outcome = scan(&[&ports[0], &ports[1]]).await;
+  assert_eq!(outcome.as_ref().unwrap().new_since_baseline, Some(0));
+  assert_eq!(exit_code(&outcome), EXIT_OPEN_PORTS);
+
+  std::fs::remove_file(baseline).unwrap();
+}
+
+#[test]
+fn test_run_jsonl_reader_closed() {
+  use std::process::{Command, Stdio};
+
+  let mut child = Command::new(env!("CARGO_BIN_EXE_skanner"))
+    .args(["--ip", "127.0.0.1-127.0.3.255", "--ports", "1", "--no-discovery", "--include-down", "--format", "jsonl", "--quiet"])
+    .stdout(Stdio::piped())
+    .stderr(Stdio::piped())
+    .spawn()
+    .unwrap();
+  // Closed before the first host is written, as `head` does once it has its lines
+  drop(child.stdout.take());
+
+  let output = child.wait_with_output().unwrap();
+  let stderr = String::from_utf8_lossy(&output.stderr);
+  assert!(!stderr.contains("panicked"), "{}", stderr);
+  assert_ne!(output.status.code(), Some(101));
+  // The scan is cancelled instead of going through every host
+  assert!(stderr.contains("Scan interrupted after"), "{}", stderr);
+}